@@ -0,0 +1,259 @@
+//! Audits waiting to be performed, which auditors claim so that each is done once.
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, FixedOffset, Utc};
+use sea_orm::{
+    entity::prelude::*,
+    sea_query::{Expr, LockBehavior, LockType, OnConflict},
+    ActiveValue::NotSet,
+    Condition, DatabaseBackend, QueryOrder, QuerySelect, QueryTrait, Set, TransactionTrait,
+};
+
+use crate::content_audit::SelectionStrategy;
+
+/// Priority of audits that someone asked for, which are taken before any other queued audit.
+pub const REQUESTED_PRIORITY: i32 = 100;
+
+/// How long a claimed audit may go unfinished before it can be claimed again.
+pub const CLAIM_TIMEOUT_SECONDS: i64 = 300;
+
+/// Content waiting to be audited.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "audit_queue")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub content_key: i32,
+    pub strategy: SelectionStrategy,
+    /// Higher priorities are claimed first.
+    pub priority: i32,
+    pub created_at: DateTime<FixedOffset>,
+    /// When an auditor took the entry, absent while the entry is waiting.
+    pub claimed_at: Option<DateTime<FixedOffset>>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::content::Entity",
+        from = "Column::ContentKey",
+        to = "super::content::Column::Id",
+        on_update = "Cascade",
+        on_delete = "Cascade"
+    )]
+    Content,
+}
+
+impl Related<super::content::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Content.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+impl Model {
+    pub fn created_at_local_time(&self) -> String {
+        self.created_at.with_timezone(&chrono::Local).to_rfc2822()
+    }
+}
+
+/// Adds content to the queue, unless it is already queued.
+///
+/// Returns the entry for the content, which is the existing one if the content was already
+/// queued. Content has at most one entry, so auditors that select the same content at the
+/// same time add it once. An existing entry with a lower priority takes on the priority and
+/// strategy given here, and is dated now, so that a request for content a strategy already queued is not left
+/// waiting behind the strategy's other entries.
+pub async fn enqueue(
+    content_key_model_id: i32,
+    strategy: SelectionStrategy,
+    priority: i32,
+    conn: &DatabaseConnection,
+) -> Result<Model> {
+    let entry = ActiveModel {
+        id: NotSet,
+        content_key: Set(content_key_model_id),
+        strategy: Set(strategy.clone()),
+        priority: Set(priority),
+        created_at: Set(Utc::now().into()),
+        claimed_at: Set(None),
+    };
+    let on_conflict = match conn.get_database_backend() {
+        // MySQL has no `DO NOTHING`, so the existing entry is updated to what it already holds.
+        DatabaseBackend::MySql => OnConflict::column(Column::ContentKey)
+            .update_column(Column::ContentKey)
+            .to_owned(),
+        _ => OnConflict::column(Column::ContentKey)
+            .do_nothing()
+            .to_owned(),
+    };
+    Entity::insert(entry)
+        .on_conflict(on_conflict)
+        .exec_without_returning(conn)
+        .await?;
+    let entry = get_pending(content_key_model_id, conn)
+        .await?
+        .ok_or_else(|| anyhow!("Queued content {content_key_model_id} was removed"))?;
+    if entry.priority >= priority {
+        return Ok(entry);
+    }
+    // The priority condition keeps a concurrent higher priority in place.
+    Entity::update_many()
+        .col_expr(Column::Priority, Expr::value(priority))
+        .col_expr(Column::Strategy, Expr::value(strategy))
+        .col_expr(
+            Column::CreatedAt,
+            Expr::value(DateTime::<FixedOffset>::from(Utc::now())),
+        )
+        .filter(Column::Id.eq(entry.id))
+        .filter(Column::Priority.lt(priority))
+        .exec(conn)
+        .await?;
+    get_pending(content_key_model_id, conn)
+        .await?
+        .ok_or_else(|| anyhow!("Queued content {content_key_model_id} was removed"))
+}
+
+/// Returns the unfinished entry for the content, whether or not it has been claimed.
+pub async fn get_pending(
+    content_key_model_id: i32,
+    conn: &DatabaseConnection,
+) -> Result<Option<Model>> {
+    Ok(Entity::find()
+        .filter(Column::ContentKey.eq(content_key_model_id))
+        .one(conn)
+        .await?)
+}
+
+/// Counts the entries of a strategy that have not been claimed.
+pub async fn count_waiting(strategy: SelectionStrategy, conn: &DatabaseConnection) -> Result<u64> {
+    Ok(Entity::find()
+        .filter(Column::Strategy.eq(strategy))
+        .filter(Column::ClaimedAt.is_null())
+        .count(conn)
+        .await?)
+}
+
+/// Counts the entries of each strategy that has any, as waiting and claimed counts.
+///
+/// Claims that have expired are still counted as claimed.
+pub async fn get_depths(conn: &DatabaseConnection) -> Result<Vec<(SelectionStrategy, u64, u64)>> {
+    // Counting a column leaves out its nulls, so only claimed entries count toward `claimed`.
+    let depths: Vec<(SelectionStrategy, i64, i64)> = Entity::find()
+        .select_only()
+        .column(Column::Strategy)
+        .column_as(Column::Id.count(), "total")
+        .column_as(Column::ClaimedAt.count(), "claimed")
+        .group_by(Column::Strategy)
+        .into_tuple()
+        .all(conn)
+        .await?;
+    Ok(depths
+        .into_iter()
+        .map(|(strategy, total, claimed)| (strategy, (total - claimed) as u64, claimed as u64))
+        .collect())
+}
+
+/// Claims up to `limit` entries of the strategies, highest priority first and oldest first
+/// within a priority.
+///
+/// A claim is a lease: entries that were claimed longer than [CLAIM_TIMEOUT_SECONDS] ago are
+/// assumed to belong to an auditor that stopped, and can be claimed again. When several
+/// auditors claim at the same time, each entry goes to only one of them.
+pub async fn claim(
+    strategies: &[SelectionStrategy],
+    limit: u64,
+    conn: &DatabaseConnection,
+) -> Result<Vec<Model>> {
+    let stale = Utc::now() - chrono::Duration::seconds(CLAIM_TIMEOUT_SECONDS);
+    let candidates = Entity::find()
+        .filter(claimable(stale))
+        .filter(Column::Strategy.is_in(strategies.to_vec()))
+        .order_by_desc(Column::Priority)
+        .order_by_asc(Column::CreatedAt)
+        .limit(limit);
+    match conn.get_database_backend() {
+        DatabaseBackend::Postgres => claim_skip_locked(candidates, conn).await,
+        _ => claim_conditionally(candidates, stale, conn).await,
+    }
+}
+
+/// Locks the candidates, passing over rows that another auditor is claiming, and claims them.
+async fn claim_skip_locked(
+    mut candidates: Select<Entity>,
+    conn: &DatabaseConnection,
+) -> Result<Vec<Model>> {
+    QueryTrait::query(&mut candidates)
+        .lock_with_behavior(LockType::Update, LockBehavior::SkipLocked);
+    let txn = conn.begin().await?;
+    let candidates = candidates.all(&txn).await?;
+    let now: DateTime<FixedOffset> = Utc::now().into();
+    Entity::update_many()
+        .col_expr(Column::ClaimedAt, Expr::value(now))
+        .filter(Column::Id.is_in(candidates.iter().map(|candidate| candidate.id)))
+        .exec(&txn)
+        .await?;
+    txn.commit().await?;
+    Ok(candidates
+        .into_iter()
+        .map(|candidate| Model {
+            claimed_at: Some(now),
+            ..candidate
+        })
+        .collect())
+}
+
+/// Claims each candidate with an update that only applies if it is still claimable.
+///
+/// For databases without `SKIP LOCKED`. An auditor that loses a race for an entry claims
+/// fewer entries rather than waiting.
+async fn claim_conditionally(
+    candidates: Select<Entity>,
+    stale: DateTime<Utc>,
+    conn: &DatabaseConnection,
+) -> Result<Vec<Model>> {
+    let candidates = candidates.all(conn).await?;
+    let mut claimed = vec![];
+    for candidate in candidates {
+        let now: DateTime<FixedOffset> = Utc::now().into();
+        let result = Entity::update_many()
+            .col_expr(Column::ClaimedAt, Expr::value(now))
+            .filter(Column::Id.eq(candidate.id))
+            .filter(claimable(stale))
+            .exec(conn)
+            .await?;
+        if result.rows_affected == 1 {
+            claimed.push(Model {
+                claimed_at: Some(now),
+                ..candidate
+            });
+        }
+    }
+    Ok(claimed)
+}
+
+/// Entries that are waiting, or whose claim is older than `stale`.
+fn claimable(stale: DateTime<Utc>) -> Condition {
+    Condition::any()
+        .add(Column::ClaimedAt.is_null())
+        .add(Column::ClaimedAt.lt(stale))
+}
+
+/// Returns a claimed entry to the queue, so that it can be claimed again straight away.
+pub async fn release(id: i32, conn: &DatabaseConnection) -> Result<()> {
+    Entity::update_many()
+        .col_expr(
+            Column::ClaimedAt,
+            Expr::value(Option::<DateTime<FixedOffset>>::None),
+        )
+        .filter(Column::Id.eq(id))
+        .exec(conn)
+        .await?;
+    Ok(())
+}
+
+/// Removes a finished entry from the queue.
+pub async fn complete(id: i32, conn: &DatabaseConnection) -> Result<()> {
+    Entity::delete_by_id(id).exec(conn).await?;
+    Ok(())
+}
@@ -0,0 +1,40 @@
+//! Backfills that have finished, so that data saved before a column existed is filled in once.
+use anyhow::Result;
+use chrono::{DateTime, FixedOffset, Utc};
+use sea_orm::{entity::prelude::*, ActiveValue::NotSet, Set};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "backfill")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    /// Identifies the backfill, e.g. "link_client_info".
+    #[sea_orm(unique)]
+    pub name: String,
+    pub completed_at: DateTime<FixedOffset>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+/// Returns whether the backfill has already finished.
+pub async fn is_complete(name: &str, conn: &DatabaseConnection) -> Result<bool> {
+    Ok(Entity::find()
+        .filter(Column::Name.eq(name))
+        .one(conn)
+        .await?
+        .is_some())
+}
+
+/// Records that the backfill has finished.
+pub async fn mark_complete(name: &str, conn: &DatabaseConnection) -> Result<()> {
+    let backfill = ActiveModel {
+        id: NotSet,
+        name: Set(name.to_owned()),
+        completed_at: Set(Utc::now().into()),
+    };
+    backfill.insert(conn).await?;
+    Ok(())
+}
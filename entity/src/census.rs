@@ -0,0 +1,342 @@
+//! Sweeps of the whole network, and comparisons between the node sets they found.
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+
+use anyhow::Result;
+use chrono::{DateTime, FixedOffset};
+use sea_orm::{
+    entity::prelude::*, ActiveValue::NotSet, IntoActiveModel, QueryOrder, QuerySelect, Set,
+    TransactionTrait,
+};
+
+use serde::Serialize;
+use trin_utils::bytes::hex_encode;
+
+use crate::{census_node, client_info, node, node_liveness, record};
+
+/// A sweep of the whole DHT, made to find every node on the network.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "census")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub started_at: DateTime<FixedOffset>,
+    /// Only present once the census has finished, after which its node set is complete.
+    pub ended_at: Option<DateTime<FixedOffset>>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(has_many = "super::census_node::Entity")]
+    CensusNode,
+}
+
+impl Related<super::census_node::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::CensusNode.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+/// Records the start of a census.
+pub async fn create(conn: &DatabaseConnection) -> Result<Model> {
+    let census = ActiveModel {
+        id: NotSet,
+        started_at: Set(chrono::offset::Utc::now().into()),
+        ended_at: Set(None),
+    };
+    Ok(census.insert(conn).await?)
+}
+
+/// Saves the nodes that were found by the census, with the ENR each had, and marks it as
+/// finished.
+pub async fn finish(
+    census: Model,
+    enr_records: &[record::Model],
+    conn: &DatabaseConnection,
+) -> Result<Model> {
+    let txn = conn.begin().await?;
+    // Keeps each insert within the bound variable limit of SQLite.
+    for chunk in enr_records.chunks(256) {
+        census_node::Entity::insert_many(chunk.iter().map(|enr_record| census_node::ActiveModel {
+            id: NotSet,
+            census: Set(census.id),
+            node: Set(enr_record.node_id),
+            record: Set(Some(enr_record.id)),
+        }))
+        .exec(&txn)
+        .await?;
+    }
+    let mut census = census.into_active_model();
+    census.ended_at = Set(Some(chrono::offset::Utc::now().into()));
+    let census = census.update(&txn).await?;
+    txn.commit().await?;
+    Ok(census)
+}
+
+/// Returns the most recent censuses that have finished, newest first.
+pub async fn get_finished(limit: u64, conn: &DatabaseConnection) -> Result<Vec<Model>> {
+    Ok(Entity::find()
+        .filter(Column::EndedAt.is_not_null())
+        .order_by_desc(Column::StartedAt)
+        .limit(limit)
+        .all(conn)
+        .await?)
+}
+
+impl Model {
+    /// Number of nodes found by the census, which estimates the size of the network.
+    pub async fn node_count(&self, conn: &DatabaseConnection) -> Result<u64> {
+        Ok(census_node::Entity::find()
+            .filter(census_node::Column::Census.eq(self.id))
+            .count(conn)
+            .await?)
+    }
+
+    /// Counts the nodes found by the census by the name of the client implementation that
+    /// they advertised at the time.
+    pub async fn client_counts(&self, conn: &DatabaseConnection) -> Result<BTreeMap<String, u64>> {
+        let census_nodes = census_node::Entity::find()
+            .filter(census_node::Column::Census.eq(self.id))
+            .find_also_related(record::Entity)
+            .all(conn)
+            .await?;
+        let mut counts = BTreeMap::new();
+        for (_, enr_record) in census_nodes {
+            let version_info = enr_record
+                .and_then(|enr_record| enr_record.client)
+                .map(|enr_client| client_info::version_info_from_enr(&enr_client));
+            let name = version_info
+                .as_deref()
+                .map_or(client_info::UNKNOWN_CLIENT, client_info::client_name);
+            *counts.entry(name.to_owned()).or_insert(0) += 1;
+        }
+        Ok(counts)
+    }
+
+    pub fn started_at_local_time(&self) -> String {
+        self.started_at.with_timezone(&chrono::Local).to_rfc2822()
+    }
+
+    /// How long the census took, if it has finished.
+    pub fn duration(&self) -> Option<chrono::Duration> {
+        self.ended_at.map(|ended_at| ended_at - self.started_at)
+    }
+
+    pub fn duration_as_string(&self) -> String {
+        match self.duration() {
+            Some(duration) => format!("{}s", duration.num_seconds()),
+            None => "-".to_owned(),
+        }
+    }
+}
+
+/// What changed on the network between two censuses.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct CensusDiff {
+    pub old_census: i32,
+    pub new_census: i32,
+    /// Hex node ids of nodes found by the new census but not the old one.
+    pub joined: Vec<String>,
+    /// Hex node ids of nodes found by the old census but not the new one.
+    pub left: Vec<String>,
+    /// Nodes found by both censuses that had a different ENR in each.
+    pub changed: Vec<EnrChange>,
+}
+
+/// What changed on the network between two time windows, judged by the nodes that responded
+/// to liveness checks in each.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct WindowDiff {
+    pub old_start: DateTime<FixedOffset>,
+    pub old_end: DateTime<FixedOffset>,
+    pub new_start: DateTime<FixedOffset>,
+    pub new_end: DateTime<FixedOffset>,
+    /// Hex node ids of nodes seen in the new window but not the old one.
+    pub joined: Vec<String>,
+    /// Hex node ids of nodes seen in the old window but not the new one.
+    pub left: Vec<String>,
+    /// Nodes seen in both windows that were reached with a different ENR in each.
+    pub changed: Vec<EnrChange>,
+}
+
+impl WindowDiff {
+    pub fn old_start_local_time(&self) -> String {
+        self.old_start.with_timezone(&chrono::Local).to_rfc2822()
+    }
+
+    pub fn new_start_local_time(&self) -> String {
+        self.new_start.with_timezone(&chrono::Local).to_rfc2822()
+    }
+}
+
+/// Nodes that joined, left or changed ENR between two sets of nodes.
+struct NodeSetChanges {
+    joined: Vec<String>,
+    left: Vec<String>,
+    changed: Vec<EnrChange>,
+}
+
+/// A node whose ENR changed between two censuses.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct EnrChange {
+    pub node_id: String,
+    pub old_sequence_number: i32,
+    pub new_sequence_number: i32,
+    /// IPv4 UDP socket, as `ip:port`.
+    pub old_socket: Option<String>,
+    pub new_socket: Option<String>,
+    /// Client version advertised under the `c` key.
+    pub old_client: Option<String>,
+    pub new_client: Option<String>,
+}
+
+impl EnrChange {
+    fn new(node_id: String, old: &record::Model, new: &record::Model) -> Self {
+        EnrChange {
+            node_id,
+            old_sequence_number: old.sequence_number,
+            new_sequence_number: new.sequence_number,
+            old_socket: old.udp_socket(),
+            new_socket: new.udp_socket(),
+            old_client: old.client.clone(),
+            new_client: new.client.clone(),
+        }
+    }
+
+    pub fn socket_changed(&self) -> bool {
+        self.old_socket != self.new_socket
+    }
+
+    pub fn client_changed(&self) -> bool {
+        self.old_client != self.new_client
+    }
+}
+
+/// Compares the nodes found by two censuses, and the ENRs they had in each.
+///
+/// Nodes found before ENRs were recorded with the census are not checked for changes.
+pub async fn diff(old: &Model, new: &Model, conn: &DatabaseConnection) -> Result<CensusDiff> {
+    let old_nodes: BTreeMap<i32, Option<i32>> = census_node::get_for_census(old.id, conn)
+        .await?
+        .into_iter()
+        .map(|census_node| (census_node.node, census_node.record))
+        .collect();
+    let new_nodes: BTreeMap<i32, Option<i32>> = census_node::get_for_census(new.id, conn)
+        .await?
+        .into_iter()
+        .map(|census_node| (census_node.node, census_node.record))
+        .collect();
+    let changes = compare_node_sets(&old_nodes, &new_nodes, conn).await?;
+    Ok(CensusDiff {
+        old_census: old.id,
+        new_census: new.id,
+        joined: changes.joined,
+        left: changes.left,
+        changed: changes.changed,
+    })
+}
+
+/// Compares the nodes that responded to liveness checks in two time windows, and the ENRs
+/// they were reached with.
+///
+/// Unlike censuses, this works whichever mode the cartographer runs in, as liveness is checked
+/// in all of them.
+pub async fn diff_windows(
+    old: Range<DateTime<FixedOffset>>,
+    new: Range<DateTime<FixedOffset>>,
+    conn: &DatabaseConnection,
+) -> Result<WindowDiff> {
+    let to_nodes = |seen: BTreeMap<i32, i32>| -> BTreeMap<i32, Option<i32>> {
+        seen.into_iter()
+            .map(|(node, enr_record)| (node, Some(enr_record)))
+            .collect()
+    };
+    let old_nodes = to_nodes(node_liveness::get_seen(old.start, old.end, conn).await?);
+    let new_nodes = to_nodes(node_liveness::get_seen(new.start, new.end, conn).await?);
+    let changes = compare_node_sets(&old_nodes, &new_nodes, conn).await?;
+    Ok(WindowDiff {
+        old_start: old.start,
+        old_end: old.end,
+        new_start: new.start,
+        new_end: new.end,
+        joined: changes.joined,
+        left: changes.left,
+        changed: changes.changed,
+    })
+}
+
+/// Compares two sets of nodes, each mapped to the ENR it had, if known.
+async fn compare_node_sets(
+    old_nodes: &BTreeMap<i32, Option<i32>>,
+    new_nodes: &BTreeMap<i32, Option<i32>>,
+    conn: &DatabaseConnection,
+) -> Result<NodeSetChanges> {
+    let joined: Vec<i32> = new_nodes
+        .keys()
+        .filter(|node| !old_nodes.contains_key(node))
+        .copied()
+        .collect();
+    let left: Vec<i32> = old_nodes
+        .keys()
+        .filter(|node| !new_nodes.contains_key(node))
+        .copied()
+        .collect();
+    let changed: Vec<(i32, i32, i32)> = old_nodes
+        .iter()
+        .filter_map(
+            |(node, old_record)| match (old_record, new_nodes.get(node)) {
+                (Some(old_record), Some(Some(new_record))) if old_record != new_record => {
+                    Some((*node, *old_record, *new_record))
+                }
+                _ => None,
+            },
+        )
+        .collect();
+
+    let node_ids: HashMap<i32, String> = node::Entity::find()
+        .filter(
+            node::Column::Id.is_in(
+                joined
+                    .iter()
+                    .chain(left.iter())
+                    .chain(changed.iter().map(|(node, _, _)| node))
+                    .copied(),
+            ),
+        )
+        .all(conn)
+        .await?
+        .into_iter()
+        .map(|node_model| (node_model.id, hex_encode(&node_model.node_id)))
+        .collect();
+    let records: HashMap<i32, record::Model> = record::Entity::find()
+        .filter(
+            record::Column::Id.is_in(
+                changed
+                    .iter()
+                    .flat_map(|(_, old_record, new_record)| [*old_record, *new_record]),
+            ),
+        )
+        .all(conn)
+        .await?
+        .into_iter()
+        .map(|enr_record| (enr_record.id, enr_record))
+        .collect();
+
+    let hex_node_id = |node: &i32| node_ids.get(node).cloned().unwrap_or_default();
+    Ok(NodeSetChanges {
+        joined: joined.iter().map(hex_node_id).collect(),
+        left: left.iter().map(hex_node_id).collect(),
+        changed: changed
+            .iter()
+            .filter_map(|(node, old_record, new_record)| {
+                Some(EnrChange::new(
+                    hex_node_id(node),
+                    records.get(old_record)?,
+                    records.get(new_record)?,
+                ))
+            })
+            .collect(),
+    })
+}
@@ -0,0 +1,83 @@
+//! The nodes found by a census, each with the record it was found at.
+use anyhow::Result;
+use sea_orm::{entity::prelude::*, QueryOrder, QuerySelect};
+
+/// A node that was found during a census.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "census_node")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub census: i32,
+    pub node: i32,
+    /// The ENR the node had when it was found, absent for nodes found before this was recorded.
+    pub record: Option<i32>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::census::Entity",
+        from = "Column::Census",
+        to = "super::census::Column::Id",
+        on_update = "Cascade",
+        on_delete = "Cascade"
+    )]
+    Census,
+    #[sea_orm(
+        belongs_to = "super::node::Entity",
+        from = "Column::Node",
+        to = "super::node::Column::Id",
+        on_update = "Cascade",
+        on_delete = "Cascade"
+    )]
+    Node,
+    #[sea_orm(
+        belongs_to = "super::record::Entity",
+        from = "Column::Record",
+        to = "super::record::Column::Id",
+        on_update = "Cascade",
+        on_delete = "Cascade"
+    )]
+    Record,
+}
+
+impl Related<super::census::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Census.def()
+    }
+}
+
+impl Related<super::node::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Node.def()
+    }
+}
+
+impl Related<super::record::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Record.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+/// Returns the ids of the node models found by the census, in ascending order.
+pub async fn get_node_ids(census_id: i32, conn: &DatabaseConnection) -> Result<Vec<i32>> {
+    Ok(Entity::find()
+        .select_only()
+        .column(Column::Node)
+        .filter(Column::Census.eq(census_id))
+        .order_by_asc(Column::Node)
+        .into_tuple()
+        .all(conn)
+        .await?)
+}
+
+/// Returns the nodes found by the census.
+pub async fn get_for_census(census_id: i32, conn: &DatabaseConnection) -> Result<Vec<Model>> {
+    Ok(Entity::find()
+        .filter(Column::Census.eq(census_id))
+        .all(conn)
+        .await?)
+}
@@ -1,7 +1,9 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.10.7
+use std::collections::BTreeMap;
+
 use anyhow::Result;
 
-use sea_orm::{entity::prelude::*, ActiveValue::NotSet, Set};
+use sea_orm::{entity::prelude::*, ActiveValue::NotSet, QuerySelect, Set};
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
 #[sea_orm(table_name = "client_info")]
@@ -33,6 +35,64 @@ impl Related<super::node::Entity> for Entity {
 
 impl ActiveModelBehavior for ActiveModel {}
 
+impl Model {
+    /// The name of the client implementation, e.g. "trin".
+    pub fn client_name(&self) -> &str {
+        client_name(&self.version_info)
+    }
+}
+
+/// Used when a node does not advertise its client.
+pub const UNKNOWN_CLIENT: &str = "unknown";
+
+/// Converts the value of an ENR `c` key to the same form as a `web3_clientVersion`
+/// response, which starts with the client name.
+///
+/// The key holds a short client identifier followed by the version, e.g. "t 0.1.1-alpha.1".
+pub fn version_info_from_enr(enr_client: &str) -> String {
+    let mut parts = enr_client.trim().splitn(2, ' ');
+    let name = match parts.next().unwrap_or_default() {
+        "t" => "trin",
+        "f" => "fluffy",
+        "u" => "ultralight",
+        "s" => "shisui",
+        "" => UNKNOWN_CLIENT,
+        other => other,
+    };
+    match parts.next().map(str::trim) {
+        Some(version) if !version.is_empty() => format!("{name} {version}"),
+        _ => name.to_owned(),
+    }
+}
+
+/// The name of the client implementation from version info, e.g. "trin" from "trin v0.1.0".
+pub fn client_name(version_info: &str) -> &str {
+    version_info
+        .split_whitespace()
+        .next()
+        .unwrap_or(UNKNOWN_CLIENT)
+}
+
+/// Counts the known nodes by the name of the client implementation they advertise.
+pub async fn get_node_counts(conn: &DatabaseConnection) -> Result<BTreeMap<String, u64>> {
+    // Nodes are counted by version in the database, then versions are combined by client name.
+    let version_counts: Vec<(Option<String>, i64)> = super::node::Entity::find()
+        .select_only()
+        .left_join(Entity)
+        .column(Column::VersionInfo)
+        .column_as(super::node::Column::Id.count(), "count")
+        .group_by(Column::VersionInfo)
+        .into_tuple()
+        .all(conn)
+        .await?;
+    let mut counts = BTreeMap::new();
+    for (version_info, count) in version_counts {
+        let name = version_info.as_deref().map_or(UNKNOWN_CLIENT, client_name);
+        *counts.entry(name.to_owned()).or_insert(0) += count as u64;
+    }
+    Ok(counts)
+}
+
 pub async fn get_or_create(version_info: String, conn: &DatabaseConnection) -> Result<Model> {
     // First try to lookup an existing entry.
     if let Some(client_info) = Entity::find()
@@ -1,5 +1,5 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.10.7
-use anyhow::Result;
+use anyhow::{ensure, Result};
 use chrono::{DateTime, FixedOffset, Utc};
 use ethereum_types::H256;
 use ethportal_api::types::content_key::OverlayContentKey;
@@ -59,6 +59,22 @@ impl Related<super::execution_metadata::Entity> for Entity {
 
 impl ActiveModelBehavior for ActiveModel {}
 
+/// The most prefix bits that ids can be bucketed by, which gives 65536 keyspace buckets.
+pub const MAX_KEYSPACE_PREFIX_BITS: u8 = 16;
+
+/// Returns the keyspace bucket of a content or node id, which is its first `prefix_bits` bits.
+pub fn keyspace_bucket(id: &[u8], prefix_bits: u8) -> Result<usize> {
+    ensure!(
+        prefix_bits <= MAX_KEYSPACE_PREFIX_BITS,
+        "Cannot bucket ids by more than {MAX_KEYSPACE_PREFIX_BITS} bits"
+    );
+    let mut high_bytes = [0u8; 2];
+    for (byte, id_byte) in high_bytes.iter_mut().zip(id) {
+        *byte = *id_byte;
+    }
+    Ok((u32::from(u16::from_be_bytes(high_bytes)) >> (16 - prefix_bits)) as usize)
+}
+
 pub async fn get_or_create<T: OverlayContentKey>(
     content_key: &T,
     conn: &DatabaseConnection,
@@ -1,11 +1,22 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.10.7
-use anyhow::{bail, Result};
-use chrono::{DateTime, FixedOffset};
+use std::collections::HashMap;
+
+use anyhow::{bail, ensure, Result};
+use chrono::{DateTime, FixedOffset, Utc};
 use clap::ValueEnum;
 use ethportal_api::types::content_key::OverlayContentKey;
-use sea_orm::{entity::prelude::*, ActiveValue::NotSet, Set};
+use sea_orm::{
+    entity::prelude::*,
+    sea_query::{Alias, CaseStatement, Cond, Expr, Func, SimpleExpr},
+    ActiveValue::NotSet,
+    Iterable, JoinType, QuerySelect, Set,
+};
+use serde_json::Value;
 
-use crate::content;
+use crate::{
+    client_info, content,
+    execution_metadata::{self, BlockAgeBand},
+};
 
 #[derive(Debug, Clone, Eq, PartialEq, EnumIter, DeriveActiveEnum)]
 #[sea_orm(rs_type = "i32", db_type = "Integer")]
@@ -34,6 +45,58 @@ pub enum SelectionStrategy {
     /// 1. Not yet audited.
     /// 2. Sorted by date entered into glados database (oldest first).
     SelectOldestUnaudited = 3,
+    /// Content that is:
+    /// 1. Spread evenly across buckets of the content id keyspace.
+    /// 2. Taken first from the buckets with the fewest recent audits.
+    KeyspaceStratified = 4,
+    /// Content that is:
+    /// 1. Spread evenly across bands of block age, from the last hour back to before the merge.
+    /// 2. Picked at random within each band.
+    BlockAgeStratified = 5,
+    /// Randomly selected block headers.
+    BlockHeaders = 6,
+    /// Randomly selected block bodies.
+    BlockBodies = 7,
+    /// Randomly selected block receipts.
+    Receipts = 8,
+    /// Randomly selected epoch accumulators.
+    EpochAccumulators = 9,
+    /// Content that someone asked to have audited, rather than content chosen by a strategy.
+    #[value(skip)]
+    Requested = 10,
+}
+
+/// What a traced audit observed a node doing with the audited content.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TraceObservation {
+    /// The node sent the content.
+    Served,
+    /// The node answered with other nodes instead of the content.
+    Missing,
+}
+
+/// The audits of a group of content, such as the content in one keyspace bucket.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct AuditCounts {
+    pub total: u64,
+    pub passes: u64,
+}
+
+impl AuditCounts {
+    /// The fraction of audits that passed, as a percentage, or None if nothing was audited.
+    pub fn success_rate(&self) -> Option<f64> {
+        match self.total {
+            0 => None,
+            total => Some(self.passes as f64 / total as f64 * 100.0),
+        }
+    }
+
+    pub fn success_rate_as_string(&self) -> String {
+        match self.success_rate() {
+            Some(rate) => format!("{rate:.1}%"),
+            None => "No audits".to_string(),
+        }
+    }
 }
 
 impl AuditResult {
@@ -144,6 +207,154 @@ pub async fn get_audits<T: OverlayContentKey>(
         .await?)
 }
 
+/// Counts the audits made since `since` by result, strategy and the name of the audited client.
+///
+/// Audits without a recorded client are counted under [client_info::UNKNOWN_CLIENT].
+pub async fn get_result_counts(
+    since: DateTime<Utc>,
+    conn: &DatabaseConnection,
+) -> Result<Vec<(AuditResult, Option<SelectionStrategy>, String, u64)>> {
+    let version_counts: Vec<(AuditResult, Option<SelectionStrategy>, Option<String>, i64)> =
+        Entity::find()
+            .select_only()
+            .column(Column::Result)
+            .column(Column::StrategyUsed)
+            .column(client_info::Column::VersionInfo)
+            .column_as(Column::Id.count(), "count")
+            .left_join(client_info::Entity)
+            .filter(Column::CreatedAt.gt(since))
+            .group_by(Column::Result)
+            .group_by(Column::StrategyUsed)
+            .group_by(client_info::Column::VersionInfo)
+            .into_tuple()
+            .all(conn)
+            .await?;
+
+    // Versions of the same client are counted together.
+    let mut counts: Vec<(AuditResult, Option<SelectionStrategy>, String, u64)> = vec![];
+    for (result, strategy, version_info, count) in version_counts {
+        // Version info from some clients is quoted.
+        let name = version_info
+            .as_deref()
+            .map(|version_info| client_info::client_name(version_info.trim_matches('"')))
+            .unwrap_or(client_info::UNKNOWN_CLIENT);
+        match counts
+            .iter_mut()
+            .find(|(r, s, n, _)| *r == result && *s == strategy && n == name)
+        {
+            Some((_, _, _, total)) => *total += count as u64,
+            None => counts.push((result, strategy, name.to_owned(), count as u64)),
+        }
+    }
+    Ok(counts)
+}
+
+/// Counts the audits made since `since` in each keyspace bucket, where audits are bucketed
+/// by the first `prefix_bits` bits of the audited content id. Empty buckets are included.
+pub async fn get_keyspace_counts(
+    prefix_bits: u8,
+    since: DateTime<Utc>,
+    conn: &DatabaseConnection,
+) -> Result<Vec<AuditCounts>> {
+    ensure!(
+        prefix_bits <= content::MAX_KEYSPACE_PREFIX_BITS,
+        "Cannot bucket audits by more than {} bits",
+        content::MAX_KEYSPACE_PREFIX_BITS
+    );
+    // Audits are counted by the leading bytes of the content id that hold the prefix, then
+    // the counts are combined by bucket.
+    let prefix_bytes = i32::from(prefix_bits.max(1) + 7) / 8;
+    let prefix = SimpleExpr::from(Func::cust(Alias::new("SUBSTR")).args([
+        Expr::col((content::Entity, content::Column::ContentId)).into(),
+        Expr::val(1).into(),
+        Expr::val(prefix_bytes).into(),
+    ]));
+    let prefix_counts: Vec<(Vec<u8>, AuditResult, i64)> = Entity::find()
+        .select_only()
+        .column_as(prefix, "prefix")
+        .column(Column::Result)
+        .column_as(Column::Id.count(), "count")
+        .inner_join(content::Entity)
+        .filter(Column::CreatedAt.gt(since))
+        // Grouping by the alias, as Postgres does not match an expression with bound values.
+        .group_by(Expr::cust("prefix"))
+        .group_by(Column::Result)
+        .into_tuple()
+        .all(conn)
+        .await?;
+
+    let mut counts = vec![AuditCounts::default(); 1 << prefix_bits];
+    for (prefix, result, count) in prefix_counts {
+        let bucket = &mut counts[content::keyspace_bucket(&prefix, prefix_bits)?];
+        bucket.total += count as u64;
+        if result == AuditResult::Success {
+            bucket.passes += count as u64;
+        }
+    }
+    Ok(counts)
+}
+
+/// Counts the audits made since `since` in each block age band, where audits are banded by
+/// the block number of the audited content. Content without a block number is left out.
+pub async fn get_block_age_counts(
+    since: DateTime<Utc>,
+    conn: &DatabaseConnection,
+) -> Result<Vec<(BlockAgeBand, AuditCounts)>> {
+    let Some(latest_block) = execution_metadata::get_latest_block_number(conn).await? else {
+        return Ok(BlockAgeBand::iter()
+            .map(|band| (band, AuditCounts::default()))
+            .collect());
+    };
+    let mut counts: Vec<(BlockAgeBand, AuditCounts)> = BlockAgeBand::iter()
+        .map(|band| (band, AuditCounts::default()))
+        .collect();
+    // Gives the position of the band that the block number falls in.
+    let mut band_index: Option<CaseStatement> = None;
+    for (index, (band, _)) in counts.iter().enumerate() {
+        let blocks = band.block_range(latest_block);
+        let in_band = Cond::all()
+            .add(execution_metadata::Column::BlockNumber.gte(blocks.start))
+            .add(execution_metadata::Column::BlockNumber.lt(blocks.end));
+        let index = index as i32;
+        band_index = Some(match band_index {
+            Some(case) => case.case(in_band, index),
+            None => Expr::case(in_band, index),
+        });
+    }
+    let Some(band_index) = band_index else {
+        return Ok(counts);
+    };
+    let band_index: SimpleExpr = band_index.into();
+    let band_counts: Vec<(Option<i32>, AuditResult, i64)> = Entity::find()
+        .select_only()
+        .column_as(band_index, "band")
+        .column(Column::Result)
+        .column_as(Column::Id.count(), "count")
+        .inner_join(content::Entity)
+        .join(
+            JoinType::InnerJoin,
+            content::Relation::ExecutionMetadata.def(),
+        )
+        .filter(Column::CreatedAt.gt(since))
+        // Grouping by the alias, as Postgres does not match an expression with bound values.
+        .group_by(Expr::cust("band"))
+        .group_by(Column::Result)
+        .into_tuple()
+        .all(conn)
+        .await?;
+
+    for (index, result, count) in band_counts {
+        let Some((_, band_counts)) = index.and_then(|index| counts.get_mut(index as usize)) else {
+            continue;
+        };
+        band_counts.total += count as u64;
+        if result == AuditResult::Success {
+            band_counts.passes += count as u64;
+        }
+    }
+    Ok(counts)
+}
+
 impl SelectionStrategy {
     /// This performs the function of Display, which is not able to be implemented
     /// for this enum.
@@ -156,6 +367,13 @@ impl SelectionStrategy {
             SelectionStrategy::Random => "Random".to_string(),
             SelectionStrategy::Failed => "Failed".to_string(),
             SelectionStrategy::SelectOldestUnaudited => "Select Oldest Unaudited".to_string(),
+            SelectionStrategy::KeyspaceStratified => "Keyspace Stratified".to_string(),
+            SelectionStrategy::BlockAgeStratified => "Block Age Stratified".to_string(),
+            SelectionStrategy::BlockHeaders => "Block Headers".to_string(),
+            SelectionStrategy::BlockBodies => "Block Bodies".to_string(),
+            SelectionStrategy::Receipts => "Receipts".to_string(),
+            SelectionStrategy::EpochAccumulators => "Epoch Accumulators".to_string(),
+            SelectionStrategy::Requested => "Requested".to_string(),
         }
     }
 }
@@ -167,6 +385,30 @@ impl Model {
     pub fn created_at_local_time(&self) -> String {
         self.created_at.with_timezone(&chrono::Local).to_rfc2822()
     }
+    /// Reads the trace to find which of the contacted nodes sent the content, and which
+    /// answered without it. Nodes that did not answer are left out.
+    ///
+    /// Node ids are lowercase hex with a `0x` prefix. Audits without a trace return nothing.
+    pub fn trace_observations(&self) -> HashMap<String, TraceObservation> {
+        let mut observations = HashMap::new();
+        let Ok(trace) = serde_json::from_str::<Value>(&self.trace) else {
+            return observations;
+        };
+        let served_by = trace["received_content_from_node"]
+            .as_str()
+            .map(normalize_node_id);
+        if let Some(responses) = trace["responses"].as_object() {
+            for (node_id, response) in responses {
+                if response["responded_with"].is_array() {
+                    observations.insert(normalize_node_id(node_id), TraceObservation::Missing);
+                }
+            }
+        }
+        if let Some(served_by) = served_by {
+            observations.insert(served_by, TraceObservation::Served);
+        }
+        observations
+    }
     /// A convenience method for displaying the strategy.
     ///
     /// A few early databse entries do not have a recorded strategy.
@@ -177,3 +419,11 @@ impl Model {
         }
     }
 }
+
+fn normalize_node_id(node_id: &str) -> String {
+    let node_id = node_id.to_lowercase();
+    match node_id.starts_with("0x") {
+        true => node_id,
+        false => format!("0x{node_id}"),
+    }
+}
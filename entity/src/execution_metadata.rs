@@ -1,7 +1,64 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.10.7
 
+use std::ops::Range;
+
 use anyhow::Result;
-use sea_orm::{entity::prelude::*, ActiveValue::NotSet, Set};
+use sea_orm::{entity::prelude::*, ActiveValue::NotSet, Iterable, QueryOrder, QuerySelect, Set};
+
+/// The first block produced after the merge.
+pub const MERGE_BLOCK_NUMBER: i32 = 15_537_394;
+
+/// Post-merge blocks are produced every 12 seconds.
+const BLOCKS_PER_HOUR: i32 = 300;
+
+/// A range of block ages, measured back from the latest block that glados knows of.
+#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq, EnumIter)]
+pub enum BlockAgeBand {
+    LastHour,
+    LastDay,
+    LastWeek,
+    LastMonth,
+    /// Older than a month, but after the merge.
+    PostMerge,
+    PreMerge,
+}
+
+impl BlockAgeBand {
+    pub fn as_text(&self) -> String {
+        match self {
+            BlockAgeBand::LastHour => "Last hour".to_string(),
+            BlockAgeBand::LastDay => "Last day".to_string(),
+            BlockAgeBand::LastWeek => "Last week".to_string(),
+            BlockAgeBand::LastMonth => "Last month".to_string(),
+            BlockAgeBand::PostMerge => "Post-merge".to_string(),
+            BlockAgeBand::PreMerge => "Pre-merge".to_string(),
+        }
+    }
+
+    /// The block numbers in the band. Each band starts where the next older band ends,
+    /// and a band is empty if the chain is too short to reach it.
+    pub fn block_range(&self, latest_block: i32) -> Range<i32> {
+        let end = latest_block.saturating_add(1);
+        // The oldest block that is still in each band, from newest band to oldest.
+        let starts = [
+            end.saturating_sub(BLOCKS_PER_HOUR),
+            end.saturating_sub(BLOCKS_PER_HOUR * 24),
+            end.saturating_sub(BLOCKS_PER_HOUR * 24 * 7),
+            end.saturating_sub(BLOCKS_PER_HOUR * 24 * 30),
+            MERGE_BLOCK_NUMBER,
+            0,
+        ];
+        let mut band_end = end;
+        for (band, start) in BlockAgeBand::iter().zip(starts) {
+            let band_start = start.clamp(0, band_end);
+            if band == *self {
+                return band_start..band_end;
+            }
+            band_end = band_start;
+        }
+        unreachable!("Every band has a start")
+    }
+}
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
 #[sea_orm(table_name = "execution_metadata")]
@@ -77,3 +134,15 @@ pub async fn get(
         .one(conn)
         .await
 }
+
+/// Returns the highest block number of any stored content.
+pub async fn get_latest_block_number(conn: &DatabaseConnection) -> Result<Option<i32>> {
+    Ok(Entity::find()
+        .select_only()
+        .column(Column::BlockNumber)
+        .order_by_desc(Column::BlockNumber)
+        .limit(1)
+        .into_tuple()
+        .one(conn)
+        .await?)
+}
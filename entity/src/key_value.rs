@@ -19,6 +19,11 @@ impl Model {
         hex_encode(&self.key)
     }
 
+    /// ENR keys are usually ASCII, such as "udp".
+    pub fn key_as_string(&self) -> String {
+        String::from_utf8_lossy(&self.key).into_owned()
+    }
+
     pub fn value_as_hex(&self) -> String {
         hex_encode(&self.value)
     }
@@ -2,12 +2,19 @@
 
 pub mod prelude;
 
+pub mod audit_queue;
+pub mod backfill;
+pub mod census;
+pub mod census_node;
 pub mod client_info;
 pub mod content;
 pub mod content_audit;
 pub mod execution_metadata;
 pub mod key_value;
 pub mod node;
+pub mod node_liveness;
+pub mod node_radius;
+pub mod node_storage_audit;
 pub mod record;
 
 pub mod test;
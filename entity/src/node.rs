@@ -1,14 +1,15 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.10.7
-use anyhow::Result;
+use anyhow::{ensure, Result};
 use ethereum_types::U256;
 use trin_types::node_id::NodeId;
 use trin_utils::bytes::hex_encode;
 
 use sea_orm::{
-    entity::prelude::*, ActiveValue::NotSet, DatabaseBackend, FromQueryResult, QueryOrder,
-    QuerySelect, Set,
+    entity::prelude::*, ActiveValue::NotSet, DatabaseBackend, QueryOrder, QuerySelect, Set,
 };
-use sea_query::Expr;
+use sea_query::{Expr, SimpleExpr};
+
+use crate::content;
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
 #[sea_orm(table_name = "node")]
@@ -17,6 +18,8 @@ pub struct Model {
     pub id: i32,
     pub node_id: Vec<u8>,
     pub node_id_high: i64,
+    /// The client implementation advertised in the node's latest ENR.
+    pub client_info: Option<i32>,
 }
 
 impl Model {
@@ -29,26 +32,53 @@ impl Model {
     }
 }
 
-#[derive(FromQueryResult)]
+/// A node and its exact XOR distance from some target.
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct ModelWithDistance {
     pub id: i32,
     pub node_id: Vec<u8>,
     pub node_id_high: i64,
-    pub distance: i64,
+    pub client_info: Option<i32>,
+    pub distance: U256,
 }
 
 impl ModelWithDistance {
+    fn new(node_model: Model, target: U256) -> Self {
+        ModelWithDistance {
+            distance: U256::from_big_endian(&node_model.node_id) ^ target,
+            id: node_model.id,
+            node_id: node_model.node_id,
+            node_id_high: node_model.node_id_high,
+            client_info: node_model.client_info,
+        }
+    }
+
     pub fn node_id_as_hex(&self) -> String {
         hex_encode(&self.node_id)
     }
+
+    /// The base 2 logarithm of the distance, as used for routing table buckets.
+    ///
+    /// Returns 0 for the target itself, and otherwise a value from 1 to 256.
+    pub fn log_distance(&self) -> u16 {
+        (256 - self.distance.leading_zeros()) as u16
+    }
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
     #[sea_orm(has_many = "super::record::Entity")]
     Record,
-    #[sea_orm(has_one = "super::client_info::Entity")]
+    #[sea_orm(
+        belongs_to = "super::client_info::Entity",
+        from = "Column::ClientInfo",
+        to = "super::client_info::Column::Id",
+        on_update = "Cascade",
+        on_delete = "SetNull"
+    )]
     ClientInfo,
+    #[sea_orm(has_many = "super::node_liveness::Entity")]
+    NodeLiveness,
 }
 
 impl Related<super::record::Entity> for Entity {
@@ -63,50 +93,115 @@ impl Related<super::client_info::Entity> for Entity {
     }
 }
 
-impl ActiveModelBehavior for ActiveModel {}
-
-#[derive(Copy, Clone, Debug, EnumIter, DeriveColumn)]
-enum ComputedColumn {
-    Distance,
+impl Related<super::node_liveness::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::NodeLiveness.def()
+    }
 }
 
-impl ColumnTrait for ComputedColumn {
-    type EntityName = Entity;
+impl ActiveModelBehavior for ActiveModel {}
 
-    fn def(&self) -> ColumnDef {
-        match self {
-            Self::Distance => ColumnType::Integer.def(),
+/// XOR distance between the high bits of each node id and of the target, as a SQL expression.
+fn high_bits_distance(target_high: i64, backend: DatabaseBackend) -> SimpleExpr {
+    match backend {
+        // SQLite has no XOR operator, but for non-negative values it is the same as this.
+        DatabaseBackend::Sqlite => Expr::cust_with_values(
+            "(\"node\".\"node_id_high\" | ?) - (\"node\".\"node_id_high\" & ?)",
+            [target_high, target_high],
+        ),
+        DatabaseBackend::Postgres => {
+            Expr::cust_with_values("(\"node\".\"node_id_high\" # $1)", [target_high])
+        }
+        DatabaseBackend::MySql => {
+            Expr::cust_with_values("(`node`.`node_id_high` ^ ?)", [target_high])
         }
     }
 }
 
+/// Returns the `count` known nodes closest to the target by XOR distance, closest first.
+///
+/// The database ranks nodes by the high bits of their node ids. Nodes that are closer in
+/// the high bits are also closer overall, so only nodes that tie with the furthest node
+/// that is kept need to be compared in full, which is done with the exact distance.
 pub async fn closest_xor(
-    node_id: NodeId,
+    target: NodeId,
+    count: u64,
     conn: &DatabaseConnection,
 ) -> Result<Vec<ModelWithDistance>> {
-    let raw_node_id = U256::from_big_endian(node_id.raw().as_slice());
-    let node_id_high: i64 = (raw_node_id >> 193).as_u64().try_into().unwrap();
+    if count == 0 {
+        return Ok(vec![]);
+    }
+    let target = U256::from_big_endian(target.raw().as_slice());
+    let target_high: i64 = (target >> 193).as_u64().try_into().unwrap();
+    let distance = high_bits_distance(target_high, conn.get_database_backend());
+
+    let furthest_high_distance: Option<i64> = Entity::find()
+        .select_only()
+        .column_as(distance.clone(), "distance")
+        .order_by_asc(distance.clone())
+        .offset(count - 1)
+        .limit(1)
+        .into_tuple()
+        .one(conn)
+        .await?;
+    let mut candidates = Entity::find();
+    if let Some(furthest_high_distance) = furthest_high_distance {
+        candidates = candidates.filter(Expr::expr(distance).lte(furthest_high_distance));
+    }
 
-    let distance_expression = match conn.get_database_backend() {
-        DatabaseBackend::Sqlite => Expr::cust_with_values(
-            "(\"node\".\"node_id_high\" | ?1) - (\"node\".\"node_id_high\" & ?2)",
-            [node_id_high, node_id_high],
-        ),
-        DatabaseBackend::Postgres => Expr::cust_with_values(
-            "(\"node\".\"node_id_high\" | $1) - (\"node\".\"node_id_high\" & $2)",
-            [node_id_high, node_id_high],
-        ),
-        DatabaseBackend::MySql => panic!("Unsupported"),
-    };
+    let mut nodes: Vec<ModelWithDistance> = candidates
+        .all(conn)
+        .await?
+        .into_iter()
+        .map(|node_model| ModelWithDistance::new(node_model, target))
+        .collect();
+    nodes.sort_by_key(|node| node.distance);
+    nodes.truncate(count.try_into().unwrap_or(usize::MAX));
+    Ok(nodes)
+}
+
+/// Returns the `count` known nodes closest to the content, which are the nodes that are
+/// most likely to store it, closest first.
+pub async fn closest_to_content(
+    content_id: [u8; 32],
+    count: u64,
+    conn: &DatabaseConnection,
+) -> Result<Vec<ModelWithDistance>> {
+    closest_xor(NodeId(content_id), count, conn).await
+}
 
-    let nodes = Entity::find()
-        .column_as(distance_expression, "distance")
-        .order_by_asc(Expr::col(ComputedColumn::Distance))
-        .limit(100)
-        .into_model::<ModelWithDistance>()
+/// Counts the known nodes in each keyspace bucket, where nodes are bucketed by the first
+/// `prefix_bits` bits of their node id. Empty buckets are included.
+pub async fn get_keyspace_counts(prefix_bits: u8, conn: &DatabaseConnection) -> Result<Vec<u64>> {
+    ensure!(
+        prefix_bits <= content::MAX_KEYSPACE_PREFIX_BITS,
+        "Cannot bucket nodes by more than {} bits",
+        content::MAX_KEYSPACE_PREFIX_BITS
+    );
+    // The high bits column holds the first 63 bits of the node id.
+    let divisor = 1i64 << (63 - prefix_bits);
+    let bucket = match conn.get_database_backend() {
+        // MySQL division gives a decimal, so integer division needs its own operator.
+        DatabaseBackend::MySql => {
+            Expr::cust_with_values("(`node`.`node_id_high` DIV ?)", [divisor])
+        }
+        _ => Expr::col(Column::NodeIdHigh).div(divisor),
+    };
+    let bucket_counts: Vec<(i64, i64)> = Entity::find()
+        .select_only()
+        .column_as(bucket, "bucket")
+        .column_as(Column::Id.count(), "count")
+        // Grouping by the alias, as Postgres does not match an expression with bound values.
+        .group_by(Expr::cust("bucket"))
+        .into_tuple()
         .all(conn)
         .await?;
-    Ok(nodes)
+
+    let mut counts = vec![0; 1 << prefix_bits];
+    for (bucket, count) in bucket_counts {
+        counts[bucket as usize] = count as u64;
+    }
+    Ok(counts)
 }
 
 pub async fn get_or_create(node_id: NodeId, conn: &DatabaseConnection) -> Result<Model> {
@@ -124,13 +219,26 @@ pub async fn get_or_create(node_id: NodeId, conn: &DatabaseConnection) -> Result
     let raw_node_id = U256::from_big_endian(node_id.raw().as_slice());
     let node_id_high: i64 = (raw_node_id >> 193).as_u64().try_into().unwrap();
 
-    // let client_info = client_info::get_or_create(,&conn)
-
     let node_id_model = ActiveModel {
         id: NotSet,
         node_id: Set(node_id.raw().into()),
         node_id_high: Set(node_id_high),
+        client_info: Set(None),
     };
 
     Ok(node_id_model.insert(conn).await?)
 }
+
+/// Records the client implementation that the node advertises.
+pub async fn set_client_info(
+    node_model_id: i32,
+    client_info_id: i32,
+    conn: &DatabaseConnection,
+) -> Result<()> {
+    Entity::update_many()
+        .col_expr(Column::ClientInfo, Expr::value(client_info_id))
+        .filter(Column::Id.eq(node_model_id))
+        .exec(conn)
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,241 @@
+//! Periodic pings of known nodes, used to summarize uptime, last seen time and churn.
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use chrono::{DateTime, FixedOffset};
+use sea_orm::{
+    entity::prelude::*, sea_query::Query, ActiveValue::NotSet, Condition, QueryOrder, QuerySelect,
+    Set,
+};
+
+/// The outcome of pinging a node to check whether it is reachable.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "node_liveness")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub node: i32,
+    /// The ENR that was used to reach the node, absent for checks made before this was recorded.
+    pub record: Option<i32>,
+    pub is_alive: bool,
+    /// Round trip time of the ping, only present if the node responded.
+    pub latency_ms: Option<i32>,
+    pub created_at: DateTime<FixedOffset>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::node::Entity",
+        from = "Column::Node",
+        to = "super::node::Column::Id",
+        on_update = "Cascade",
+        on_delete = "Cascade"
+    )]
+    Node,
+    #[sea_orm(
+        belongs_to = "super::record::Entity",
+        from = "Column::Record",
+        to = "super::record::Column::Id",
+        on_update = "Cascade",
+        on_delete = "Cascade"
+    )]
+    Record,
+}
+
+impl Related<super::node::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Node.def()
+    }
+}
+
+impl Related<super::record::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Record.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+/// Records a liveness check of the node the ENR belongs to. A node that did not respond has
+/// no latency.
+pub async fn create(
+    enr_record: &super::record::Model,
+    latency_ms: Option<i32>,
+    conn: &DatabaseConnection,
+) -> Result<Model> {
+    let liveness = ActiveModel {
+        id: NotSet,
+        node: Set(enr_record.node_id),
+        record: Set(Some(enr_record.id)),
+        is_alive: Set(latency_ms.is_some()),
+        latency_ms: Set(latency_ms),
+        created_at: Set(chrono::offset::Utc::now().into()),
+    };
+    Ok(liveness.insert(conn).await?)
+}
+
+/// Returns the most recent liveness checks for the node, newest first.
+pub async fn get_history(
+    node_model_id: i32,
+    limit: u64,
+    conn: &DatabaseConnection,
+) -> Result<Vec<Model>> {
+    Ok(Entity::find()
+        .filter(Column::Node.eq(node_model_id))
+        .order_by_desc(Column::CreatedAt)
+        .limit(limit)
+        .all(conn)
+        .await?)
+}
+
+/// Summarizes the liveness checks of the node made since the given time.
+pub async fn get_summary(
+    node_model_id: i32,
+    since: DateTime<FixedOffset>,
+    conn: &DatabaseConnection,
+) -> Result<LivenessSummary> {
+    let checks = Entity::find()
+        .filter(Column::Node.eq(node_model_id))
+        .filter(Column::CreatedAt.gte(since))
+        .order_by_asc(Column::CreatedAt)
+        .all(conn)
+        .await?;
+    let last_seen = get_last_seen(node_model_id, conn).await?;
+    Ok(LivenessSummary::from_checks(&checks, last_seen))
+}
+
+/// Returns the most recent time the node responded to a liveness check.
+pub async fn get_last_seen(
+    node_model_id: i32,
+    conn: &DatabaseConnection,
+) -> Result<Option<DateTime<FixedOffset>>> {
+    Ok(Entity::find()
+        .filter(Column::Node.eq(node_model_id))
+        .filter(Column::IsAlive.eq(true))
+        .order_by_desc(Column::CreatedAt)
+        .one(conn)
+        .await?
+        .map(|check| check.created_at))
+}
+
+/// Returns the most recent time each of the nodes responded to a liveness check, for those
+/// that ever did.
+pub async fn get_last_seen_for_nodes(
+    node_model_ids: &[i32],
+    conn: &DatabaseConnection,
+) -> Result<BTreeMap<i32, DateTime<FixedOffset>>> {
+    let last_seen: Vec<(i32, DateTime<FixedOffset>)> = Entity::find()
+        .select_only()
+        .column(Column::Node)
+        .column_as(Column::CreatedAt.max(), "last_seen")
+        .filter(Column::Node.is_in(node_model_ids.iter().copied()))
+        .filter(Column::IsAlive.eq(true))
+        .group_by(Column::Node)
+        .into_tuple()
+        .all(conn)
+        .await?;
+    Ok(last_seen.into_iter().collect())
+}
+
+/// Returns the most recently added nodes, newest first, each with its latest liveness check
+/// if it has been checked.
+pub async fn get_recent_nodes_with_latest_check(
+    limit: u64,
+    conn: &DatabaseConnection,
+) -> Result<Vec<(super::node::Model, Option<Model>)>> {
+    // Ids increase with time, so the highest id of each node is its latest check.
+    let latest_checks = Query::select()
+        .expr(Column::Id.max())
+        .from(Entity)
+        .group_by_col(Column::Node)
+        .to_owned();
+    Ok(super::node::Entity::find()
+        .find_also_related(Entity)
+        .filter(
+            Condition::any()
+                .add(Column::Id.is_null())
+                .add(Column::Id.in_subquery(latest_checks)),
+        )
+        .order_by_desc(super::node::Column::Id)
+        .limit(limit)
+        .all(conn)
+        .await?)
+}
+
+/// Returns the nodes that responded to a liveness check in the time window, each with the ENR
+/// used in its most recent successful check. Checks that did not record their ENR are left out.
+pub async fn get_seen(
+    start: DateTime<FixedOffset>,
+    end: DateTime<FixedOffset>,
+    conn: &DatabaseConnection,
+) -> Result<BTreeMap<i32, i32>> {
+    let checks: Vec<(i32, i32)> = Entity::find()
+        .select_only()
+        .column(Column::Node)
+        .column(Column::Record)
+        .filter(Column::IsAlive.eq(true))
+        .filter(Column::Record.is_not_null())
+        .filter(Column::CreatedAt.gte(start))
+        .filter(Column::CreatedAt.lt(end))
+        .order_by_asc(Column::CreatedAt)
+        .into_tuple()
+        .all(conn)
+        .await?;
+    // Later checks replace earlier ones.
+    Ok(checks.into_iter().collect())
+}
+
+impl Model {
+    pub fn created_at_local_time(&self) -> String {
+        self.created_at.with_timezone(&chrono::Local).to_rfc2822()
+    }
+}
+
+/// Reachability of a node over a period of time.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LivenessSummary {
+    pub total_checks: usize,
+    pub alive_checks: usize,
+    /// Most recent time the node responded, which may be before the period.
+    pub last_seen: Option<DateTime<FixedOffset>>,
+    /// Number of times the node went from reachable to unreachable or back.
+    pub churn: usize,
+}
+
+impl LivenessSummary {
+    /// Creates a summary from checks in chronological order.
+    pub fn from_checks(checks: &[Model], last_seen: Option<DateTime<FixedOffset>>) -> Self {
+        LivenessSummary {
+            total_checks: checks.len(),
+            alive_checks: checks.iter().filter(|check| check.is_alive).count(),
+            last_seen,
+            churn: checks
+                .windows(2)
+                .filter(|pair| pair[0].is_alive != pair[1].is_alive)
+                .count(),
+        }
+    }
+
+    /// Percentage of checks the node responded to, if it has been checked at all.
+    pub fn uptime_percentage(&self) -> Option<f64> {
+        if self.total_checks == 0 {
+            return None;
+        }
+        Some(self.alive_checks as f64 / self.total_checks as f64 * 100.0)
+    }
+
+    pub fn uptime_as_string(&self) -> String {
+        match self.uptime_percentage() {
+            Some(uptime) => format!("{uptime:.1}%"),
+            None => "-".to_owned(),
+        }
+    }
+
+    pub fn last_seen_local_time(&self) -> String {
+        match self.last_seen {
+            Some(time) => time.with_timezone(&chrono::Local).to_rfc2822(),
+            None => "Never".to_owned(),
+        }
+    }
+}
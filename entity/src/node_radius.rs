@@ -0,0 +1,111 @@
+//! Data radii advertised by nodes, kept as a history of observations.
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use chrono::{DateTime, FixedOffset};
+use ethereum_types::U256;
+use sea_orm::{
+    entity::prelude::*, sea_query::Query, ActiveValue::NotSet, QueryOrder, QuerySelect, Set,
+};
+use trin_utils::bytes::hex_encode;
+
+/// A data radius advertised by a node, as observed at a point in time.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "node_radius")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub node: i32,
+    /// Big-endian.
+    pub data_radius: Vec<u8>,
+    pub created_at: DateTime<FixedOffset>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::node::Entity",
+        from = "Column::Node",
+        to = "super::node::Column::Id",
+        on_update = "Cascade",
+        on_delete = "Cascade"
+    )]
+    Node,
+}
+
+impl Related<super::node::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Node.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+pub async fn create(
+    node_model_id: i32,
+    data_radius: Vec<u8>,
+    conn: &DatabaseConnection,
+) -> Result<Model> {
+    let node_radius = ActiveModel {
+        id: NotSet,
+        node: Set(node_model_id),
+        data_radius: Set(data_radius),
+        created_at: Set(chrono::offset::Utc::now().into()),
+    };
+    Ok(node_radius.insert(conn).await?)
+}
+
+/// Returns the most recent radius observations for the node, newest first.
+pub async fn get_history(
+    node_model_id: i32,
+    limit: u64,
+    conn: &DatabaseConnection,
+) -> Result<Vec<Model>> {
+    Ok(Entity::find()
+        .filter(Column::Node.eq(node_model_id))
+        .order_by_desc(Column::CreatedAt)
+        .limit(limit)
+        .all(conn)
+        .await?)
+}
+
+/// Returns the most recent radius observation of each of the nodes, for those that have one.
+pub async fn get_latest_for_nodes(
+    node_model_ids: &[i32],
+    conn: &DatabaseConnection,
+) -> Result<BTreeMap<i32, Model>> {
+    // Ids increase with time, so the highest id of each node is its latest observation.
+    let latest_observations = Query::select()
+        .expr(Column::Id.max())
+        .from(Entity)
+        .and_where(Column::Node.is_in(node_model_ids.iter().copied()))
+        .group_by_col(Column::Node)
+        .to_owned();
+    Ok(Entity::find()
+        .filter(Column::Id.in_subquery(latest_observations))
+        .all(conn)
+        .await?
+        .into_iter()
+        .map(|radius| (radius.node, radius))
+        .collect())
+}
+
+impl Model {
+    pub fn data_radius_as_hex(&self) -> String {
+        hex_encode(&self.data_radius)
+    }
+
+    pub fn get_data_radius(&self) -> U256 {
+        U256::from_big_endian(&self.data_radius)
+    }
+
+    /// The fraction of the keyspace covered by the radius, as a percentage.
+    pub fn keyspace_percentage(&self) -> f64 {
+        let high_bits = (self.get_data_radius() >> 192).low_u64();
+        high_bits as f64 / u64::MAX as f64 * 100.0
+    }
+
+    pub fn created_at_local_time(&self) -> String {
+        self.created_at.with_timezone(&chrono::Local).to_rfc2822()
+    }
+}
@@ -0,0 +1,140 @@
+//! Audits of single nodes, asked directly for content that falls within their radius.
+use anyhow::Result;
+use chrono::{DateTime, FixedOffset};
+use sea_orm::{entity::prelude::*, ActiveValue::NotSet, QueryOrder, QuerySelect, Set};
+
+use crate::content_audit::{AuditResult, SelectionStrategy};
+
+/// The result of asking a single node, whose radius covers the content, for that content.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "node_storage_audit")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub content_key: i32,
+    pub node: i32,
+    /// The record used to contact the node.
+    pub record: i32,
+    pub created_at: DateTime<FixedOffset>,
+    pub strategy_used: Option<SelectionStrategy>,
+    pub result: AuditResult,
+    /// The radius reported by the node at the time of the audit, big-endian.
+    pub data_radius: Vec<u8>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::content::Entity",
+        from = "Column::ContentKey",
+        to = "super::content::Column::Id",
+        on_update = "Cascade",
+        on_delete = "Cascade"
+    )]
+    Content,
+    #[sea_orm(
+        belongs_to = "super::node::Entity",
+        from = "Column::Node",
+        to = "super::node::Column::Id",
+        on_update = "Cascade",
+        on_delete = "Cascade"
+    )]
+    Node,
+    #[sea_orm(
+        belongs_to = "super::record::Entity",
+        from = "Column::Record",
+        to = "super::record::Column::Id",
+        on_update = "Cascade",
+        on_delete = "Cascade"
+    )]
+    Record,
+}
+
+impl Related<super::content::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Content.def()
+    }
+}
+
+impl Related<super::node::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Node.def()
+    }
+}
+
+impl Related<super::record::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Record.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+pub async fn create(
+    content_key_model_id: i32,
+    record: &super::record::Model,
+    content_found: bool,
+    strategy_used: SelectionStrategy,
+    data_radius: Vec<u8>,
+    conn: &DatabaseConnection,
+) -> Result<Model> {
+    let audit_result = if content_found {
+        AuditResult::Success
+    } else {
+        AuditResult::Failure
+    };
+
+    let node_storage_audit = ActiveModel {
+        id: NotSet,
+        content_key: Set(content_key_model_id),
+        node: Set(record.node_id),
+        record: Set(record.id),
+        created_at: Set(chrono::offset::Utc::now().into()),
+        strategy_used: Set(Some(strategy_used)),
+        result: Set(audit_result),
+        data_radius: Set(data_radius),
+    };
+    Ok(node_storage_audit.insert(conn).await?)
+}
+
+/// Returns the most recent audits of the nodes responsible for the content, newest first,
+/// each with the audited node.
+pub async fn get_for_content(
+    content_key_model_id: i32,
+    limit: u64,
+    conn: &DatabaseConnection,
+) -> Result<Vec<(Model, Option<super::node::Model>)>> {
+    Ok(Entity::find()
+        .filter(Column::ContentKey.eq(content_key_model_id))
+        .order_by_desc(Column::CreatedAt)
+        .limit(limit)
+        .find_also_related(super::node::Entity)
+        .all(conn)
+        .await?)
+}
+
+/// Returns the most recent audits of content that the node was responsible for, newest first,
+/// each with the audited content.
+pub async fn get_for_node(
+    node_model_id: i32,
+    limit: u64,
+    conn: &DatabaseConnection,
+) -> Result<Vec<(Model, Option<super::content::Model>)>> {
+    Ok(Entity::find()
+        .filter(Column::Node.eq(node_model_id))
+        .order_by_desc(Column::CreatedAt)
+        .limit(limit)
+        .find_also_related(super::content::Entity)
+        .all(conn)
+        .await?)
+}
+
+impl Model {
+    pub fn is_success(&self) -> bool {
+        self.result == AuditResult::Success
+    }
+
+    pub fn created_at_local_time(&self) -> String {
+        self.created_at.with_timezone(&chrono::Local).to_rfc2822()
+    }
+}
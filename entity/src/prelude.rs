@@ -1,8 +1,15 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.10.7
 
+pub use super::audit_queue::Entity as AuditQueue;
+pub use super::backfill::Entity as Backfill;
+pub use super::census::Entity as Census;
+pub use super::census_node::Entity as CensusNode;
 pub use super::content::Entity as Content;
 pub use super::content_audit::Entity as ContentAudit;
 pub use super::execution_metadata::Entity as ExecutionMetadata;
 pub use super::key_value::Entity as KeyValue;
 pub use super::node::Entity as Node;
+pub use super::node_liveness::Entity as NodeLiveness;
+pub use super::node_radius::Entity as NodeRadius;
+pub use super::node_storage_audit::Entity as NodeStorageAudit;
 pub use super::record::Entity as Record;
@@ -1,7 +1,16 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.10.7
+use std::net::IpAddr;
+use std::str::FromStr;
+
 use anyhow::Result;
-use sea_orm::{entity::prelude::*, ActiveValue::NotSet, Set};
+use sea_orm::{
+    entity::prelude::*,
+    sea_query::{Expr, Query},
+    ActiveValue::NotSet,
+    Condition, QueryOrder, QuerySelect, Set,
+};
 use trin_types::enr::Enr;
+use trin_utils::bytes::hex_encode;
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
 #[sea_orm(table_name = "record")]
@@ -11,6 +20,20 @@ pub struct Model {
     pub node_id: i32,
     pub raw: String,
     pub sequence_number: i32,
+    /// Decoded from the `id` key, e.g. "v4".
+    pub identity_scheme: Option<String>,
+    /// Decoded from the `secp256k1` key, compressed.
+    pub public_key: Option<Vec<u8>>,
+    /// Decoded from the `ip` key, in dotted decimal notation.
+    pub ip: Option<String>,
+    /// Decoded from the `ip6` key.
+    pub ip6: Option<String>,
+    /// Decoded from the `udp` key.
+    pub udp: Option<i32>,
+    /// Decoded from the `tcp` key.
+    pub tcp: Option<i32>,
+    /// Decoded from the Portal `c` key, which names the client implementation.
+    pub client: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -41,6 +64,32 @@ impl Related<super::node::Entity> for Entity {
 
 impl ActiveModelBehavior for ActiveModel {}
 
+impl ActiveModel {
+    /// Sets the columns that hold the decoded values of well-known ENR keys.
+    fn set_decoded_fields(&mut self, enr: &Enr) {
+        self.identity_scheme = Set(enr.id());
+        self.public_key = Set(enr.get("secp256k1").map(|key| key.to_vec()));
+        self.ip = Set(enr.ip4().map(|ip| ip.to_string()));
+        self.ip6 = Set(enr.ip6().map(|ip| ip.to_string()));
+        self.udp = Set(enr.udp4().map(i32::from));
+        self.tcp = Set(enr.tcp4().map(i32::from));
+        self.client = Set(enr
+            .get("c")
+            .map(|client| String::from_utf8_lossy(client).into_owned()));
+    }
+}
+
+impl Model {
+    pub fn public_key_as_hex(&self) -> Option<String> {
+        self.public_key.as_ref().map(hex_encode)
+    }
+
+    /// The IPv4 UDP socket of the node, as `ip:port`.
+    pub fn udp_socket(&self) -> Option<String> {
+        Some(format!("{}:{}", self.ip.as_ref()?, self.udp?))
+    }
+}
+
 pub async fn get_or_create(enr: &Enr, conn: &DatabaseConnection) -> Result<Model> {
     let node_id = super::node::get_or_create(enr.node_id().into(), conn)
         .await
@@ -58,13 +107,16 @@ pub async fn get_or_create(enr: &Enr, conn: &DatabaseConnection) -> Result<Model
     }
 
     // If no record exists, create one and return it
-    let enr_model_unsaved = ActiveModel {
+    let mut enr_model_unsaved = ActiveModel {
         id: NotSet,
         node_id: Set(node_id.id),
         raw: Set(enr.to_base64()),
         sequence_number: Set(enr.seq().try_into().unwrap()),
+        ..Default::default()
     };
+    enr_model_unsaved.set_decoded_fields(enr);
     let enr_model = enr_model_unsaved.insert(conn).await.unwrap();
+    link_client_info(&enr_model, conn).await?;
 
     for (enr_key, enr_value) in enr.iter() {
         super::key_value::get_or_create(enr_model.id, enr_key, &enr_value.to_vec(), conn)
@@ -74,3 +126,168 @@ pub async fn get_or_create(enr: &Enr, conn: &DatabaseConnection) -> Result<Model
 
     Ok(enr_model)
 }
+
+/// Returns the record with the highest sequence number for the node.
+pub async fn get_latest(node_model_id: i32, conn: &DatabaseConnection) -> Result<Option<Model>> {
+    Ok(Entity::find()
+        .filter(Column::NodeId.eq(node_model_id))
+        .order_by_desc(Column::SequenceNumber)
+        .one(conn)
+        .await?)
+}
+
+/// Returns the record with the highest sequence number for every known node.
+pub async fn get_latest_for_each_node(conn: &DatabaseConnection) -> Result<Vec<Model>> {
+    // Sequence numbers are unique per node, so this matches one record for each.
+    let latest_sequence_numbers = Query::select()
+        .column(Column::NodeId)
+        .expr(Column::SequenceNumber.max())
+        .from(Entity)
+        .group_by_col(Column::NodeId)
+        .to_owned();
+    Ok(Entity::find()
+        .filter(
+            Expr::tuple([
+                Expr::col(Column::NodeId).into(),
+                Expr::col(Column::SequenceNumber).into(),
+            ])
+            .in_subquery(latest_sequence_numbers),
+        )
+        .order_by_asc(Column::NodeId)
+        .all(conn)
+        .await?)
+}
+
+/// Links the node to the client it advertises, if this is its latest record.
+async fn link_client_info(enr_model: &Model, conn: &DatabaseConnection) -> Result<()> {
+    let Some(enr_client) = &enr_model.client else {
+        return Ok(());
+    };
+    if get_latest(enr_model.node_id, conn).await?.as_ref() != Some(enr_model) {
+        return Ok(());
+    }
+    let version_info = super::client_info::version_info_from_enr(enr_client);
+    let client_info = super::client_info::get_or_create(version_info, conn).await?;
+    super::node::set_client_info(enr_model.node_id, client_info.id, conn).await
+}
+
+/// Names the backfill that decodes well-known ENR keys.
+const DECODE_FIELDS_BACKFILL: &str = "decode_enr_fields";
+
+/// Number of records decoded per query.
+const DECODE_PAGE_SIZE: u64 = 500;
+
+/// Outcome of decoding the well-known keys of records.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DecodeSummary {
+    /// Number of records that were updated.
+    pub decoded: usize,
+    /// Ids of records whose raw ENR could not be decoded, with the reason. These are left as
+    /// they were.
+    pub failed: Vec<(i32, String)>,
+}
+
+/// Decodes the well-known keys of records saved before they were stored in their own columns.
+///
+/// Records saved since are decoded as they are saved, so this only runs once. Nothing is
+/// decoded once the backfill has finished.
+pub async fn decode_missing_fields(conn: &DatabaseConnection) -> Result<DecodeSummary> {
+    let mut summary = DecodeSummary::default();
+    if super::backfill::is_complete(DECODE_FIELDS_BACKFILL, conn).await? {
+        return Ok(summary);
+    }
+    // Pages follow the id, as decoded records drop out of the filter.
+    let mut after_id = 0;
+    loop {
+        // Every valid ENR has an identity scheme, so it is only missing if never decoded.
+        let undecoded = Entity::find()
+            .filter(Column::IdentityScheme.is_null())
+            .filter(Column::Id.gt(after_id))
+            .order_by_asc(Column::Id)
+            .limit(DECODE_PAGE_SIZE)
+            .all(conn)
+            .await?;
+        let Some(last) = undecoded.last() else {
+            break;
+        };
+        after_id = last.id;
+        for enr_model in undecoded {
+            let enr = match Enr::from_str(&enr_model.raw) {
+                Ok(enr) => enr,
+                Err(e) => {
+                    summary.failed.push((enr_model.id, e));
+                    continue;
+                }
+            };
+            let mut enr_model: ActiveModel = enr_model.into();
+            enr_model.set_decoded_fields(&enr);
+            enr_model.update(conn).await?;
+            summary.decoded += 1;
+        }
+    }
+    super::backfill::mark_complete(DECODE_FIELDS_BACKFILL, conn).await?;
+    Ok(summary)
+}
+
+/// Names the backfill that links nodes to their client.
+const LINK_CLIENT_INFO_BACKFILL: &str = "link_client_info";
+
+/// Links nodes saved before clients were identified to the client in their latest record.
+///
+/// Nodes saved since are linked as their records are saved, so this only runs once. Returns the
+/// number of nodes that were linked, which is zero once the backfill has finished.
+pub async fn link_missing_client_info(conn: &DatabaseConnection) -> Result<usize> {
+    if super::backfill::is_complete(LINK_CLIENT_INFO_BACKFILL, conn).await? {
+        return Ok(0);
+    }
+    let unlinked = super::node::Entity::find()
+        .filter(super::node::Column::ClientInfo.is_null())
+        .all(conn)
+        .await?;
+    let mut count = 0;
+    for node_model in unlinked {
+        if let Some(enr_model) = get_latest(node_model.id, conn).await? {
+            if enr_model.client.is_some() {
+                link_client_info(&enr_model, conn).await?;
+                count += 1;
+            }
+        }
+    }
+    super::backfill::mark_complete(LINK_CLIENT_INFO_BACKFILL, conn).await?;
+    Ok(count)
+}
+
+/// Returns the records that advertise the given IPv4 or IPv6 address.
+pub async fn get_by_ip(ip: IpAddr, conn: &DatabaseConnection) -> Result<Vec<Model>> {
+    let column = match ip {
+        IpAddr::V4(_) => Column::Ip,
+        IpAddr::V6(_) => Column::Ip6,
+    };
+    Ok(Entity::find()
+        .filter(column.eq(ip.to_string()))
+        .order_by_asc(Column::Id)
+        .all(conn)
+        .await?)
+}
+
+/// Returns the records that advertise the given UDP or TCP port.
+pub async fn get_by_port(port: u16, conn: &DatabaseConnection) -> Result<Vec<Model>> {
+    Ok(Entity::find()
+        .filter(
+            Condition::any()
+                .add(Column::Udp.eq(port))
+                .add(Column::Tcp.eq(port)),
+        )
+        .order_by_asc(Column::Id)
+        .all(conn)
+        .await?)
+}
+
+/// Returns the records whose client string contains the given text, e.g. "trin".
+pub async fn get_by_client(client: &str, conn: &DatabaseConnection) -> Result<Vec<Model>> {
+    Ok(Entity::find()
+        .filter(Column::Client.contains(client))
+        .order_by_asc(Column::Id)
+        .all(conn)
+        .await?)
+}
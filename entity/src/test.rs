@@ -1,4 +1,5 @@
 #![allow(unused_imports)]
+use std::collections::HashMap;
 use std::str::FromStr;
 
 #[cfg(test)]
@@ -17,8 +18,11 @@ use migration::{Migrator, MigratorTrait};
 use trin_utils::bytes::hex_encode;
 
 use crate::content::SubProtocol;
-use crate::content_audit::SelectionStrategy;
-use crate::{client_info, content, content_audit, node, record};
+use crate::content_audit::{AuditResult, SelectionStrategy, TraceObservation};
+use crate::{
+    audit_queue, census, census_node, client_info, content, content_audit, execution_metadata,
+    node, node_liveness, node_radius, node_storage_audit, record,
+};
 
 #[allow(dead_code)]
 async fn setup_database() -> Result<DbConn, DbErr> {
@@ -50,11 +54,13 @@ async fn test_node_crud() -> Result<(), DbErr> {
         id: NotSet,
         node_id: Set(node_id_a.clone()),
         node_id_high: Set(0),
+        client_info: Set(None),
     };
     let node_b = node::ActiveModel {
         id: NotSet,
         node_id: Set(node_id_b.clone()),
         node_id_high: Set(0),
+        client_info: Set(None),
     };
 
     assert_eq!(node::Entity::find().count(&conn).await?, 0);
@@ -111,6 +117,104 @@ async fn crud_record() -> Result<(), DbErr> {
     Ok(())
 }
 
+/// Tests that well-known ENR keys are decoded into their own columns.
+#[tokio::test]
+async fn test_record_decoded_fields() {
+    let conn = setup_database().await.unwrap();
+
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    let (key, mut enr) = trin_types::enr::generate_random_remote_enr();
+    let ip = Ipv4Addr::new(192, 168, 0, 1);
+    let ip6 = Ipv6Addr::LOCALHOST;
+    enr.set_ip(IpAddr::V4(ip), &key).unwrap();
+    enr.set_ip(IpAddr::V6(ip6), &key).unwrap();
+    enr.set_udp4(9009, &key).unwrap();
+    enr.set_tcp4(30303, &key).unwrap();
+    enr.insert("c", b"t 0.1.1-alpha.1", &key).unwrap();
+    let enr_model = record::get_or_create(&enr, &conn).await.unwrap();
+    assert_eq!(enr_model.identity_scheme, Some("v4".to_owned()));
+    assert_eq!(
+        enr_model.public_key,
+        Some(enr.get("secp256k1").unwrap().to_vec())
+    );
+    assert_eq!(enr_model.public_key.as_ref().unwrap().len(), 33);
+    assert_eq!(enr_model.ip, Some("192.168.0.1".to_owned()));
+    assert_eq!(enr_model.ip6, Some("::1".to_owned()));
+    assert_eq!(enr_model.udp, Some(9009));
+    assert_eq!(enr_model.tcp, Some(30303));
+    assert_eq!(enr_model.client, Some("t 0.1.1-alpha.1".to_owned()));
+    assert_eq!(enr_model.udp_socket(), Some("192.168.0.1:9009".to_owned()));
+
+    // Nodes can be queried by their decoded fields.
+    let (_, other_enr) = trin_types::enr::generate_random_remote_enr();
+    let other_model = record::get_or_create(&other_enr, &conn).await.unwrap();
+    assert_eq!(other_model.client, None);
+    assert_eq!(other_model.tcp, None);
+    assert_eq!(
+        record::get_by_ip(IpAddr::V4(ip), &conn).await.unwrap(),
+        vec![enr_model.clone()]
+    );
+    assert_eq!(
+        record::get_by_ip(IpAddr::V6(ip6), &conn).await.unwrap(),
+        vec![enr_model.clone()]
+    );
+    assert_eq!(
+        record::get_by_port(30303, &conn).await.unwrap(),
+        vec![enr_model.clone()]
+    );
+    assert_eq!(
+        record::get_by_port(8000, &conn).await.unwrap(),
+        vec![other_model.clone()]
+    );
+    assert_eq!(
+        record::get_by_client("t 0.1", &conn).await.unwrap(),
+        vec![enr_model.clone()]
+    );
+
+    // Records saved before the columns existed are decoded on request.
+    let mut undecoded: record::ActiveModel = enr_model.clone().into();
+    undecoded.identity_scheme = Set(None);
+    undecoded.client = Set(None);
+    undecoded.update(&conn).await.unwrap();
+    // Records that cannot be decoded are skipped, without stopping the others.
+    let corrupt = record::ActiveModel {
+        id: NotSet,
+        node_id: Set(other_model.node_id),
+        raw: Set("enr:not-an-enr".to_owned()),
+        sequence_number: Set(0),
+        identity_scheme: Set(None),
+        public_key: Set(None),
+        ip: Set(None),
+        ip6: Set(None),
+        udp: Set(None),
+        tcp: Set(None),
+        client: Set(None),
+    }
+    .insert(&conn)
+    .await
+    .unwrap();
+    let summary = record::decode_missing_fields(&conn).await.unwrap();
+    assert_eq!(summary.decoded, 1);
+    assert_eq!(
+        summary
+            .failed
+            .iter()
+            .map(|(record_id, _)| *record_id)
+            .collect::<Vec<_>>(),
+        vec![corrupt.id]
+    );
+    // The backfill only runs once.
+    assert_eq!(
+        record::decode_missing_fields(&conn).await.unwrap(),
+        record::DecodeSummary::default()
+    );
+    assert_eq!(
+        record::get_latest(enr_model.node_id, &conn).await.unwrap(),
+        Some(enr_model)
+    );
+}
+
 #[allow(dead_code)]
 /// Returns a history content key representing the header with proof
 /// for block hash `0x0001...1e1f`
@@ -395,12 +499,12 @@ async fn test_query_closest() {
         distance_a_b > distance_b_c
     );
 
-    let nodes_near_a = node::closest_xor(node_id_a, &conn).await.unwrap();
+    let nodes_near_a = node::closest_xor(node_id_a, 100, &conn).await.unwrap();
     assert_eq!(nodes_near_a.len(), 3);
 
-    let expected_distances_a = match distance_a_b > distance_a_c {
-        true => [0, distance_a_c, distance_a_b],
-        false => [0, distance_a_b, distance_a_c],
+    let expected_distances_a = match distance_a_b_full > distance_a_c_full {
+        true => [U256::zero(), distance_a_c_full, distance_a_b_full],
+        false => [U256::zero(), distance_a_b_full, distance_a_c_full],
     };
     let actual_distances_a = [
         nodes_near_a[0].distance,
@@ -416,7 +520,7 @@ async fn test_query_closest() {
     let order_from_a = [nodes_near_a[0].id, nodes_near_a[1].id, nodes_near_a[2].id];
     assert_eq!(order_from_a, expected_from_a);
 
-    let nodes_near_b = node::closest_xor(node_id_b, &conn).await.unwrap();
+    let nodes_near_b = node::closest_xor(node_id_b, 100, &conn).await.unwrap();
     let expected_from_b = match distance_a_b > distance_b_c {
         true => [node_b.id, node_c.id, node_a.id],
         false => [node_b.id, node_a.id, node_c.id],
@@ -424,7 +528,7 @@ async fn test_query_closest() {
     let order_from_b = [nodes_near_b[0].id, nodes_near_b[1].id, nodes_near_b[2].id];
     assert_eq!(order_from_b, expected_from_b);
 
-    let nodes_near_c = node::closest_xor(node_id_c, &conn).await.unwrap();
+    let nodes_near_c = node::closest_xor(node_id_c, 100, &conn).await.unwrap();
     let expected_from_c = match distance_a_c > distance_b_c {
         true => [node_c.id, node_b.id, node_a.id],
         false => [node_c.id, node_a.id, node_b.id],
@@ -432,3 +536,959 @@ async fn test_query_closest() {
     let order_from_c = [nodes_near_c[0].id, nodes_near_c[1].id, nodes_near_c[2].id];
     assert_eq!(order_from_c, expected_from_c);
 }
+
+/// Tests that nodes which share the same high bits are still ranked by their exact distance.
+#[tokio::test]
+async fn test_query_closest_exact() {
+    let conn = setup_database().await.unwrap();
+
+    let target = NodeId::random();
+    let mut nodes = vec![];
+    // Flipping only low bits keeps the high bits, and so the coarse ranking, the same.
+    for flipped_byte in [31, 20, 30, 25] {
+        let mut node_id = target.raw();
+        node_id[flipped_byte] ^= 0x80;
+        nodes.push(node::get_or_create(NodeId(node_id), &conn).await.unwrap());
+    }
+    // A node that is further away in the high bits.
+    let mut far_id = target.raw();
+    far_id[0] ^= 0x80;
+    let far = node::get_or_create(NodeId(far_id), &conn).await.unwrap();
+
+    let closest = node::closest_xor(target, 3, &conn).await.unwrap();
+    let closest_ids: Vec<i32> = closest.iter().map(|node| node.id).collect();
+    assert_eq!(closest_ids, vec![nodes[0].id, nodes[2].id, nodes[3].id]);
+    assert_eq!(closest[0].log_distance(), 8);
+    assert_eq!(closest[1].log_distance(), 16);
+    assert_eq!(closest[2].log_distance(), 56);
+
+    let all = node::closest_xor(target, 10, &conn).await.unwrap();
+    assert_eq!(all.len(), 5);
+    assert_eq!(all[4].id, far.id);
+    assert_eq!(all[4].log_distance(), 256);
+    assert!(node::closest_xor(target, 0, &conn)
+        .await
+        .unwrap()
+        .is_empty());
+
+    let by_content = node::closest_to_content(target.raw(), 1, &conn)
+        .await
+        .unwrap();
+    assert_eq!(by_content, closest[..1].to_vec());
+
+    let from_node = node::closest_xor(NodeId(far_id), 1, &conn).await.unwrap();
+    assert_eq!(from_node[0].id, far.id);
+    assert_eq!(from_node[0].log_distance(), 0);
+}
+
+#[tokio::test]
+async fn test_keyspace_counts() {
+    let conn = setup_database().await.unwrap();
+
+    let mut nodes = vec![];
+    for first_byte in [0x00, 0x3f, 0xff, 0x80] {
+        let mut node_id = NodeId::random().raw();
+        node_id[0] = first_byte;
+        nodes.push(node::get_or_create(NodeId(node_id), &conn).await.unwrap());
+    }
+    assert_eq!(
+        node::get_keyspace_counts(2, &conn).await.unwrap(),
+        vec![2, 0, 1, 1]
+    );
+    assert_eq!(node::get_keyspace_counts(0, &conn).await.unwrap(), vec![4]);
+    assert!(node::get_keyspace_counts(17, &conn).await.is_err());
+
+    let client_info_model = client_info::ActiveModel {
+        id: NotSet,
+        version_info: Set("trin v0.1.0".to_owned()),
+    }
+    .insert(&conn)
+    .await
+    .unwrap();
+    let now = Utc::now();
+    let audits = [
+        (0x01, AuditResult::Success, now),
+        (0x02, AuditResult::Failure, now),
+        (0xc0, AuditResult::Success, now),
+        // Too old to be counted.
+        (0xc1, AuditResult::Failure, now - chrono::Duration::days(2)),
+    ];
+    for (first_byte, result, created_at) in audits {
+        let mut content_id = [0u8; 32];
+        content_id[0] = first_byte;
+        let content_model = content::ActiveModel {
+            id: NotSet,
+            content_id: Set(content_id.to_vec()),
+            content_key: Set(content_id.to_vec()),
+            protocol_id: Set(SubProtocol::History),
+            first_available_at: Set(now.into()),
+        }
+        .insert(&conn)
+        .await
+        .unwrap();
+        content_audit::ActiveModel {
+            id: NotSet,
+            content_key: Set(content_model.id),
+            created_at: Set(created_at.into()),
+            strategy_used: Set(Some(SelectionStrategy::Latest)),
+            result: Set(result),
+            trace: Set("".to_owned()),
+            client_info: Set(Some(client_info_model.id)),
+            node: Set(Some(nodes[0].id)),
+        }
+        .insert(&conn)
+        .await
+        .unwrap();
+    }
+    let since = now - chrono::Duration::days(1);
+    let counts = content_audit::get_keyspace_counts(2, since, &conn)
+        .await
+        .unwrap();
+    let totals: Vec<(u64, u64)> = counts.iter().map(|c| (c.passes, c.total)).collect();
+    assert_eq!(totals, vec![(1, 2), (0, 0), (0, 0), (1, 1)]);
+    assert_eq!(counts[0].success_rate(), Some(50.0));
+    assert_eq!(counts[1].success_rate(), None);
+    // Prefixes longer than a byte are counted from the first two bytes of the id.
+    let counts = content_audit::get_keyspace_counts(12, since, &conn)
+        .await
+        .unwrap();
+    assert_eq!(counts.len(), 1 << 12);
+    assert_eq!(counts[0x010].total, 1);
+    assert_eq!(counts[0xc00].passes, 1);
+    assert_eq!(counts.iter().map(|c| c.total).sum::<u64>(), 3);
+
+    assert_eq!(content::keyspace_bucket(&[0xab, 0xcd], 16).unwrap(), 0xabcd);
+    assert_eq!(content::keyspace_bucket(&[0xab], 4).unwrap(), 0xa);
+    assert_eq!(content::keyspace_bucket(&[0xab, 0xcd], 0).unwrap(), 0);
+}
+
+#[test]
+fn test_block_age_band_ranges() {
+    use execution_metadata::{BlockAgeBand, MERGE_BLOCK_NUMBER};
+
+    let latest = 17_000_000;
+    assert_eq!(
+        BlockAgeBand::LastHour.block_range(latest),
+        16_999_701..17_000_001
+    );
+    assert_eq!(
+        BlockAgeBand::LastDay.block_range(latest),
+        16_992_801..16_999_701
+    );
+    assert_eq!(
+        BlockAgeBand::LastMonth.block_range(latest).end,
+        BlockAgeBand::LastWeek.block_range(latest).start
+    );
+    assert_eq!(
+        BlockAgeBand::PostMerge.block_range(latest),
+        MERGE_BLOCK_NUMBER..16_784_001
+    );
+    assert_eq!(
+        BlockAgeBand::PreMerge.block_range(latest),
+        0..MERGE_BLOCK_NUMBER
+    );
+
+    // Bands that a short chain does not reach are empty.
+    assert_eq!(BlockAgeBand::LastHour.block_range(1000), 701..1001);
+    assert_eq!(BlockAgeBand::LastDay.block_range(1000), 0..701);
+    assert!(BlockAgeBand::LastWeek.block_range(1000).is_empty());
+    assert!(BlockAgeBand::PreMerge.block_range(1000).is_empty());
+}
+
+#[tokio::test]
+async fn test_block_age_counts() {
+    use execution_metadata::BlockAgeBand;
+
+    let conn = setup_database().await.unwrap();
+    let client_info_model = client_info::ActiveModel {
+        id: NotSet,
+        version_info: Set("trin v0.1.0".to_owned()),
+    }
+    .insert(&conn)
+    .await
+    .unwrap();
+    let node = node::get_or_create(NodeId::random(), &conn).await.unwrap();
+    assert!(execution_metadata::get_latest_block_number(&conn)
+        .await
+        .unwrap()
+        .is_none());
+
+    let audits = [
+        (1, 17_000_000, true),
+        (2, 16_999_999, false),
+        (3, 1_000_000, true),
+    ];
+    for (hash_byte, block_number, query_successful) in audits {
+        let content_key = HistoryContentKey::BlockHeaderWithProof(BlockHeaderKey {
+            block_hash: [hash_byte; 32],
+        });
+        let content_model = content::get_or_create(&content_key, &conn).await.unwrap();
+        execution_metadata::get_or_create(content_model.id, block_number, &conn)
+            .await
+            .unwrap();
+        content_audit::create(
+            content_model.id,
+            client_info_model.id,
+            node.id,
+            query_successful,
+            SelectionStrategy::BlockAgeStratified,
+            "".to_owned(),
+            &conn,
+        )
+        .await
+        .unwrap();
+    }
+    assert_eq!(
+        execution_metadata::get_latest_block_number(&conn)
+            .await
+            .unwrap(),
+        Some(17_000_000)
+    );
+
+    let counts = content_audit::get_block_age_counts(Utc::now() - chrono::Duration::days(1), &conn)
+        .await
+        .unwrap();
+    let counts: HashMap<BlockAgeBand, (u64, u64)> = counts
+        .into_iter()
+        .map(|(band, counts)| (band, (counts.passes, counts.total)))
+        .collect();
+    assert_eq!(counts.len(), 6);
+    assert_eq!(counts[&BlockAgeBand::LastHour], (1, 2));
+    assert_eq!(counts[&BlockAgeBand::LastDay], (0, 0));
+    assert_eq!(counts[&BlockAgeBand::PreMerge], (1, 1));
+}
+
+/// Tests that audits are counted by result, strategy and client name across client versions.
+#[tokio::test]
+async fn test_audit_result_counts() {
+    let conn = setup_database().await.unwrap();
+    let node = node::get_or_create(NodeId::random(), &conn).await.unwrap();
+    let mut client_ids = vec![];
+    for version_info in ["trin v0.1.0", "\"trin v0.1.1\"", "fluffy v0.2.0"] {
+        let client_info_model = client_info::get_or_create(version_info.to_owned(), &conn)
+            .await
+            .unwrap();
+        client_ids.push(client_info_model.id);
+    }
+
+    let audits = [
+        (client_ids[0], true, SelectionStrategy::Latest),
+        (client_ids[1], true, SelectionStrategy::Latest),
+        (client_ids[1], false, SelectionStrategy::Latest),
+        (client_ids[2], true, SelectionStrategy::Random),
+    ];
+    for (hash_byte, (client_id, query_successful, strategy)) in audits.into_iter().enumerate() {
+        let content_key = HistoryContentKey::BlockHeaderWithProof(BlockHeaderKey {
+            block_hash: [hash_byte as u8; 32],
+        });
+        let content_model = content::get_or_create(&content_key, &conn).await.unwrap();
+        content_audit::create(
+            content_model.id,
+            client_id,
+            node.id,
+            query_successful,
+            strategy,
+            "".to_owned(),
+            &conn,
+        )
+        .await
+        .unwrap();
+    }
+
+    let mut counts =
+        content_audit::get_result_counts(Utc::now() - chrono::Duration::hours(1), &conn)
+            .await
+            .unwrap();
+    counts.sort_by_key(|(result, _, name, _)| (name.clone(), result.as_text()));
+    assert_eq!(
+        counts,
+        vec![
+            (
+                AuditResult::Success,
+                Some(SelectionStrategy::Random),
+                "fluffy".to_owned(),
+                1
+            ),
+            (
+                AuditResult::Failure,
+                Some(SelectionStrategy::Latest),
+                "trin".to_owned(),
+                1
+            ),
+            (
+                AuditResult::Success,
+                Some(SelectionStrategy::Latest),
+                "trin".to_owned(),
+                2
+            ),
+        ]
+    );
+    assert!(content_audit::get_result_counts(Utc::now(), &conn)
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+/// Tests that per-node audits are stored against the record used to reach the node.
+#[tokio::test]
+async fn test_node_storage_audit_crud() {
+    let conn = setup_database().await.unwrap();
+    let key = sample_history_key();
+    let content_model = content::get_or_create(&key, &conn).await.unwrap();
+
+    let enr = trin_types::enr::Enr::from_str("enr:-I24QDy_atpK3KlPjl6X5yIrK7FosdHI1cW0I0MeiaIVuYg3AEEH9tRSTyFb2k6lpUiFsqxt8uTW3jVMUzoSlQf5OXYBY4d0IDAuMS4wgmlkgnY0gmlwhKEjVaWJc2VjcDI1NmsxoQOSGugH1jSdiE_fRK1FIBe9oLxaWH8D_7xXSnaOVBe-SYN1ZHCCIyg").unwrap();
+    let record_model = record::get_or_create(&enr, &conn).await.unwrap();
+    let latest_record = record::get_latest(record_model.node_id, &conn)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(latest_record, record_model);
+
+    let radius = vec![255; 32];
+    node_storage_audit::create(
+        content_model.id,
+        &record_model,
+        false,
+        SelectionStrategy::Latest,
+        radius.clone(),
+        &conn,
+    )
+    .await
+    .unwrap();
+    node_storage_audit::create(
+        content_model.id,
+        &record_model,
+        true,
+        SelectionStrategy::Random,
+        radius.clone(),
+        &conn,
+    )
+    .await
+    .unwrap();
+
+    let audits = node_storage_audit::get_for_content(content_model.id, 10, &conn)
+        .await
+        .unwrap();
+    assert_eq!(audits.len(), 2);
+    assert_eq!(audits[0].0.node, record_model.node_id);
+    assert_eq!(audits[0].0.record, record_model.id);
+    assert_eq!(audits[0].0.data_radius, radius);
+    assert_eq!(audits[0].1.as_ref().unwrap().id, record_model.node_id);
+    assert_eq!(
+        audits
+            .iter()
+            .filter(|(audit, _)| audit.is_success())
+            .count(),
+        1
+    );
+
+    let node_audits = node_storage_audit::get_for_node(record_model.node_id, 1, &conn)
+        .await
+        .unwrap();
+    assert_eq!(node_audits.len(), 1);
+    assert_eq!(node_audits[0].1, Some(content_model));
+}
+
+/// Tests that radius observations are returned newest first.
+#[tokio::test]
+async fn test_node_radius_history() {
+    let conn = setup_database().await.unwrap();
+    let node = node::get_or_create(NodeId::random(), &conn).await.unwrap();
+
+    let full_radius = vec![255; 32];
+    let mut half_radius = vec![255; 32];
+    half_radius[0] = 127;
+    node_radius::create(node.id, full_radius.clone(), &conn)
+        .await
+        .unwrap();
+    node_radius::create(node.id, half_radius.clone(), &conn)
+        .await
+        .unwrap();
+
+    let history = node_radius::get_history(node.id, 10, &conn).await.unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].data_radius, half_radius);
+    assert_eq!(history[1].data_radius, full_radius);
+    assert_eq!(history[1].get_data_radius(), U256::MAX);
+    assert!((history[0].keyspace_percentage() - 50.0).abs() < 0.001);
+    assert!((history[1].keyspace_percentage() - 100.0).abs() < 0.001);
+
+    let latest = node_radius::get_history(node.id, 1, &conn).await.unwrap();
+    assert_eq!(latest, vec![history[0].clone()]);
+
+    let unobserved = node::get_or_create(NodeId::random(), &conn).await.unwrap();
+    let latest = node_radius::get_latest_for_nodes(&[node.id, unobserved.id], &conn)
+        .await
+        .unwrap();
+    assert_eq!(
+        latest.into_iter().collect::<Vec<_>>(),
+        vec![(node.id, history[0].clone())]
+    );
+}
+
+#[tokio::test]
+async fn test_node_liveness_summary() {
+    let conn = setup_database().await.unwrap();
+    let (_, enr) = trin_types::enr::generate_random_remote_enr();
+    let enr_record = record::get_or_create(&enr, &conn).await.unwrap();
+    let node = node::Entity::find_by_id(enr_record.node_id)
+        .one(&conn)
+        .await
+        .unwrap()
+        .unwrap();
+    let start: chrono::DateTime<FixedOffset> = chrono::offset::Utc::now().into();
+
+    let summary = node_liveness::get_summary(node.id, start, &conn)
+        .await
+        .unwrap();
+    assert_eq!(summary.total_checks, 0);
+    assert_eq!(summary.uptime_percentage(), None);
+    assert_eq!(summary.last_seen, None);
+
+    // Up, up, down, up: two changes in reachability.
+    for latency_ms in [Some(20), Some(35), None, Some(15)] {
+        node_liveness::create(&enr_record, latency_ms, &conn)
+            .await
+            .unwrap();
+    }
+
+    let summary = node_liveness::get_summary(node.id, start, &conn)
+        .await
+        .unwrap();
+    assert_eq!(summary.total_checks, 4);
+    assert_eq!(summary.alive_checks, 3);
+    assert_eq!(summary.churn, 2);
+    assert_eq!(summary.uptime_percentage(), Some(75.0));
+
+    let history = node_liveness::get_history(node.id, 10, &conn)
+        .await
+        .unwrap();
+    assert_eq!(history.len(), 4);
+    assert_eq!(summary.last_seen, Some(history[0].created_at));
+    assert!(!history[1].is_alive);
+    assert_eq!(history[1].latency_ms, None);
+
+    // Checks before the period are not counted, but the node was still last seen then.
+    let later: chrono::DateTime<FixedOffset> = chrono::offset::Utc::now().into();
+    let summary = node_liveness::get_summary(node.id, later, &conn)
+        .await
+        .unwrap();
+    assert_eq!(summary.total_checks, 0);
+    assert_eq!(summary.last_seen, Some(history[0].created_at));
+
+    // Nodes are listed newest first, with their latest check if they have one.
+    let (_, unchecked_enr) = trin_types::enr::generate_random_remote_enr();
+    let unchecked_record = record::get_or_create(&unchecked_enr, &conn).await.unwrap();
+    let recent = node_liveness::get_recent_nodes_with_latest_check(10, &conn)
+        .await
+        .unwrap();
+    let recent: Vec<(i32, Option<node_liveness::Model>)> = recent
+        .into_iter()
+        .map(|(node_model, check)| (node_model.id, check))
+        .collect();
+    assert_eq!(
+        recent,
+        vec![
+            (unchecked_record.node_id, None),
+            (node.id, Some(history[0].clone())),
+        ]
+    );
+
+    // Nodes that never responded have not been seen.
+    let last_seen =
+        node_liveness::get_last_seen_for_nodes(&[node.id, unchecked_record.node_id], &conn)
+            .await
+            .unwrap();
+    assert_eq!(
+        last_seen.into_iter().collect::<Vec<_>>(),
+        vec![(node.id, history[0].created_at)]
+    );
+}
+
+#[tokio::test]
+async fn test_census_snapshot() {
+    let conn = setup_database().await.unwrap();
+    let (_, enr_a) = trin_types::enr::generate_random_remote_enr();
+    let (_, enr_b) = trin_types::enr::generate_random_remote_enr();
+    let record_a = record::get_or_create(&enr_a, &conn).await.unwrap();
+    let record_b = record::get_or_create(&enr_b, &conn).await.unwrap();
+
+    let census = census::create(&conn).await.unwrap();
+    assert_eq!(census.ended_at, None);
+    assert_eq!(census.duration(), None);
+    // Unfinished censuses have an incomplete node set, so are not reported.
+    assert!(census::get_finished(10, &conn).await.unwrap().is_empty());
+
+    let census = census::finish(census, &[record_b.clone(), record_a.clone()], &conn)
+        .await
+        .unwrap();
+    assert!(census.ended_at.is_some());
+    assert!(census.duration().unwrap() >= chrono::Duration::zero());
+    assert_eq!(census.node_count(&conn).await.unwrap(), 2);
+    let mut expected = vec![record_a.node_id, record_b.node_id];
+    expected.sort();
+    assert_eq!(
+        census_node::get_node_ids(census.id, &conn).await.unwrap(),
+        expected
+    );
+
+    let empty = census::create(&conn).await.unwrap();
+    let empty = census::finish(empty, &[], &conn).await.unwrap();
+    assert_eq!(empty.node_count(&conn).await.unwrap(), 0);
+    assert_eq!(
+        census::get_finished(10, &conn).await.unwrap(),
+        vec![empty, census]
+    );
+}
+
+#[tokio::test]
+async fn test_census_diff() {
+    let conn = setup_database().await.unwrap();
+    let (_, stays_enr) = trin_types::enr::generate_random_remote_enr();
+    let (_, leaves_enr) = trin_types::enr::generate_random_remote_enr();
+    let (_, joins_enr) = trin_types::enr::generate_random_remote_enr();
+    let (moves_key, mut moves_enr) = trin_types::enr::generate_random_remote_enr();
+    let stays = record::get_or_create(&stays_enr, &conn).await.unwrap();
+    let leaves = record::get_or_create(&leaves_enr, &conn).await.unwrap();
+    let joins = record::get_or_create(&joins_enr, &conn).await.unwrap();
+    let moved_from = record::get_or_create(&moves_enr, &conn).await.unwrap();
+    let old_socket = moves_enr.udp4_socket().unwrap();
+
+    // The moving node changes port and starts advertising its client.
+    moves_enr.set_udp4(9009, &moves_key).unwrap();
+    moves_enr.insert("c", b"t 0.1.1", &moves_key).unwrap();
+    let moved_to = record::get_or_create(&moves_enr, &conn).await.unwrap();
+
+    let old = census::create(&conn).await.unwrap();
+    let old = census::finish(old, &[stays.clone(), leaves.clone(), moved_from], &conn)
+        .await
+        .unwrap();
+    let new = census::create(&conn).await.unwrap();
+    let new = census::finish(new, &[stays, joins.clone(), moved_to], &conn)
+        .await
+        .unwrap();
+
+    let diff = census::diff(&old, &new, &conn).await.unwrap();
+    assert_eq!(diff.old_census, old.id);
+    assert_eq!(diff.new_census, new.id);
+    assert_eq!(diff.joined, vec![hex_encode(joins_enr.node_id().raw())]);
+    assert_eq!(diff.left, vec![hex_encode(leaves_enr.node_id().raw())]);
+    assert_eq!(diff.changed.len(), 1);
+    let change = &diff.changed[0];
+    assert_eq!(change.node_id, hex_encode(moves_enr.node_id().raw()));
+    assert_eq!(change.new_sequence_number, change.old_sequence_number + 2);
+    assert_eq!(change.old_socket, Some(old_socket.to_string()));
+    assert_eq!(change.new_socket, Some(format!("{}:9009", old_socket.ip())));
+    assert!(change.socket_changed());
+    assert_eq!(change.old_client, None);
+    assert_eq!(change.new_client, Some("t 0.1.1".to_owned()));
+    assert!(change.client_changed());
+
+    // Nothing changes when a census is compared with itself.
+    let diff = census::diff(&new, &new, &conn).await.unwrap();
+    assert!(diff.joined.is_empty() && diff.left.is_empty() && diff.changed.is_empty());
+}
+
+#[tokio::test]
+async fn test_window_diff() {
+    let conn = setup_database().await.unwrap();
+    let (_, stays_enr) = trin_types::enr::generate_random_remote_enr();
+    let (_, leaves_enr) = trin_types::enr::generate_random_remote_enr();
+    let (_, joins_enr) = trin_types::enr::generate_random_remote_enr();
+    let (moves_key, mut moves_enr) = trin_types::enr::generate_random_remote_enr();
+    let stays = record::get_or_create(&stays_enr, &conn).await.unwrap();
+    let leaves = record::get_or_create(&leaves_enr, &conn).await.unwrap();
+    let joins = record::get_or_create(&joins_enr, &conn).await.unwrap();
+    let moved_from = record::get_or_create(&moves_enr, &conn).await.unwrap();
+    moves_enr.set_udp4(9009, &moves_key).unwrap();
+    let moved_to = record::get_or_create(&moves_enr, &conn).await.unwrap();
+
+    let old_start: chrono::DateTime<FixedOffset> = Utc::now().into();
+    for enr_record in [&stays, &leaves, &moved_from] {
+        node_liveness::create(enr_record, Some(10), &conn)
+            .await
+            .unwrap();
+    }
+    std::thread::sleep(std::time::Duration::from_millis(5));
+    let new_start: chrono::DateTime<FixedOffset> = Utc::now().into();
+    for enr_record in [&stays, &joins, &moved_to] {
+        node_liveness::create(enr_record, Some(10), &conn)
+            .await
+            .unwrap();
+    }
+    // Nodes that do not respond are not seen.
+    node_liveness::create(&leaves, None, &conn).await.unwrap();
+    let new_end: chrono::DateTime<FixedOffset> = (Utc::now() + chrono::Duration::seconds(1)).into();
+
+    let diff = census::diff_windows(old_start..new_start, new_start..new_end, &conn)
+        .await
+        .unwrap();
+    assert_eq!(diff.joined, vec![hex_encode(joins_enr.node_id().raw())]);
+    assert_eq!(diff.left, vec![hex_encode(leaves_enr.node_id().raw())]);
+    assert_eq!(diff.changed.len(), 1);
+    assert_eq!(
+        diff.changed[0].node_id,
+        hex_encode(moves_enr.node_id().raw())
+    );
+    assert!(diff.changed[0].socket_changed());
+    assert!(!diff.changed[0].client_changed());
+}
+
+#[test]
+fn test_audit_trace_observations() {
+    let audit = content_audit::Model {
+        id: 1,
+        content_key: 1,
+        client_info: None,
+        node: None,
+        created_at: Utc::now().into(),
+        strategy_used: None,
+        result: content_audit::AuditResult::Success,
+        trace: r#"{
+            "origin": "0x0101",
+            "received_content_from_node": "0xAAAA",
+            "responses": {
+                "0x0101": {"timestamp_ms": 10, "responded_with": ["0xbbbb"]},
+                "0xbbbb": {"timestamp_ms": 20, "responded_with": []},
+                "0xaaaa": {"timestamp_ms": 30, "responded_with": []},
+                "cccc": {"timestamp_ms": 40, "responded_with": ["0xdddd"]},
+                "0xdddd": {"timestamp_ms": 50, "responded_with": "timeout"}
+            }
+        }"#
+        .to_string(),
+    };
+    let observations = audit.trace_observations();
+    assert_eq!(observations.len(), 4);
+    assert_eq!(observations["0xaaaa"], TraceObservation::Served);
+    assert_eq!(observations["0x0101"], TraceObservation::Missing);
+    assert_eq!(observations["0xbbbb"], TraceObservation::Missing);
+    assert_eq!(observations["0xcccc"], TraceObservation::Missing);
+
+    // Audits without a trace observe nothing.
+    let untraced = content_audit::Model {
+        trace: "".to_string(),
+        ..audit
+    };
+    assert!(untraced.trace_observations().is_empty());
+}
+
+#[test]
+fn test_version_info_from_enr() {
+    assert_eq!(
+        client_info::version_info_from_enr("t 0.1.1-alpha.1-4ec9a5"),
+        "trin 0.1.1-alpha.1-4ec9a5"
+    );
+    assert_eq!(client_info::version_info_from_enr("f"), "fluffy");
+    assert_eq!(
+        client_info::version_info_from_enr("u 0.0.1"),
+        "ultralight 0.0.1"
+    );
+    assert_eq!(client_info::version_info_from_enr("x 1.0"), "x 1.0");
+    assert_eq!(client_info::version_info_from_enr(""), "unknown");
+    assert_eq!(client_info::client_name("trin v0.1.0"), "trin");
+    assert_eq!(client_info::client_name(""), "unknown");
+}
+
+#[tokio::test]
+async fn test_node_client_info_from_enr() {
+    let conn = setup_database().await.unwrap();
+    let (key, mut enr) = trin_types::enr::generate_random_remote_enr();
+    let (_, silent_enr) = trin_types::enr::generate_random_remote_enr();
+    record::get_or_create(&silent_enr, &conn).await.unwrap();
+
+    enr.insert("c", b"t 0.1.0", &key).unwrap();
+    let old_record = enr.clone();
+    let enr_model = record::get_or_create(&enr, &conn).await.unwrap();
+    let (node_model, client) = node::Entity::find_by_id(enr_model.node_id)
+        .find_also_related(client_info::Entity)
+        .one(&conn)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(client.unwrap().version_info, "trin 0.1.0");
+
+    // The client follows the latest ENR, even if an older one is seen afterwards.
+    enr.insert("c", b"t 0.1.1", &key).unwrap();
+    record::get_or_create(&enr, &conn).await.unwrap();
+    let mut stale = old_record;
+    stale.set_seq(0, &key).unwrap();
+    record::get_or_create(&stale, &conn).await.unwrap();
+    let client = node_model
+        .find_related(client_info::Entity)
+        .one(&conn)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(client.version_info, "trin 0.1.1");
+    assert_eq!(client.client_name(), "trin");
+
+    // Only the record with the highest sequence number is latest, whatever the insert order.
+    let latest = record::get_latest_for_each_node(&conn).await.unwrap();
+    assert_eq!(latest.len(), 2);
+    let latest_client = latest
+        .iter()
+        .find(|record| record.node_id == node_model.id)
+        .unwrap();
+    assert_eq!(latest_client.sequence_number, enr.seq() as i32);
+
+    let counts = client_info::get_node_counts(&conn).await.unwrap();
+    assert_eq!(
+        counts.into_iter().collect::<Vec<_>>(),
+        vec![("trin".to_owned(), 1), ("unknown".to_owned(), 1)]
+    );
+
+    // Nodes saved before clients were identified are linked on request.
+    let mut unlinked: node::ActiveModel = node_model.clone().into();
+    unlinked.client_info = Set(None);
+    unlinked.update(&conn).await.unwrap();
+    assert_eq!(record::link_missing_client_info(&conn).await.unwrap(), 1);
+    let node_model = node::Entity::find_by_id(node_model.id)
+        .one(&conn)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(node_model.client_info, Some(client.id));
+
+    // The backfill only runs once.
+    let mut unlinked: node::ActiveModel = node_model.into();
+    unlinked.client_info = Set(None);
+    unlinked.update(&conn).await.unwrap();
+    assert_eq!(record::link_missing_client_info(&conn).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn test_census_client_counts() {
+    let conn = setup_database().await.unwrap();
+    let mut enr_records = vec![];
+    for enr_client in [Some("t 0.1.0"), Some("t 0.1.1"), Some("f 0.1.0"), None] {
+        let (key, mut enr) = trin_types::enr::generate_random_remote_enr();
+        if let Some(enr_client) = enr_client {
+            enr.insert("c", enr_client.as_bytes(), &key).unwrap();
+        }
+        enr_records.push(record::get_or_create(&enr, &conn).await.unwrap());
+    }
+    let census_model = census::create(&conn).await.unwrap();
+    let census_model = census::finish(census_model, &enr_records, &conn)
+        .await
+        .unwrap();
+
+    let counts = census_model.client_counts(&conn).await.unwrap();
+    assert_eq!(
+        counts.into_iter().collect::<Vec<_>>(),
+        vec![
+            ("fluffy".to_owned(), 1),
+            ("trin".to_owned(), 2),
+            ("unknown".to_owned(), 1)
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_audit_queue() {
+    let conn = setup_database().await.unwrap();
+    let mut content_models = vec![];
+    for seed in 1..=3 {
+        let content_key = HistoryContentKey::BlockHeaderWithProof(BlockHeaderKey {
+            block_hash: [seed; 32],
+        });
+        content_models.push(content::get_or_create(&content_key, &conn).await.unwrap());
+    }
+    let latest = audit_queue::enqueue(content_models[0].id, SelectionStrategy::Latest, 0, &conn)
+        .await
+        .unwrap();
+    let requested = audit_queue::enqueue(
+        content_models[1].id,
+        SelectionStrategy::Requested,
+        audit_queue::REQUESTED_PRIORITY,
+        &conn,
+    )
+    .await
+    .unwrap();
+    // Content that is already queued keeps its entry.
+    let requeued = audit_queue::enqueue(content_models[0].id, SelectionStrategy::Random, 0, &conn)
+        .await
+        .unwrap();
+    assert_eq!(requeued.id, latest.id);
+    assert_eq!(requeued.strategy, SelectionStrategy::Latest);
+    assert_eq!(
+        audit_queue::count_waiting(SelectionStrategy::Random, &conn)
+            .await
+            .unwrap(),
+        0
+    );
+    assert_eq!(
+        audit_queue::get_pending(content_models[1].id, &conn)
+            .await
+            .unwrap()
+            .map(|entry| entry.id),
+        Some(requested.id)
+    );
+    assert!(audit_queue::get_pending(content_models[2].id, &conn)
+        .await
+        .unwrap()
+        .is_none());
+
+    assert_eq!(
+        audit_queue::count_waiting(SelectionStrategy::Latest, &conn)
+            .await
+            .unwrap(),
+        1
+    );
+    let mut depths = audit_queue::get_depths(&conn).await.unwrap();
+    depths.sort_by_key(|(strategy, _, _)| strategy.as_text());
+    assert_eq!(
+        depths,
+        vec![
+            (SelectionStrategy::Latest, 1, 0),
+            (SelectionStrategy::Requested, 1, 0)
+        ]
+    );
+
+    // Only entries of the given strategies are claimed.
+    let strategies = [SelectionStrategy::Latest, SelectionStrategy::Requested];
+    assert!(audit_queue::claim(&[SelectionStrategy::Random], 10, &conn)
+        .await
+        .unwrap()
+        .is_empty());
+    // The requested entry is claimed first despite being added later.
+    let claimed = audit_queue::claim(&strategies, 1, &conn).await.unwrap();
+    assert_eq!(claimed.len(), 1);
+    assert_eq!(claimed[0].id, requested.id);
+    assert!(claimed[0].claimed_at.is_some());
+    let mut depths = audit_queue::get_depths(&conn).await.unwrap();
+    depths.sort_by_key(|(strategy, _, _)| strategy.as_text());
+    assert_eq!(
+        depths,
+        vec![
+            (SelectionStrategy::Latest, 1, 0),
+            (SelectionStrategy::Requested, 0, 1)
+        ]
+    );
+    let claimed = audit_queue::claim(&strategies, 10, &conn).await.unwrap();
+    assert_eq!(
+        claimed.iter().map(|entry| entry.id).collect::<Vec<_>>(),
+        vec![latest.id]
+    );
+    assert!(audit_queue::claim(&strategies, 10, &conn)
+        .await
+        .unwrap()
+        .is_empty());
+    assert_eq!(
+        audit_queue::count_waiting(SelectionStrategy::Latest, &conn)
+            .await
+            .unwrap(),
+        0
+    );
+
+    // A claim that was never finished expires.
+    let mut abandoned: audit_queue::ActiveModel = claimed[0].clone().into();
+    abandoned.claimed_at = Set(Some(
+        (Utc::now() - chrono::Duration::seconds(audit_queue::CLAIM_TIMEOUT_SECONDS + 1)).into(),
+    ));
+    abandoned.update(&conn).await.unwrap();
+    let reclaimed = audit_queue::claim(&strategies, 10, &conn).await.unwrap();
+    assert_eq!(
+        reclaimed.iter().map(|entry| entry.id).collect::<Vec<_>>(),
+        vec![latest.id]
+    );
+
+    // Released entries can be claimed again straight away.
+    audit_queue::release(latest.id, &conn).await.unwrap();
+    assert_eq!(
+        audit_queue::count_waiting(SelectionStrategy::Latest, &conn)
+            .await
+            .unwrap(),
+        1
+    );
+
+    // Finished entries are no longer pending.
+    audit_queue::complete(requested.id, &conn).await.unwrap();
+    assert!(audit_queue::get_pending(content_models[1].id, &conn)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+/// Tests that requesting content a strategy already queued moves it ahead of the strategy's
+/// other entries.
+#[tokio::test]
+async fn test_audit_queue_request_raises_priority() {
+    let conn = setup_database().await.unwrap();
+    let mut content_models = vec![];
+    for seed in 1..=2 {
+        let content_key = HistoryContentKey::BlockHeaderWithProof(BlockHeaderKey {
+            block_hash: [seed; 32],
+        });
+        let content_model = content::get_or_create(&content_key, &conn).await.unwrap();
+        audit_queue::enqueue(content_model.id, SelectionStrategy::Random, 0, &conn)
+            .await
+            .unwrap();
+        content_models.push(content_model);
+    }
+
+    let queued = audit_queue::get_pending(content_models[1].id, &conn)
+        .await
+        .unwrap()
+        .unwrap();
+    let requested = audit_queue::enqueue(
+        content_models[1].id,
+        SelectionStrategy::Requested,
+        audit_queue::REQUESTED_PRIORITY,
+        &conn,
+    )
+    .await
+    .unwrap();
+    assert_eq!(requested.id, queued.id);
+    assert_eq!(requested.strategy, SelectionStrategy::Requested);
+    assert_eq!(requested.priority, audit_queue::REQUESTED_PRIORITY);
+
+    // A strategy selecting the content again does not lower the priority of the request.
+    let requeued = audit_queue::enqueue(content_models[1].id, SelectionStrategy::Random, 0, &conn)
+        .await
+        .unwrap();
+    assert_eq!(requeued, requested);
+
+    let strategies = [SelectionStrategy::Random, SelectionStrategy::Requested];
+    let claimed = audit_queue::claim(&strategies, 1, &conn).await.unwrap();
+    assert_eq!(claimed.len(), 1);
+    assert_eq!(claimed[0].content_key, content_models[1].id);
+}
+
+/// Tests that concurrent claims on Postgres, which skip locked rows, hand out each entry once.
+///
+/// Runs only when `DATABASE_URL` points at Postgres, as other databases claim differently.
+#[tokio::test]
+async fn test_claim_skip_locked() {
+    let is_postgres = std::env::var("DATABASE_URL")
+        .map(|url| url.starts_with("postgres"))
+        .unwrap_or(false);
+    if !is_postgres {
+        return;
+    }
+    let conn = setup_database().await.unwrap();
+    for seed in 100..120 {
+        let content_key = HistoryContentKey::BlockHeaderWithProof(BlockHeaderKey {
+            block_hash: [seed; 32],
+        });
+        let content_model = content::get_or_create(&content_key, &conn).await.unwrap();
+        audit_queue::enqueue(content_model.id, SelectionStrategy::Random, 0, &conn)
+            .await
+            .unwrap();
+    }
+    let strategies = [SelectionStrategy::Random];
+    let claims = tokio::join!(
+        audit_queue::claim(&strategies, 5, &conn),
+        audit_queue::claim(&strategies, 5, &conn),
+        audit_queue::claim(&strategies, 5, &conn),
+        audit_queue::claim(&strategies, 5, &conn),
+    );
+    let mut claimed_ids = vec![];
+    for claimed in [claims.0, claims.1, claims.2, claims.3] {
+        claimed_ids.extend(claimed.unwrap().into_iter().map(|entry| entry.id));
+    }
+    let claimed_count = claimed_ids.len();
+    claimed_ids.sort();
+    claimed_ids.dedup();
+    assert_eq!(claimed_ids.len(), claimed_count);
+    assert_eq!(claimed_count, 20);
+}
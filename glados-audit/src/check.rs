@@ -0,0 +1,292 @@
+use std::fs;
+
+use anyhow::{anyhow, bail, Result};
+use ethportal_api::types::content_key::{
+    BlockBodyKey, BlockHeaderKey, BlockReceiptsKey, HistoryContentKey, OverlayContentKey,
+};
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+use trin_utils::bytes::{hex_decode, hex_encode};
+
+use entity::{
+    client_info, content,
+    content_audit::{self, SelectionStrategy},
+    execution_metadata, node,
+};
+use glados_core::jsonrpc::PortalClient;
+
+use crate::{cli::CheckArgs, validation::content_is_valid};
+
+/// The outcome of auditing one content key against one portal client.
+#[derive(Debug)]
+pub struct CheckOutcome {
+    pub content_key: HistoryContentKey,
+    pub client_url: String,
+    /// Whether valid content was found, or the reason the audit could not be made.
+    pub result: Result<bool, String>,
+    /// Empty unless a trace was asked for.
+    pub trace: String,
+}
+
+impl CheckOutcome {
+    pub fn result_as_text(&self) -> String {
+        match &self.result {
+            Ok(true) => "success".to_string(),
+            Ok(false) => "fail".to_string(),
+            Err(e) => format!("error: {e}"),
+        }
+    }
+}
+
+/// Audits the content named by the arguments against every client, and prints each outcome.
+pub async fn run_check(
+    args: &CheckArgs,
+    clients: &[PortalClient],
+    conn: &DatabaseConnection,
+) -> Result<Vec<CheckOutcome>> {
+    if clients.is_empty() {
+        bail!("No portal client to audit against.");
+    }
+    let content_keys = collect_content_keys(args, conn).await?;
+    if content_keys.is_empty() {
+        bail!("No content to audit. Pass --content-key, --block-number or --key-file.");
+    }
+    let mut outcomes = vec![];
+    for content_key in &content_keys {
+        for client in clients {
+            let outcome = check_content_key(content_key, client, args.trace).await;
+            println!(
+                "{} {} {}: {}",
+                hex_encode(content_key.to_bytes()),
+                content_key,
+                outcome.client_url,
+                outcome.result_as_text()
+            );
+            if args.trace {
+                println!("{}", outcome.trace);
+            }
+            if args.record {
+                if let Ok(success) = outcome.result {
+                    record_outcome(&outcome, success, client, conn).await?;
+                }
+            }
+            outcomes.push(outcome);
+        }
+    }
+    let passes = outcomes
+        .iter()
+        .filter(|outcome| matches!(outcome.result, Ok(true)))
+        .count();
+    println!("{passes} of {} audits passed", outcomes.len());
+    Ok(outcomes)
+}
+
+/// Gathers the content keys passed directly, read from the file and found for the blocks.
+pub async fn collect_content_keys(
+    args: &CheckArgs,
+    conn: &DatabaseConnection,
+) -> Result<Vec<HistoryContentKey>> {
+    let mut hex_keys = args.content_key.clone();
+    if let Some(path) = &args.key_file {
+        let file = fs::read_to_string(path)
+            .map_err(|e| anyhow!("Could not read key file {}: {e}", path.display()))?;
+        hex_keys.extend(
+            file.lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(str::to_owned),
+        );
+    }
+    let mut content_keys = vec![];
+    for hex_key in hex_keys {
+        match HistoryContentKey::from(hex_decode(&hex_key)?) {
+            HistoryContentKey::Unknown(_) => bail!("Could not decode content key {hex_key}"),
+            content_key => content_keys.push(content_key),
+        }
+    }
+    for block_number in &args.block_number {
+        content_keys.extend(block_content_keys(*block_number, conn).await?);
+    }
+    Ok(content_keys)
+}
+
+/// Returns the header, body and receipts keys of a block that is in the database.
+async fn block_content_keys(
+    block_number: i32,
+    conn: &DatabaseConnection,
+) -> Result<Vec<HistoryContentKey>> {
+    let Some((_, Some(content_model))) = execution_metadata::Entity::find()
+        .filter(execution_metadata::Column::BlockNumber.eq(block_number))
+        .find_also_related(content::Entity)
+        .one(conn)
+        .await?
+    else {
+        bail!("Block {block_number} is not in the database.")
+    };
+    let block_hash = match HistoryContentKey::from(content_model.content_key) {
+        HistoryContentKey::BlockHeaderWithProof(key) => key.block_hash,
+        HistoryContentKey::BlockBody(key) => key.block_hash,
+        HistoryContentKey::BlockReceipts(key) => key.block_hash,
+        _ => bail!("Block {block_number} is not stored with a block hash."),
+    };
+    Ok(vec![
+        HistoryContentKey::BlockHeaderWithProof(BlockHeaderKey { block_hash }),
+        HistoryContentKey::BlockBody(BlockBodyKey { block_hash }),
+        HistoryContentKey::BlockReceipts(BlockReceiptsKey { block_hash }),
+    ])
+}
+
+/// Asks the client to find the content in the network, and checks what it returns.
+pub async fn check_content_key(
+    content_key: &HistoryContentKey,
+    client: &PortalClient,
+    with_trace: bool,
+) -> CheckOutcome {
+    let client_url = client.api.client_url.clone();
+    let response = match with_trace {
+        true => client.api.clone().get_content_with_trace(content_key).await,
+        false => client
+            .api
+            .clone()
+            .get_content(content_key)
+            .await
+            .map(|content| (content, String::new())),
+    };
+    let (result, trace) = match response {
+        Ok((Some(content), trace)) => (Ok(content_is_valid(content_key, &content.raw)), trace),
+        Ok((None, trace)) => (Ok(false), trace),
+        Err(e) => (Err(e.to_string()), String::new()),
+    };
+    CheckOutcome {
+        content_key: content_key.clone(),
+        client_url,
+        result,
+        trace,
+    }
+}
+
+/// Stores the outcome as an audit, adding the content key if it is not yet known.
+async fn record_outcome(
+    outcome: &CheckOutcome,
+    success: bool,
+    client: &PortalClient,
+    conn: &DatabaseConnection,
+) -> Result<content_audit::Model> {
+    let content_key_model = content::get_or_create(&outcome.content_key, conn).await?;
+    let client_info_model = client_info::get_or_create(client.client_info.clone(), conn).await?;
+    let node_model = node::get_or_create(client.enr.node_id().into(), conn).await?;
+    content_audit::create(
+        content_key_model.id,
+        client_info_model.id,
+        node_model.id,
+        success,
+        SelectionStrategy::Requested,
+        outcome.trace.clone(),
+        conn,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use glados_test_support::{
+        block_body_key, setup_database, MockPortalNode, MockResponse, EMPTY_BLOCK_BODY,
+    };
+
+    use super::*;
+
+    /// Tests that content keys are gathered from arguments, a file and block numbers.
+    #[tokio::test]
+    async fn test_collect_content_keys() {
+        let conn = setup_database().await;
+        let block_hash = [7; 32];
+        let header_key = HistoryContentKey::BlockHeaderWithProof(BlockHeaderKey { block_hash });
+        let header_model = content::get_or_create(&header_key, &conn).await.unwrap();
+        execution_metadata::get_or_create(header_model.id, 1000, &conn)
+            .await
+            .unwrap();
+
+        let argument_key = block_body_key(1);
+        let file_key = HistoryContentKey::BlockReceipts(BlockReceiptsKey {
+            block_hash: [2; 32],
+        });
+        let key_file = env::temp_dir().join("glados-audit-test-collect-content-keys.txt");
+        fs::write(
+            &key_file,
+            format!("# Keys to check\n\n{}\n", hex_encode(file_key.to_bytes())),
+        )
+        .unwrap();
+
+        let args = CheckArgs {
+            content_key: vec![hex_encode(argument_key.to_bytes())],
+            block_number: vec![1000],
+            key_file: Some(key_file.clone()),
+            ..Default::default()
+        };
+        let content_keys = collect_content_keys(&args, &conn).await.unwrap();
+        fs::remove_file(key_file).unwrap();
+        assert_eq!(
+            content_keys,
+            vec![
+                argument_key,
+                file_key,
+                header_key,
+                HistoryContentKey::BlockBody(BlockBodyKey { block_hash }),
+                HistoryContentKey::BlockReceipts(BlockReceiptsKey { block_hash }),
+            ]
+        );
+
+        let unknown_block = CheckArgs {
+            block_number: vec![2000],
+            ..Default::default()
+        };
+        assert!(collect_content_keys(&unknown_block, &conn).await.is_err());
+        let invalid_key = CheckArgs {
+            content_key: vec!["0x99".to_owned()],
+            ..Default::default()
+        };
+        assert!(collect_content_keys(&invalid_key, &conn).await.is_err());
+    }
+
+    /// Tests that traced outcomes are recorded when asked for.
+    #[tokio::test]
+    async fn test_run_check() {
+        let conn = setup_database().await;
+        let present_key = block_body_key(1);
+        let absent_key = block_body_key(2);
+        let node = MockPortalNode::new();
+        node.respond_to_content_key(
+            &present_key,
+            MockResponse::Content(hex_decode(EMPTY_BLOCK_BODY).unwrap()),
+        );
+        node.respond_to_content_key(&absent_key, MockResponse::ContentAbsent);
+        let url = node.serve_http().await.unwrap();
+        let client = PortalClient::from(url).await.unwrap();
+
+        let args = CheckArgs {
+            content_key: vec![
+                hex_encode(present_key.to_bytes()),
+                hex_encode(absent_key.to_bytes()),
+            ],
+            trace: true,
+            record: true,
+            ..Default::default()
+        };
+        let outcomes = run_check(&args, &[client], &conn).await.unwrap();
+        let results: Vec<Result<bool, String>> =
+            outcomes.into_iter().map(|outcome| outcome.result).collect();
+        assert_eq!(results, vec![Ok(true), Ok(false)]);
+        assert_eq!(
+            node.request_count("portal_historyTraceRecursiveFindContent"),
+            2
+        );
+
+        let audits = content_audit::Entity::find().all(&conn).await.unwrap();
+        assert_eq!(audits.len(), 2);
+        for audit in audits {
+            assert_eq!(audit.strategy_used, Some(SelectionStrategy::Requested));
+            assert_eq!(audit.trace, "{}");
+        }
+    }
+}
@@ -1,4 +1,6 @@
-use clap::{ArgAction, Parser, ValueEnum};
+use std::{net::SocketAddr, path::PathBuf};
+
+use clap::{ArgAction, Parser, Subcommand, ValueEnum};
 use entity::content_audit::SelectionStrategy;
 
 const DEFAULT_DB_URL: &str = "sqlite::memory:";
@@ -10,7 +12,7 @@ pub struct Args {
     pub database_url: String,
     #[arg(short, long, default_value = "4", help = "number of auditing threads")]
     pub concurrency: u8,
-    #[arg(short, long, action(ArgAction::Append), value_enum, default_value = None, help = "Specific strategy to use. Default is to use all available strategies. May be passed multiple times for multiple strategies (--strategy latest --strategy random). Duplicates are permitted (--strategy random --strategy random).")]
+    #[arg(short, long, action(ArgAction::Append), value_enum, default_value = None, help = "Specific strategy to use. Default is to use all available strategies, except those that audit only one kind of content (block_headers, block_bodies, receipts, epoch_accumulators). May be passed multiple times for multiple strategies (--strategy latest --strategy random). Duplicates are permitted (--strategy random --strategy random).")]
     pub strategy: Option<Vec<SelectionStrategy>>,
     #[arg(
         short,
@@ -40,8 +42,103 @@ pub struct Args {
         help = "relative weight of the 'random' strategy"
     )]
     pub random_strategy_weight: u8,
+    #[arg(
+        long,
+        default_value = "1",
+        help = "relative weight of the 'keyspace stratified' strategy"
+    )]
+    pub keyspace_strategy_weight: u8,
+    #[arg(
+        long,
+        default_value = "1",
+        help = "relative weight of the 'block age stratified' strategy"
+    )]
+    pub block_age_strategy_weight: u8,
+    #[arg(
+        long,
+        default_value = "1",
+        help = "relative weight of the 'block headers' strategy"
+    )]
+    pub block_headers_strategy_weight: u8,
+    #[arg(
+        long,
+        default_value = "1",
+        help = "relative weight of the 'block bodies' strategy"
+    )]
+    pub block_bodies_strategy_weight: u8,
+    #[arg(
+        long,
+        default_value = "1",
+        help = "relative weight of the 'receipts' strategy"
+    )]
+    pub receipts_strategy_weight: u8,
+    #[arg(
+        long,
+        default_value = "1",
+        help = "relative weight of the 'epoch accumulators' strategy"
+    )]
+    pub epoch_accumulators_strategy_weight: u8,
     #[arg(short, long, action(ArgAction::Append))]
     pub portal_client: Vec<String>,
+    #[arg(
+        long,
+        action(ArgAction::Append),
+        help = "Header sent with every request to an https:// or wss:// portal client, written as 'Name: value'. May be passed multiple times."
+    )]
+    pub portal_client_header: Vec<String>,
+    #[arg(
+        long,
+        help = "Bearer token sent in the Authorization header to an https:// or wss:// portal client"
+    )]
+    pub portal_client_bearer_token: Option<String>,
+    #[arg(
+        long,
+        value_enum,
+        default_value = "recursive",
+        help = "How content is audited: by a recursive network lookup, or by asking each known node that should store it"
+    )]
+    pub mode: AuditMode,
+    #[arg(
+        long,
+        default_value = "8",
+        help = "maximum number of nodes audited per content key in 'node_storage' mode"
+    )]
+    pub node_storage_max_nodes: u8,
+    #[arg(
+        long,
+        help = "Address to serve Prometheus metrics at /metrics on, e.g. 127.0.0.1:9101. Metrics are not served unless set."
+    )]
+    pub metrics_address: Option<SocketAddr>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug, Clone, Eq, PartialEq)]
+pub enum Command {
+    /// Audits specific content once against every portal client, prints the outcomes and exits.
+    Check(CheckArgs),
+}
+
+#[derive(clap::Args, Debug, Clone, Default, Eq, PartialEq)]
+pub struct CheckArgs {
+    #[arg(
+        long,
+        action(ArgAction::Append),
+        help = "Hex encoded content key to audit. May be passed multiple times."
+    )]
+    pub content_key: Vec<String>,
+    #[arg(
+        long,
+        action(ArgAction::Append),
+        help = "Block number whose header, body and receipts are audited. The block must be in the database. May be passed multiple times."
+    )]
+    pub block_number: Vec<i32>,
+    #[arg(long, help = "File with one hex encoded content key per line")]
+    pub key_file: Option<PathBuf>,
+    #[arg(long, help = "Print the trace of each audit")]
+    pub trace: bool,
+    #[arg(long, help = "Record the outcome of each audit in the database")]
+    pub record: bool,
 }
 
 impl Default for Args {
@@ -53,8 +150,20 @@ impl Default for Args {
             failed_strategy_weight: 1,
             oldest_strategy_weight: 1,
             random_strategy_weight: 1,
+            keyspace_strategy_weight: 1,
+            block_age_strategy_weight: 1,
+            block_headers_strategy_weight: 1,
+            block_bodies_strategy_weight: 1,
+            receipts_strategy_weight: 1,
+            epoch_accumulators_strategy_weight: 1,
             strategy: None,
             portal_client: vec!["ipc:////tmp/trin-jsonrpc.ipc".to_owned()],
+            portal_client_header: vec![],
+            portal_client_bearer_token: None,
+            mode: AuditMode::Recursive,
+            node_storage_max_nodes: 8,
+            metrics_address: None,
+            command: None,
         }
     }
 }
@@ -145,6 +254,109 @@ mod test {
         };
         assert_eq!(result, expected);
     }
+
+    /// Tests that strategies for one kind of content can be chosen and weighted.
+    #[test]
+    fn test_content_kind_strategies() {
+        const PORTAL_CLIENT_STRING: &str = "ipc:////path/to/ipc";
+        let result = Args::parse_from([
+            "test",
+            "--portal-client",
+            PORTAL_CLIENT_STRING,
+            "--strategy",
+            "receipts",
+            "--strategy",
+            "epoch_accumulators",
+            "--receipts-strategy-weight",
+            "3",
+        ]);
+        let expected = Args {
+            strategy: Some(vec![
+                SelectionStrategy::Receipts,
+                SelectionStrategy::EpochAccumulators,
+            ]),
+            receipts_strategy_weight: 3,
+            portal_client: vec![PORTAL_CLIENT_STRING.to_owned()],
+            ..Default::default()
+        };
+        assert_eq!(result, expected);
+    }
+
+    /// Tests that specific content can be audited once.
+    #[test]
+    fn test_check_command() {
+        const PORTAL_CLIENT_STRING: &str = "ipc:////path/to/ipc";
+        let result = Args::parse_from([
+            "test",
+            "--portal-client",
+            PORTAL_CLIENT_STRING,
+            "check",
+            "--content-key",
+            "0x01",
+            "--block-number",
+            "100",
+            "--block-number",
+            "200",
+            "--trace",
+        ]);
+        let expected = Args {
+            portal_client: vec![PORTAL_CLIENT_STRING.to_owned()],
+            command: Some(Command::Check(CheckArgs {
+                content_key: vec!["0x01".to_owned()],
+                block_number: vec![100, 200],
+                trace: true,
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        assert_eq!(result, expected);
+    }
+
+    /// Tests that headers and a bearer token can be passed for an authenticated endpoint.
+    #[test]
+    fn test_portal_client_headers() {
+        const PORTAL_CLIENT_STRING: &str = "https://portal.example.com";
+        let result = Args::parse_from([
+            "test",
+            "--portal-client",
+            PORTAL_CLIENT_STRING,
+            "--portal-client-header",
+            "X-Api-Key: abc",
+            "--portal-client-header",
+            "X-Team: glados",
+            "--portal-client-bearer-token",
+            "secret",
+        ]);
+        let expected = Args {
+            portal_client: vec![PORTAL_CLIENT_STRING.to_owned()],
+            portal_client_header: vec!["X-Api-Key: abc".to_owned(), "X-Team: glados".to_owned()],
+            portal_client_bearer_token: Some("secret".to_owned()),
+            ..Default::default()
+        };
+        assert_eq!(result, expected);
+    }
+
+    /// Tests that node storage audits can be selected.
+    #[test]
+    fn test_node_storage_mode() {
+        const PORTAL_CLIENT_STRING: &str = "ipc:////path/to/ipc";
+        let result = Args::parse_from([
+            "test",
+            "--portal-client",
+            PORTAL_CLIENT_STRING,
+            "--mode",
+            "node_storage",
+            "--node-storage-max-nodes",
+            "3",
+        ]);
+        let expected = Args {
+            portal_client: vec![PORTAL_CLIENT_STRING.to_owned()],
+            mode: AuditMode::NodeStorage,
+            node_storage_max_nodes: 3,
+            ..Default::default()
+        };
+        assert_eq!(result, expected);
+    }
 }
 
 /// Used by a user to specify the intended form of transport
@@ -155,3 +367,13 @@ pub enum TransportType {
     IPC,
     HTTP,
 }
+
+/// Used by a user to specify how content is audited.
+#[derive(Debug, Clone, Eq, PartialEq, ValueEnum)]
+#[clap(rename_all = "snake_case")]
+pub enum AuditMode {
+    /// Ask the Portal node to find the content anywhere in the network.
+    Recursive,
+    /// Ask each known node whose radius covers the content for it directly.
+    NodeStorage,
+}
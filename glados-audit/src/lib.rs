@@ -1,5 +1,8 @@
 use std::{
     collections::HashMap,
+    future::Future,
+    net::SocketAddr,
+    slice,
     sync::{
         atomic::{AtomicU8, Ordering},
         Arc,
@@ -9,26 +12,38 @@ use std::{
 
 use anyhow::Result;
 use clap::Parser;
-use cli::Args;
+use cli::{Args, AuditMode, Command};
 use ethportal_api::types::content_key::{HistoryContentKey, OverlayContentKey};
 use sea_orm::DatabaseConnection;
 use tokio::{
-    sync::mpsc::{self, Receiver},
-    time::{sleep, Duration},
+    sync::mpsc,
+    task::JoinHandle,
+    time::{sleep, timeout, Duration, Instant},
 };
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info, warn};
 use trin_utils::bytes::hex_encode;
 
 use entity::{
-    client_info, content,
-    content_audit::{self, SelectionStrategy},
+    audit_queue, client_info, content,
+    content_audit::{self, AuditResult, SelectionStrategy},
     execution_metadata, node,
 };
-use glados_core::jsonrpc::PortalClient;
+use glados_core::jsonrpc::{PortalClient, RequestHeaders, TransportConfig};
 
-use crate::{selection::start_audit_selection_task, validation::content_is_valid};
+use crate::{
+    metrics::{record_audit, ERROR_RESULT},
+    node_storage::perform_node_storage_audit,
+    queue::{claim_tasks, enqueue_selected_tasks, release_task},
+    selection::start_audit_selection_task,
+    validation::content_is_valid,
+};
 
+pub mod check;
 pub mod cli;
+pub(crate) mod metrics;
+pub(crate) mod node_storage;
+pub(crate) mod queue;
 pub(crate) mod selection;
 pub(crate) mod validation;
 
@@ -45,6 +60,14 @@ pub struct AuditConfig {
     pub concurrency: u8,
     /// Portal Clients
     pub portal_clients: Vec<PortalClient>,
+    /// Whether to audit the network as a whole, or the individual nodes storing content.
+    pub mode: AuditMode,
+    /// Number of nodes asked for each content key when auditing individual nodes.
+    pub node_storage_max_nodes: u8,
+    /// Where metrics are served, if anywhere.
+    pub metrics_address: Option<SocketAddr>,
+    /// A one-off command to run instead of auditing continuously.
+    pub command: Option<Command>,
 }
 
 impl AuditConfig {
@@ -72,6 +95,8 @@ impl AuditConfig {
                     SelectionStrategy::Random,
                     SelectionStrategy::Failed,
                     SelectionStrategy::SelectOldestUnaudited,
+                    SelectionStrategy::KeyspaceStratified,
+                    SelectionStrategy::BlockAgeStratified,
                 ]
             }
         };
@@ -82,13 +107,29 @@ impl AuditConfig {
                 SelectionStrategy::Random => args.random_strategy_weight,
                 SelectionStrategy::Failed => args.failed_strategy_weight,
                 SelectionStrategy::SelectOldestUnaudited => args.oldest_strategy_weight,
+                SelectionStrategy::KeyspaceStratified => args.keyspace_strategy_weight,
+                SelectionStrategy::BlockAgeStratified => args.block_age_strategy_weight,
+                SelectionStrategy::BlockHeaders => args.block_headers_strategy_weight,
+                SelectionStrategy::BlockBodies => args.block_bodies_strategy_weight,
+                SelectionStrategy::Receipts => args.receipts_strategy_weight,
+                SelectionStrategy::EpochAccumulators => args.epoch_accumulators_strategy_weight,
+                // Cannot be chosen on the command line.
+                SelectionStrategy::Requested => 1,
             };
             weights.insert(strat.clone(), weight);
         }
 
+        let mut headers = RequestHeaders::default();
+        for header in &args.portal_client_header {
+            headers.insert_parsed(header)?;
+        }
+        if let Some(token) = &args.portal_client_bearer_token {
+            headers.insert_bearer_token(token)?;
+        }
         let mut portal_clients: Vec<PortalClient> = vec![];
         for client_url in args.portal_client {
-            let client = PortalClient::from(client_url).await?;
+            let transport = TransportConfig::from_url(&client_url, headers.clone())?;
+            let client = PortalClient::from_config(transport).await?;
             info!("Found a portal client with type: {:?}", client.client_info);
             portal_clients.push(client);
         }
@@ -99,6 +140,10 @@ impl AuditConfig {
             weights,
             concurrency: args.concurrency,
             portal_clients,
+            mode: args.mode,
+            node_storage_max_nodes: args.node_storage_max_nodes,
+            metrics_address: args.metrics_address,
+            command: args.command,
         })
     }
 }
@@ -107,18 +152,35 @@ impl AuditConfig {
 pub struct AuditTask {
     pub strategy: SelectionStrategy,
     pub content_key: HistoryContentKey,
+    /// The audit queue entry the task was claimed from, which is removed once the audit is done.
+    pub queue_id: Option<i32>,
 }
 
-// Associates strategies with their channels and weights.
+/// How long audits in progress are given to finish after shutdown is signalled.
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long collation waits before checking the audit queue again after finding it empty.
+///
+/// The queue is a database table, so an empty queue would otherwise be polled in a tight loop.
+const COLLATION_IDLE_INTERVAL: Duration = Duration::from_millis(500);
+
+// Associates strategies with their weights.
 #[derive(Debug)]
-pub struct TaskChannel {
+pub struct TaskWeight {
     strategy: SelectionStrategy,
     weight: u8,
-    rx: Receiver<AuditTask>,
 }
 
-pub async fn run_glados_audit(conn: DatabaseConnection, config: AuditConfig) {
-    let mut task_channels: Vec<TaskChannel> = vec![];
+/// Audits content until shutdown is signalled.
+///
+/// On shutdown, selection and collation stop, tasks that were claimed but not started are
+/// returned to the queue, and audits in progress are given [SHUTDOWN_TIMEOUT] to finish.
+pub async fn run_glados_audit(
+    conn: DatabaseConnection,
+    config: AuditConfig,
+    shutdown: CancellationToken,
+) {
+    let mut task_weights: Vec<TaskWeight> = vec![];
     for strategy in &config.strategies {
         // Each strategy sends tasks to a separate channel.
         let (tx, rx) = mpsc::channel::<AuditTask>(100);
@@ -126,50 +188,73 @@ pub async fn run_glados_audit(conn: DatabaseConnection, config: AuditConfig) {
             error!(strategy=?strategy, "no weight for strategy");
             return
         };
-        let task_channel = TaskChannel {
+        task_weights.push(TaskWeight {
             strategy: strategy.clone(),
             weight: *weight,
-            rx,
-        };
-        task_channels.push(task_channel);
+        });
         // Strategies generate tasks in their own thread for their own channel.
-        tokio::spawn(start_audit_selection_task(
-            strategy.clone(),
-            tx,
-            conn.clone(),
+        tokio::spawn(stop_on_shutdown(
+            start_audit_selection_task(strategy.clone(), tx, conn.clone()),
+            shutdown.clone(),
+        ));
+        // Selected tasks are stored in the audit queue, where any auditor can claim them.
+        tokio::spawn(stop_on_shutdown(
+            enqueue_selected_tasks(strategy.clone(), rx, conn.clone()),
+            shutdown.clone(),
         ));
     }
-    // Collation of generated tasks, taken proportional to weights.
-    let (collation_tx, collation_rx) = mpsc::channel::<AuditTask>(100);
-    tokio::spawn(start_collation(collation_tx, task_channels));
+    // Collation of queued tasks, claimed proportional to weights. The channel is kept small
+    // so that tasks are not claimed long before there is capacity to audit them.
+    let (collation_tx, collation_rx) = mpsc::channel::<AuditTask>(config.concurrency.max(1).into());
+    tokio::spawn(start_collation(
+        collation_tx,
+        task_weights,
+        conn.clone(),
+        shutdown.clone(),
+    ));
     // Perform collated audit tasks.
-    tokio::spawn(perform_content_audits(config, collation_rx, conn));
-    debug!("setting up CTRL+C listener");
-    tokio::signal::ctrl_c()
-        .await
-        .expect("failed to pause until ctrl-c");
+    perform_content_audits(config, collation_rx, conn, shutdown).await;
+    info!("audits stopped");
+}
 
-    info!("got CTRL+C. shutting down...");
+/// Runs the task until shutdown, when it is dropped at whatever point it is waiting.
+async fn stop_on_shutdown(task: impl Future<Output = ()>, shutdown: CancellationToken) {
+    tokio::select! {
+        _ = shutdown.cancelled() => {}
+        _ = task => {}
+    }
 }
 
-/// Listens to tasks coming on different strategy channels and selects
-/// according to strategy weight. Collated audit tasks are sent in a single
-/// channel for completion.
+/// Claims tasks from the audit queue for each strategy according to strategy
+/// weight. Collated audit tasks are sent in a single channel for completion.
+///
+/// Requested audits are claimed ahead of all strategies, whatever strategies
+/// this auditor runs.
 async fn start_collation(
     collation_tx: mpsc::Sender<AuditTask>,
-    mut task_channels: Vec<TaskChannel>,
+    task_weights: Vec<TaskWeight>,
+    conn: DatabaseConnection,
+    shutdown: CancellationToken,
 ) {
-    loop {
-        for tasks in task_channels.iter_mut() {
+    // Claims already started are finished, so that no claimed task is lost.
+    while !shutdown.is_cancelled() {
+        let mut collated = claim_tasks(
+            &[SelectionStrategy::Requested],
+            collation_tx.capacity(),
+            &collation_tx,
+            &conn,
+        )
+        .await;
+        for tasks in &task_weights {
             debug!(strategy=?tasks.strategy, max=tasks.weight, "collating");
-            for _ in 0..tasks.weight {
-                match tasks.rx.try_recv() {
-                    Ok(task) => collation_tx
-                        .send(task)
-                        .await
-                        .expect("Unable to collate task"),
-                    Err(_) => break,
-                }
+            let limit = collation_tx.capacity().min(tasks.weight.into());
+            let strategies = slice::from_ref(&tasks.strategy);
+            collated += claim_tasks(strategies, limit, &collation_tx, &conn).await;
+        }
+        if collated == 0 {
+            tokio::select! {
+                _ = shutdown.cancelled() => {}
+                _ = sleep(COLLATION_IDLE_INTERVAL) => {}
             }
         }
     }
@@ -179,13 +264,17 @@ async fn perform_content_audits(
     config: AuditConfig,
     mut rx: mpsc::Receiver<AuditTask>,
     conn: DatabaseConnection,
+    shutdown: CancellationToken,
 ) {
     let concurrency = config.concurrency;
     let active_threads = Arc::new(AtomicU8::new(0));
+    // Audits in progress, with the queue entry of each audit that came from the queue.
+    let mut in_flight: Vec<(Option<i32>, JoinHandle<()>)> = vec![];
 
     let mut cycle_of_clients = config.portal_clients.iter().cycle();
 
     loop {
+        in_flight.retain(|(_, audit)| !audit.is_finished());
         let active_count = active_threads.load(Ordering::Relaxed);
         if active_count >= concurrency {
             // Each audit is performed in new thread if enough concurrency is available.
@@ -194,8 +283,10 @@ async fn perform_content_audits(
                 max.threads = concurrency,
                 "Waiting for responses on all audit threads... Sleeping..."
             );
-            sleep(Duration::from_millis(5000)).await;
-            continue;
+            tokio::select! {
+                _ = shutdown.cancelled() => break,
+                _ = sleep(Duration::from_millis(5000)) => continue,
+            }
         }
 
         debug!(
@@ -204,27 +295,98 @@ async fn perform_content_audits(
             "Checking Rx channel for audits"
         );
 
-        match rx.recv().await {
-            Some(task) => {
-                active_threads.fetch_add(1, Ordering::Relaxed);
-                let client = match cycle_of_clients.next() {
-                    Some(client) => client,
-                    None => {
-                        error!("Empty list of clients for audit.");
-                        return;
-                    }
-                };
-                tokio::spawn(perform_single_audit(
-                    active_threads.clone(),
-                    task,
-                    client.clone(),
-                    conn.clone(),
-                ))
-            }
+        let task = tokio::select! {
+            _ = shutdown.cancelled() => break,
+            task = rx.recv() => match task {
+                Some(task) => task,
+                None => break,
+            },
+        };
+        active_threads.fetch_add(1, Ordering::Relaxed);
+        let client = match cycle_of_clients.next() {
+            Some(client) => client,
             None => {
-                continue;
+                error!("Empty list of clients for audit.");
+                return;
             }
         };
+        let queue_id = task.queue_id;
+        let audit = tokio::spawn(perform_audit(
+            active_threads.clone(),
+            task,
+            client.clone(),
+            config.mode.clone(),
+            config.node_storage_max_nodes,
+            conn.clone(),
+        ));
+        in_flight.push((queue_id, audit));
+    }
+
+    // Tasks that were claimed but not started are left for another auditor.
+    rx.close();
+    while let Some(task) = rx.recv().await {
+        release_task(task.queue_id, &conn).await;
+    }
+    info!(
+        audits = in_flight.len(),
+        "Waiting for audits in progress to finish."
+    );
+    let finished = timeout(SHUTDOWN_TIMEOUT, async {
+        for (_, audit) in in_flight.iter_mut() {
+            if let Err(e) = audit.await {
+                error!(err=?e, "Audit did not finish.");
+            }
+        }
+    })
+    .await;
+    if finished.is_err() {
+        warn!(
+            timeout.seconds = SHUTDOWN_TIMEOUT.as_secs(),
+            "Audits in progress did not finish in time, abandoning them."
+        );
+        for (queue_id, audit) in in_flight {
+            if audit.is_finished() {
+                continue;
+            }
+            audit.abort();
+            // An abandoned audit is returned to the queue rather than left claimed.
+            release_task(queue_id, &conn).await;
+        }
+    }
+}
+
+/// Performs an audit in the configured mode, then removes its task from the audit queue.
+///
+/// The task is removed whether or not the audit succeeded, so that a key which cannot be
+/// audited is not retried forever.
+async fn perform_audit(
+    active_threads: Arc<AtomicU8>,
+    task: AuditTask,
+    client: PortalClient,
+    mode: AuditMode,
+    node_storage_max_nodes: u8,
+    conn: DatabaseConnection,
+) {
+    let queue_id = task.queue_id;
+    match mode {
+        AuditMode::Recursive => {
+            perform_single_audit(active_threads, task, client, conn.clone()).await
+        }
+        AuditMode::NodeStorage => {
+            perform_node_storage_audit(
+                active_threads,
+                task,
+                client,
+                node_storage_max_nodes,
+                conn.clone(),
+            )
+            .await
+        }
+    }
+    if let Some(queue_id) = queue_id {
+        if let Err(e) = audit_queue::complete(queue_id, &conn).await {
+            error!(queue.id = queue_id, err=?e, "Could not remove audit from the queue.");
+        }
     }
 }
 
@@ -245,6 +407,10 @@ async fn perform_single_audit(
         client.url = client.api.client_url.clone(),
         "auditing content",
     );
+    let strategy = task.strategy.as_text();
+    // Version info from some clients is quoted.
+    let client_name = client_info::client_name(client_info.trim_matches('"')).to_owned();
+    let started = Instant::now();
     let content_response = match client.api.get_content(&task.content_key).await {
         Ok(c) => c,
         Err(e) => {
@@ -253,6 +419,7 @@ async fn perform_single_audit(
                 err=?e,
                 "Problem requesting content from Portal node."
             );
+            record_audit(ERROR_RESULT, &strategy, &client_name, None);
             active_threads.fetch_sub(1, Ordering::Relaxed);
             return;
         }
@@ -263,6 +430,16 @@ async fn perform_single_audit(
         Some(content_bytes) => content_is_valid(&task.content_key, &content_bytes.raw),
         None => false,
     };
+    let result = match audit_result {
+        true => AuditResult::Success,
+        false => AuditResult::Failure,
+    };
+    record_audit(
+        &result.as_text(),
+        &strategy,
+        &client_name,
+        Some(started.elapsed()),
+    );
 
     let content_key_model = match content::get(&task.content_key, &conn).await {
         Ok(Some(m)) => m,
@@ -350,3 +527,369 @@ async fn perform_single_audit(
     };
     active_threads.fetch_sub(1, Ordering::Relaxed);
 }
+
+#[cfg(test)]
+mod tests {
+    use glados_test_support::{
+        block_body_key, insert_content, setup_database, MockPortalNode, MockResponse,
+        EMPTY_BLOCK_BODY, MOCK_CLIENT_VERSION,
+    };
+    use sea_orm::EntityTrait;
+    use trin_utils::bytes::hex_decode;
+
+    use entity::content_audit::AuditResult;
+
+    use super::*;
+    use crate::metrics::{AUDITS, AUDIT_DURATION};
+
+    /// Audits the content key against the node at the url, and returns all recorded audits.
+    async fn audit_content(
+        client_url: String,
+        content_key: HistoryContentKey,
+        conn: &DatabaseConnection,
+    ) -> Vec<content_audit::Model> {
+        let client = PortalClient::from(client_url).await.unwrap();
+        let active_threads = Arc::new(AtomicU8::new(1));
+        let task = AuditTask {
+            strategy: SelectionStrategy::Latest,
+            content_key,
+            queue_id: None,
+        };
+        perform_single_audit(active_threads.clone(), task, client, conn.clone()).await;
+        assert_eq!(active_threads.load(Ordering::Relaxed), 0);
+        content_audit::Entity::find().all(conn).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_audit_content_present() {
+        let conn = setup_database().await;
+        let content_key = block_body_key(1);
+        insert_content(&content_key, &conn).await;
+        let node = MockPortalNode::new();
+        node.respond_to_content_key(
+            &content_key,
+            MockResponse::Content(hex_decode(EMPTY_BLOCK_BODY).unwrap()),
+        );
+
+        let url = node.serve_http().await.unwrap();
+        let audits = audit_content(url, content_key, &conn).await;
+        assert_eq!(audits.len(), 1);
+        assert_eq!(audits[0].result, AuditResult::Success);
+        assert_eq!(audits[0].strategy_used, Some(SelectionStrategy::Latest));
+
+        let client = client_info::Entity::find_by_id(audits[0].client_info.unwrap())
+            .one(&conn)
+            .await
+            .unwrap()
+            .unwrap();
+        // The version is currently stored as the raw JSON string, including quotes.
+        assert!(client.version_info.contains(MOCK_CLIENT_VERSION));
+        let audited_node = node::Entity::find_by_id(audits[0].node.unwrap())
+            .one(&conn)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(audited_node.node_id, node.enr().node_id().raw().to_vec());
+    }
+
+    /// Tests that audits are counted and timed by result, strategy and client.
+    ///
+    /// The metrics are process-wide and shared with other tests, so only their change
+    /// across the audits is checked.
+    #[tokio::test]
+    async fn test_audit_metrics() {
+        let conn = setup_database().await;
+        let node = MockPortalNode::new();
+        let url = node.serve_http().await.unwrap();
+        let client = PortalClient::from(url).await.unwrap();
+        let present_key = block_body_key(1);
+        let absent_key = block_body_key(2);
+        let error_key = block_body_key(3);
+        node.respond_to_content_key(
+            &present_key,
+            MockResponse::Content(hex_decode(EMPTY_BLOCK_BODY).unwrap()),
+        );
+        node.respond_to_content_key(&absent_key, MockResponse::ContentAbsent);
+        node.respond_to_content_key(
+            &error_key,
+            MockResponse::Error {
+                code: -32099,
+                message: "lookup failed".to_owned(),
+            },
+        );
+        let strategy = SelectionStrategy::BlockBodies;
+        let labels = strategy.as_text();
+        let results = ["success", "fail", ERROR_RESULT];
+        let counts =
+            || results.map(|result| AUDITS.with_label_values(&[result, &labels, "trin"]).get());
+        let durations = || {
+            AUDIT_DURATION
+                .with_label_values(&[&labels, "trin"])
+                .get_sample_count()
+        };
+        let counts_before = counts();
+        let durations_before = durations();
+
+        for content_key in [present_key, absent_key, error_key] {
+            insert_content(&content_key, &conn).await;
+            let task = AuditTask {
+                strategy: strategy.clone(),
+                content_key,
+                queue_id: None,
+            };
+            let active_threads = Arc::new(AtomicU8::new(1));
+            perform_single_audit(active_threads, task, client.clone(), conn.clone()).await;
+        }
+
+        for (after, before) in counts().into_iter().zip(counts_before) {
+            assert_eq!(after - before, 1);
+        }
+        // Only audits that were answered are timed.
+        assert_eq!(durations() - durations_before, 2);
+    }
+
+    #[tokio::test]
+    async fn test_audit_content_absent() {
+        let conn = setup_database().await;
+        let content_key = block_body_key(1);
+        insert_content(&content_key, &conn).await;
+        let node = MockPortalNode::new();
+        node.respond_to_content_key(&content_key, MockResponse::ContentAbsent);
+
+        let url = node.serve_ipc().await.unwrap();
+        let audits = audit_content(url, content_key, &conn).await;
+        assert_eq!(audits.len(), 1);
+        assert_eq!(audits[0].result, AuditResult::Failure);
+    }
+
+    /// Tests that content which does not decode fails the audit.
+    #[tokio::test]
+    async fn test_audit_invalid_content() {
+        let conn = setup_database().await;
+        let content_key = block_body_key(1);
+        insert_content(&content_key, &conn).await;
+        let node = MockPortalNode::new();
+        node.respond_to_content_key(&content_key, MockResponse::Content(vec![0x12, 0x34]));
+
+        let url = node.serve_http().await.unwrap();
+        let audits = audit_content(url, content_key, &conn).await;
+        assert_eq!(audits.len(), 1);
+        assert_eq!(audits[0].result, AuditResult::Failure);
+    }
+
+    /// Tests that no audit is recorded when the node cannot give a usable response.
+    #[tokio::test]
+    async fn test_audit_not_recorded_on_bad_response() {
+        let conn = setup_database().await;
+        let content_key = block_body_key(1);
+        insert_content(&content_key, &conn).await;
+        let node = MockPortalNode::new();
+        let http_url = node.serve_http().await.unwrap();
+        let ipc_url = node.serve_ipc().await.unwrap();
+
+        let bad_responses = [
+            MockResponse::Malformed,
+            MockResponse::Error {
+                code: -32000,
+                message: "internal error".to_owned(),
+            },
+        ];
+        for response in bad_responses {
+            node.respond_to_content_key(&content_key, response);
+            for url in [&http_url, &ipc_url] {
+                let audits = audit_content(url.clone(), content_key.clone(), &conn).await;
+                assert!(audits.is_empty());
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_audit_slow_response() {
+        let conn = setup_database().await;
+        let content_key = block_body_key(1);
+        insert_content(&content_key, &conn).await;
+        let node = MockPortalNode::new();
+        node.respond_to_content_key(
+            &content_key,
+            MockResponse::Slow(
+                Duration::from_millis(200),
+                Box::new(MockResponse::Content(hex_decode(EMPTY_BLOCK_BODY).unwrap())),
+            ),
+        );
+
+        let url = node.serve_ipc().await.unwrap();
+        let audits = audit_content(url, content_key, &conn).await;
+        assert_eq!(audits.len(), 1);
+        assert_eq!(audits[0].result, AuditResult::Success);
+    }
+
+    /// Tests that content in the database is selected, audited and recorded.
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_run_glados_audit() {
+        let conn = setup_database().await;
+        let present_key = block_body_key(1);
+        let absent_key = block_body_key(2);
+        insert_content(&present_key, &conn).await;
+        insert_content(&absent_key, &conn).await;
+        let node = MockPortalNode::new();
+        node.respond_to_content_key(
+            &present_key,
+            MockResponse::Content(hex_decode(EMPTY_BLOCK_BODY).unwrap()),
+        );
+        node.respond_to_content_key(&absent_key, MockResponse::ContentAbsent);
+
+        let url = node.serve_http().await.unwrap();
+        let config = AuditConfig {
+            database_url: "sqlite::memory:".to_owned(),
+            strategies: vec![SelectionStrategy::Latest],
+            weights: HashMap::from([(SelectionStrategy::Latest, 1)]),
+            concurrency: 2,
+            portal_clients: vec![PortalClient::from(url).await.unwrap()],
+            mode: AuditMode::Recursive,
+            node_storage_max_nodes: 8,
+            metrics_address: None,
+            command: None,
+        };
+        let shutdown = CancellationToken::new();
+        let audit = tokio::spawn(run_glados_audit(conn.clone(), config, shutdown.clone()));
+
+        let mut audits = vec![];
+        for _ in 0..100 {
+            audits = content_audit::Entity::find().all(&conn).await.unwrap();
+            if audits.len() == 2 {
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        shutdown.cancel();
+        audit.await.unwrap();
+
+        assert_eq!(audits.len(), 2);
+        for audit in audits {
+            let expected = match audit.content_key {
+                1 => AuditResult::Success,
+                _ => AuditResult::Failure,
+            };
+            assert_eq!(audit.result, expected);
+        }
+    }
+
+    /// Tests that an audit asked for through the queue table is performed and then dequeued.
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_run_glados_audit_queued() {
+        let conn = setup_database().await;
+        let content_key = block_body_key(1);
+        insert_content(&content_key, &conn).await;
+        let node = MockPortalNode::new();
+        node.respond_to_content_key(
+            &content_key,
+            MockResponse::Content(hex_decode(EMPTY_BLOCK_BODY).unwrap()),
+        );
+        let content_key_model = content::get(&content_key, &conn).await.unwrap().unwrap();
+        audit_queue::enqueue(
+            content_key_model.id,
+            SelectionStrategy::Requested,
+            audit_queue::REQUESTED_PRIORITY,
+            &conn,
+        )
+        .await
+        .unwrap();
+
+        let url = node.serve_http().await.unwrap();
+        let config = AuditConfig {
+            database_url: "sqlite::memory:".to_owned(),
+            strategies: vec![],
+            weights: HashMap::new(),
+            concurrency: 2,
+            portal_clients: vec![PortalClient::from(url).await.unwrap()],
+            mode: AuditMode::Recursive,
+            node_storage_max_nodes: 8,
+            metrics_address: None,
+            command: None,
+        };
+        let shutdown = CancellationToken::new();
+        let audit = tokio::spawn(run_glados_audit(conn.clone(), config, shutdown.clone()));
+
+        let mut pending = true;
+        for _ in 0..100 {
+            pending = audit_queue::get_pending(content_key_model.id, &conn)
+                .await
+                .unwrap()
+                .is_some();
+            if !pending {
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        shutdown.cancel();
+        audit.await.unwrap();
+
+        assert!(!pending);
+        let audits = content_audit::Entity::find().all(&conn).await.unwrap();
+        assert_eq!(audits.len(), 1);
+        assert_eq!(audits[0].result, AuditResult::Success);
+        assert_eq!(audits[0].strategy_used, Some(SelectionStrategy::Requested));
+    }
+
+    /// Tests that shutdown lets an audit in progress finish and returns unstarted tasks to the queue.
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_run_glados_audit_shutdown() {
+        let conn = setup_database().await;
+        let slow_key = block_body_key(1);
+        let waiting_key = block_body_key(2);
+        let node = MockPortalNode::new();
+        for content_key in [&slow_key, &waiting_key] {
+            insert_content(content_key, &conn).await;
+            node.respond_to_content_key(
+                content_key,
+                MockResponse::Slow(
+                    Duration::from_millis(500),
+                    Box::new(MockResponse::Content(hex_decode(EMPTY_BLOCK_BODY).unwrap())),
+                ),
+            );
+            let content_key_model = content::get(content_key, &conn).await.unwrap().unwrap();
+            audit_queue::enqueue(
+                content_key_model.id,
+                SelectionStrategy::Requested,
+                audit_queue::REQUESTED_PRIORITY,
+                &conn,
+            )
+            .await
+            .unwrap();
+        }
+
+        let url = node.serve_http().await.unwrap();
+        let config = AuditConfig {
+            database_url: "sqlite::memory:".to_owned(),
+            strategies: vec![],
+            weights: HashMap::new(),
+            concurrency: 1,
+            portal_clients: vec![PortalClient::from(url).await.unwrap()],
+            mode: AuditMode::Recursive,
+            node_storage_max_nodes: 8,
+            metrics_address: None,
+            command: None,
+        };
+        let shutdown = CancellationToken::new();
+        let audit = tokio::spawn(run_glados_audit(conn.clone(), config, shutdown.clone()));
+
+        // Shut down once the first audit is underway, while the second waits its turn.
+        for _ in 0..100 {
+            if node.request_count("portal_historyRecursiveFindContent") > 0 {
+                break;
+            }
+            sleep(Duration::from_millis(10)).await;
+        }
+        shutdown.cancel();
+        audit.await.unwrap();
+
+        assert_eq!(node.request_count("portal_historyRecursiveFindContent"), 1);
+        let audits = content_audit::Entity::find().all(&conn).await.unwrap();
+        assert_eq!(audits.len(), 1);
+        assert_eq!(audits[0].result, AuditResult::Success);
+        let queued = audit_queue::Entity::find().all(&conn).await.unwrap();
+        assert_eq!(queued.len(), 1);
+        assert_ne!(queued[0].content_key, audits[0].content_key);
+        assert!(queued[0].claimed_at.is_none());
+    }
+}
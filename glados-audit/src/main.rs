@@ -1,8 +1,11 @@
 use anyhow::Result;
 use sea_orm::Database;
-use tracing::{debug, info};
+use tokio::signal;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info};
 
-use glados_audit::{run_glados_audit, AuditConfig};
+use glados_audit::{check::run_check, cli::Command, run_glados_audit, AuditConfig};
+use glados_core::metrics;
 use migration::{Migrator, MigratorTrait};
 
 #[tokio::main]
@@ -36,6 +39,29 @@ async fn main() -> Result<()> {
 
     Migrator::up(&conn, None).await?;
 
-    run_glados_audit(conn, config).await;
+    match &config.command {
+        Some(Command::Check(args)) => {
+            run_check(args, &config.portal_clients, &conn).await?;
+        }
+        None => {
+            if let Some(address) = config.metrics_address {
+                tokio::spawn(async move {
+                    if let Err(e) = metrics::serve(address).await {
+                        error!(err=?e, "Could not serve metrics.");
+                    }
+                });
+            }
+            let shutdown = CancellationToken::new();
+            let mut audit = tokio::spawn(run_glados_audit(conn, config, shutdown.clone()));
+            tokio::select! {
+                _ = signal::ctrl_c() => {
+                    info!("got CTRL+C. shutting down...");
+                    shutdown.cancel();
+                    audit.await?;
+                }
+                _ = &mut audit => {}
+            }
+        }
+    }
     Ok(())
 }
@@ -0,0 +1,50 @@
+use std::time::Duration;
+
+use lazy_static::lazy_static;
+use prometheus::{
+    register_histogram_vec, register_int_counter_vec, register_int_gauge_vec, HistogramVec,
+    IntCounterVec, IntGaugeVec,
+};
+
+lazy_static! {
+    /// Audits made, by result, strategy and client name.
+    ///
+    /// The client is the local portal client for recursive audits, and the audited node's
+    /// advertised client for node storage audits.
+    pub static ref AUDITS: IntCounterVec = register_int_counter_vec!(
+        "glados_audit_audits_total",
+        "Audits made, by result, strategy and client.",
+        &["result", "strategy", "client"]
+    )
+    .expect("audit metric can be registered");
+
+    /// Time taken by a portal client to answer a content request, by strategy and client name.
+    pub static ref AUDIT_DURATION: HistogramVec = register_histogram_vec!(
+        "glados_audit_duration_seconds",
+        "Time taken by a portal client to answer an audit.",
+        &["strategy", "client"],
+        vec![0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0]
+    )
+    .expect("audit duration metric can be registered");
+
+    /// Tasks in the audit queue that are not yet claimed, by strategy.
+    pub static ref QUEUE_WAITING: IntGaugeVec = register_int_gauge_vec!(
+        "glados_audit_queue_waiting",
+        "Unclaimed tasks in the audit queue, by strategy.",
+        &["strategy"]
+    )
+    .expect("queue metric can be registered");
+}
+
+/// Result label of audits that could not be made because a request to a portal client failed.
+pub(crate) const ERROR_RESULT: &str = "error";
+
+/// Counts an audit, and records how long the client took to answer when it did.
+pub(crate) fn record_audit(result: &str, strategy: &str, client: &str, duration: Option<Duration>) {
+    AUDITS.with_label_values(&[result, strategy, client]).inc();
+    if let Some(duration) = duration {
+        AUDIT_DURATION
+            .with_label_values(&[strategy, client])
+            .observe(duration.as_secs_f64());
+    }
+}
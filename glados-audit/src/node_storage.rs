@@ -0,0 +1,453 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::{
+    atomic::{AtomicU8, Ordering},
+    Arc,
+};
+use std::time::Instant;
+
+use ethportal_api::types::{content_key::OverlayContentKey, discv5::Enr};
+use glados_core::jsonrpc::{DataRadius, FindContentInfo, PortalApi, PortalClient, Subnetwork};
+use sea_orm::DatabaseConnection;
+use tokio::task::JoinSet;
+use tracing::{debug, error, info, warn};
+use trin_utils::bytes::hex_encode;
+
+use entity::{
+    client_info::{self, UNKNOWN_CLIENT},
+    content,
+    content_audit::AuditResult,
+    node, node_storage_audit, record,
+};
+
+use crate::{
+    metrics::{record_audit, ERROR_RESULT},
+    validation::content_is_valid,
+    AuditTask,
+};
+
+/// Number of the closest known nodes that are checked for a content key.
+const MAX_CANDIDATES: u64 = 100;
+
+/// Number of candidates that are pinged at once.
+const CONCURRENT_PINGS: usize = 16;
+
+/// Audits whether the known nodes that are responsible for some content are storing it.
+///
+/// Known nodes closest to the content id are pinged for their radius, several at a time.
+/// Those whose radius covers the content are sent a FINDCONTENT directly, closest first,
+/// and the answer is recorded for that node. Nodes that cannot be reached are skipped.
+///
+/// After auditing finishes the thread counter is decremented.
+pub async fn perform_node_storage_audit(
+    active_threads: Arc<AtomicU8>,
+    task: AuditTask,
+    client: PortalClient,
+    max_nodes: u8,
+    conn: DatabaseConnection,
+) {
+    audit_responsible_nodes(&task, &client, max_nodes, &conn).await;
+    active_threads.fetch_sub(1, Ordering::Relaxed);
+}
+
+async fn audit_responsible_nodes(
+    task: &AuditTask,
+    client: &PortalClient,
+    max_nodes: u8,
+    conn: &DatabaseConnection,
+) {
+    let content_key = hex_encode(task.content_key.to_bytes());
+    let strategy = task.strategy.as_text();
+    let content_model = match content::get(&task.content_key, conn).await {
+        Ok(Some(m)) => m,
+        Ok(None) => {
+            error!(content.key = content_key, "Content key not found in db.");
+            return;
+        }
+        Err(e) => {
+            error!(content.key=content_key, err=?e, "Could not look up content key in db.");
+            return;
+        }
+    };
+    let content_id = task.content_key.content_id();
+    let candidates = match node::closest_to_content(content_id, MAX_CANDIDATES, conn).await {
+        Ok(nodes) => nodes,
+        Err(e) => {
+            error!(content.key=content_key, err=?e, "Could not look up nodes near content.");
+            return;
+        }
+    };
+
+    let mut covering = CoveringNodes::new(
+        candidates,
+        content_key.clone(),
+        client.api.clone(),
+        conn.clone(),
+    );
+    let mut audited: u8 = 0;
+    while audited < max_nodes {
+        let Some(node) = covering.next().await else {
+            break;
+        };
+        let CoveringNode {
+            node_id,
+            record,
+            enr,
+            radius,
+        } = node;
+        let client_name = match &record.client {
+            Some(enr_client) => {
+                client_info::client_name(&client_info::version_info_from_enr(enr_client)).to_owned()
+            }
+            None => UNKNOWN_CLIENT.to_owned(),
+        };
+        let started = Instant::now();
+        let content_found = match client
+            .api
+            .find_content(Subnetwork::History, &enr, &task.content_key)
+            .await
+        {
+            Ok(FindContentInfo::Content(content)) => {
+                content_is_valid(&task.content_key, &content.raw)
+            }
+            Ok(FindContentInfo::Enrs(_)) => false,
+            Ok(FindContentInfo::ConnectionId(_)) => {
+                warn!(
+                    content.key = content_key,
+                    node.id = node_id,
+                    "Content offered over uTP cannot be validated."
+                );
+                continue;
+            }
+            Err(e) => {
+                debug!(content.key=content_key, node.id=node_id, err=?e, "Node did not respond to find content.");
+                record_audit(ERROR_RESULT, &strategy, &client_name, None);
+                continue;
+            }
+        };
+        let result = match content_found {
+            true => AuditResult::Success,
+            false => AuditResult::Failure,
+        };
+        record_audit(
+            &result.as_text(),
+            &strategy,
+            &client_name,
+            Some(started.elapsed()),
+        );
+
+        let mut data_radius = [0u8; 32];
+        radius.to_big_endian(&mut data_radius);
+        if let Err(e) = node_storage_audit::create(
+            content_model.id,
+            &record,
+            content_found,
+            task.strategy.clone(),
+            data_radius.to_vec(),
+            conn,
+        )
+        .await
+        {
+            error!(content.key=content_key, node.id=node_id, err=?e, "Could not create node storage audit entry in db.");
+            continue;
+        }
+        audited += 1;
+        info!(
+            content.key = content_key,
+            node.id = node_id,
+            audit.pass = content_found,
+            "Node storage audit."
+        );
+    }
+    if audited == 0 {
+        warn!(
+            content.key = content_key,
+            "No reachable nodes found with the content in their radius."
+        );
+    }
+}
+
+/// A candidate that responded to a ping with a radius that covers the content.
+struct CoveringNode {
+    node_id: String,
+    record: record::Model,
+    enr: Enr,
+    radius: DataRadius,
+}
+
+/// Pings candidates concurrently, and yields those that cover the content in the order
+/// the candidates were given.
+///
+/// Candidates are only pinged as they are needed, at most [`CONCURRENT_PINGS`] at a time.
+struct CoveringNodes {
+    candidates: std::iter::Enumerate<std::vec::IntoIter<node::ModelWithDistance>>,
+    pings: JoinSet<(usize, Option<CoveringNode>)>,
+    /// Outcomes of pings that finished before those of closer candidates.
+    finished: BTreeMap<usize, Option<CoveringNode>>,
+    next_index: usize,
+    content_key: String,
+    api: PortalApi,
+    conn: DatabaseConnection,
+}
+
+impl CoveringNodes {
+    fn new(
+        candidates: Vec<node::ModelWithDistance>,
+        content_key: String,
+        api: PortalApi,
+        conn: DatabaseConnection,
+    ) -> Self {
+        CoveringNodes {
+            candidates: candidates.into_iter().enumerate(),
+            pings: JoinSet::new(),
+            finished: BTreeMap::new(),
+            next_index: 0,
+            content_key,
+            api,
+            conn,
+        }
+    }
+
+    /// Returns the next closest candidate that covers the content, if any remain.
+    async fn next(&mut self) -> Option<CoveringNode> {
+        loop {
+            if let Some(outcome) = self.finished.remove(&self.next_index) {
+                self.next_index += 1;
+                match outcome {
+                    Some(covering) => return Some(covering),
+                    None => continue,
+                }
+            }
+            while self.pings.len() < CONCURRENT_PINGS {
+                let Some((index, candidate)) = self.candidates.next() else {
+                    break;
+                };
+                let ping = ping_candidate(
+                    candidate,
+                    self.content_key.clone(),
+                    self.api.clone(),
+                    self.conn.clone(),
+                );
+                self.pings.spawn(async move { (index, ping.await) });
+            }
+            match self.pings.join_next().await {
+                Some(Ok((index, outcome))) => {
+                    self.finished.insert(index, outcome);
+                }
+                Some(Err(e)) => {
+                    // The outcome of the candidate is unknown, so closer candidates cannot
+                    // be told apart from farther ones.
+                    error!(err=?e, "Candidate ping task failed.");
+                    return None;
+                }
+                None => return None,
+            }
+        }
+    }
+}
+
+/// Pings a candidate at its latest record, and returns it if its radius covers the content.
+async fn ping_candidate(
+    candidate: node::ModelWithDistance,
+    content_key: String,
+    api: PortalApi,
+    conn: DatabaseConnection,
+) -> Option<CoveringNode> {
+    let node_id = candidate.node_id_as_hex();
+    let record = match record::get_latest(candidate.id, &conn).await {
+        Ok(Some(record)) => record,
+        Ok(None) => return None,
+        Err(e) => {
+            error!(node.id=node_id, err=?e, "Could not look up record for node.");
+            return None;
+        }
+    };
+    let enr = match Enr::from_str(&record.raw) {
+        Ok(enr) => enr,
+        Err(e) => {
+            error!(node.id=node_id, err=?e, "Could not decode stored record.");
+            return None;
+        }
+    };
+    let radius = match api.ping(Subnetwork::History, &enr).await {
+        Ok(pong) => pong.data_radius,
+        Err(e) => {
+            debug!(node.id=node_id, err=?e, "Node did not respond to ping.");
+            return None;
+        }
+    };
+    // The radius is from a different version of ethereum-types, so is compared by bytes.
+    let mut distance = [0u8; 32];
+    candidate.distance.to_big_endian(&mut distance);
+    if DataRadius::from_big_endian(&distance) > radius {
+        debug!(
+            content.key = content_key,
+            node.id = node_id,
+            "Content is outside of node radius."
+        );
+        return None;
+    }
+    Some(CoveringNode {
+        node_id,
+        record,
+        enr,
+        radius,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use glados_test_support::{
+        block_body_key, insert_content, setup_database, MockPortalNode, MockResponse,
+        EMPTY_BLOCK_BODY, MOCK_ENR as COVERING_ENR, PEER_ENR as OUT_OF_RADIUS_ENR,
+    };
+    use sea_orm::EntityTrait;
+    use serde_json::json;
+
+    use entity::content_audit::{AuditResult, SelectionStrategy};
+
+    use super::*;
+    use crate::metrics::AUDITS;
+
+    /// Tests that only nodes whose radius covers the content are audited, and that each
+    /// audit records whether that node returned the content.
+    #[tokio::test]
+    async fn test_node_storage_audit() {
+        let conn = setup_database().await;
+        let content_key = block_body_key(1);
+        insert_content(&content_key, &conn).await;
+        let covering_record = record::get_or_create(&Enr::from_str(COVERING_ENR).unwrap(), &conn)
+            .await
+            .unwrap();
+        record::get_or_create(&Enr::from_str(OUT_OF_RADIUS_ENR).unwrap(), &conn)
+            .await
+            .unwrap();
+
+        let node = MockPortalNode::new();
+        node.respond_to(
+            "portal_historyPing",
+            json!([COVERING_ENR]),
+            MockResponse::Result(
+                json!({"enrSeq": 1, "dataRadius": format!("0x{}", "f".repeat(64))}),
+            ),
+        );
+        node.respond_to(
+            "portal_historyPing",
+            json!([OUT_OF_RADIUS_ENR]),
+            MockResponse::Result(json!({"enrSeq": 1, "dataRadius": "0x0"})),
+        );
+        let find_content_params = json!([COVERING_ENR, hex_encode(content_key.to_bytes())]);
+        node.respond_to(
+            "portal_historyFindContent",
+            find_content_params.clone(),
+            MockResponse::Result(json!({ "content": EMPTY_BLOCK_BODY })),
+        );
+        let client = PortalClient::from(node.serve_http().await.unwrap())
+            .await
+            .unwrap();
+        // No other test audits with this strategy, so its metrics only change here.
+        let strategy = SelectionStrategy::Failed;
+        let audit_count = |result: &str| {
+            AUDITS
+                .with_label_values(&[result, &strategy.as_text(), "trin"])
+                .get()
+        };
+        let successes = audit_count("success");
+        let task = AuditTask {
+            strategy: strategy.clone(),
+            content_key,
+            queue_id: None,
+        };
+
+        let active_threads = Arc::new(AtomicU8::new(1));
+        perform_node_storage_audit(
+            active_threads.clone(),
+            task.clone(),
+            client.clone(),
+            8,
+            conn.clone(),
+        )
+        .await;
+        assert_eq!(active_threads.load(Ordering::Relaxed), 0);
+        assert_eq!(node.request_count("portal_historyPing"), 2);
+        assert_eq!(node.request_count("portal_historyFindContent"), 1);
+        let audits = node_storage_audit::Entity::find().all(&conn).await.unwrap();
+        assert_eq!(audits.len(), 1);
+        assert_eq!(audits[0].record, covering_record.id);
+        assert_eq!(audits[0].result, AuditResult::Success);
+        assert_eq!(audits[0].data_radius, vec![255; 32]);
+        // The audit is counted under the client advertised in the node's record.
+        assert_eq!(audit_count("success") - successes, 1);
+
+        // The node no longer has the content, and instead returns other nodes.
+        node.respond_to(
+            "portal_historyFindContent",
+            find_content_params,
+            MockResponse::Result(json!({ "enrs": [] })),
+        );
+        active_threads.fetch_add(1, Ordering::Relaxed);
+        let failures = audit_count("fail");
+        perform_node_storage_audit(active_threads, task, client, 8, conn.clone()).await;
+        assert_eq!(audit_count("fail") - failures, 1);
+        let audits = node_storage_audit::Entity::find().all(&conn).await.unwrap();
+        assert_eq!(audits.len(), 2);
+        assert_eq!(audits[1].record, covering_record.id);
+        assert_eq!(audits[1].result, AuditResult::Failure);
+    }
+
+    /// Tests that candidates are audited closest first, even when a closer candidate is
+    /// slower to answer its ping.
+    #[tokio::test]
+    async fn test_node_storage_audit_closest_first() {
+        let conn = setup_database().await;
+        let content_key = block_body_key(2);
+        insert_content(&content_key, &conn).await;
+        for enr in [COVERING_ENR, OUT_OF_RADIUS_ENR] {
+            record::get_or_create(&Enr::from_str(enr).unwrap(), &conn)
+                .await
+                .unwrap();
+        }
+        let closest = node::closest_to_content(content_key.content_id(), 1, &conn)
+            .await
+            .unwrap()
+            .remove(0);
+        let closest_record = record::get_latest(closest.id, &conn)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let node = MockPortalNode::new();
+        let pong = json!({"enrSeq": 1, "dataRadius": format!("0x{}", "f".repeat(64))});
+        for enr in [COVERING_ENR, OUT_OF_RADIUS_ENR] {
+            let response = match enr == closest_record.raw {
+                true => MockResponse::Slow(
+                    Duration::from_millis(200),
+                    Box::new(MockResponse::Result(pong.clone())),
+                ),
+                false => MockResponse::Result(pong.clone()),
+            };
+            node.respond_to("portal_historyPing", json!([enr]), response);
+            node.respond_to(
+                "portal_historyFindContent",
+                json!([enr, hex_encode(content_key.to_bytes())]),
+                MockResponse::Result(json!({ "content": EMPTY_BLOCK_BODY })),
+            );
+        }
+        let client = PortalClient::from(node.serve_http().await.unwrap())
+            .await
+            .unwrap();
+        let task = AuditTask {
+            strategy: SelectionStrategy::SelectOldestUnaudited,
+            content_key,
+            queue_id: None,
+        };
+
+        audit_responsible_nodes(&task, &client, 1, &conn).await;
+        assert_eq!(node.request_count("portal_historyFindContent"), 1);
+        let audits = node_storage_audit::Entity::find().all(&conn).await.unwrap();
+        assert_eq!(audits.len(), 1);
+        assert_eq!(audits[0].record, closest_record.id);
+    }
+}
@@ -0,0 +1,244 @@
+use anyhow::{bail, Result};
+use ethportal_api::types::content_key::HistoryContentKey;
+use sea_orm::{DatabaseConnection, EntityTrait};
+use tokio::{
+    sync::mpsc,
+    time::{sleep, Duration},
+};
+use tracing::{debug, error};
+
+use entity::{audit_queue, content, content_audit::SelectionStrategy};
+
+use crate::{metrics::QUEUE_WAITING, AuditTask};
+
+/// Most unclaimed tasks of one strategy kept in the audit queue.
+///
+/// A strategy whose share of the queue is full stops selecting content until auditors
+/// claim some of it.
+pub const MAX_WAITING_PER_STRATEGY: u64 = 100;
+
+/// Priority of tasks selected by a strategy.
+const STRATEGY_PRIORITY: i32 = 0;
+
+/// Stores the tasks a strategy selects in the audit queue table, where any auditor can claim them.
+///
+/// Tasks are taken from the channel only while the strategy has room in the queue, so a full
+/// queue fills the channel and the strategy waits. Content that is already queued is not
+/// added again.
+pub async fn enqueue_selected_tasks(
+    strategy: SelectionStrategy,
+    mut rx: mpsc::Receiver<AuditTask>,
+    conn: DatabaseConnection,
+) {
+    while let Some(task) = rx.recv().await {
+        loop {
+            match audit_queue::count_waiting(strategy.clone(), &conn).await {
+                Ok(waiting) => {
+                    QUEUE_WAITING
+                        .with_label_values(&[&strategy.as_text()])
+                        .set(waiting as i64);
+                    if waiting < MAX_WAITING_PER_STRATEGY {
+                        break;
+                    }
+                }
+                Err(e) => error!(strategy=?strategy, err=?e, "Could not count queued audits."),
+            }
+            sleep(Duration::from_secs(1)).await;
+        }
+        if let Err(e) = enqueue_task(&task, &conn).await {
+            error!(content.key=?task.content_key, err=?e, "Could not queue audit.");
+        }
+    }
+    debug!(strategy=?strategy, "Strategy channel closed.");
+}
+
+/// Adds the task to the audit queue, unless its content is already waiting for an audit.
+async fn enqueue_task(task: &AuditTask, conn: &DatabaseConnection) -> Result<()> {
+    let Some(content_key_model) = content::get(&task.content_key, conn).await? else {
+        bail!("Content key not found in db.")
+    };
+    audit_queue::enqueue(
+        content_key_model.id,
+        task.strategy.clone(),
+        STRATEGY_PRIORITY,
+        conn,
+    )
+    .await?;
+    Ok(())
+}
+
+/// Claims up to `limit` queued tasks of the strategies and sends them in the channel.
+///
+/// Returns the number of tasks sent.
+pub async fn claim_tasks(
+    strategies: &[SelectionStrategy],
+    limit: usize,
+    tx: &mpsc::Sender<AuditTask>,
+    conn: &DatabaseConnection,
+) -> usize {
+    if limit == 0 {
+        return 0;
+    }
+    let entries = match audit_queue::claim(strategies, limit as u64, conn).await {
+        Ok(entries) => entries,
+        Err(e) => {
+            error!(strategies=?strategies, err=?e, "Could not claim queued audits.");
+            return 0;
+        }
+    };
+    let mut sent = 0;
+    for entry in entries {
+        let content_key_model = match content::Entity::find_by_id(entry.content_key)
+            .one(conn)
+            .await
+        {
+            Ok(Some(content_key_model)) => content_key_model,
+            Ok(None) => {
+                error!(queue.id = entry.id, "Queued content key not found in db.");
+                continue;
+            }
+            Err(e) => {
+                error!(queue.id = entry.id, err=?e, "Could not look up queued content key.");
+                continue;
+            }
+        };
+        let task = AuditTask {
+            strategy: entry.strategy,
+            content_key: HistoryContentKey::from(content_key_model.content_key),
+            queue_id: Some(entry.id),
+        };
+        if let Err(e) = tx.send(task).await {
+            error!(queue.id = entry.id, err=?e, "Could not send claimed audit.");
+            release_task(e.0.queue_id, conn).await;
+            continue;
+        }
+        sent += 1;
+    }
+    sent
+}
+
+/// Returns a claimed task that will not be audited here to the queue, given the id of
+/// its queue entry if it came from the queue.
+pub async fn release_task(queue_id: Option<i32>, conn: &DatabaseConnection) {
+    let Some(queue_id) = queue_id else {
+        return;
+    };
+    if let Err(e) = audit_queue::release(queue_id, conn).await {
+        error!(queue.id = queue_id, err=?e, "Could not return audit to the queue.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethportal_api::types::content_key::OverlayContentKey;
+    use glados_test_support::{block_body_key, insert_content, setup_database};
+    use tokio::sync::mpsc::channel;
+
+    use super::*;
+
+    async fn insert_keys(count: u8, conn: &DatabaseConnection) -> Vec<HistoryContentKey> {
+        let content_keys: Vec<HistoryContentKey> = (0..count).map(block_body_key).collect();
+        for content_key in &content_keys {
+            insert_content(content_key, conn).await;
+        }
+        content_keys
+    }
+
+    /// Tests that selected tasks are queued once each, up to the limit for the strategy.
+    #[tokio::test]
+    async fn test_enqueue_selected_tasks() {
+        let conn = setup_database().await;
+        let content_keys = insert_keys(MAX_WAITING_PER_STRATEGY as u8 + 1, &conn).await;
+        let (tx, rx) = channel::<AuditTask>(200);
+        let enqueue = tokio::spawn(enqueue_selected_tasks(
+            SelectionStrategy::Random,
+            rx,
+            conn.clone(),
+        ));
+        // The first key is selected twice.
+        for content_key in content_keys.iter().take(1).chain(&content_keys) {
+            tx.send(AuditTask {
+                strategy: SelectionStrategy::Random,
+                content_key: content_key.clone(),
+                queue_id: None,
+            })
+            .await
+            .unwrap();
+        }
+        let mut waiting = 0;
+        for _ in 0..50 {
+            waiting = audit_queue::count_waiting(SelectionStrategy::Random, &conn)
+                .await
+                .unwrap();
+            if waiting == MAX_WAITING_PER_STRATEGY {
+                break;
+            }
+            sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(waiting, MAX_WAITING_PER_STRATEGY);
+
+        // The duplicate was never queued, so the queue holds the first keys in order.
+        let (claim_tx, mut claim_rx) = channel::<AuditTask>(200);
+        let claimed = claim_tasks(&[SelectionStrategy::Random], 200, &claim_tx, &conn).await;
+        assert_eq!(claimed, MAX_WAITING_PER_STRATEGY as usize);
+        let mut claimed_keys = vec![];
+        while let Ok(task) = claim_rx.try_recv() {
+            assert!(task.queue_id.is_some());
+            claimed_keys.push(task.content_key);
+        }
+        assert_eq!(claimed_keys, content_keys[..claimed]);
+
+        // Claiming made room for the last key.
+        let mut claimed = 0;
+        for _ in 0..50 {
+            claimed = claim_tasks(&[SelectionStrategy::Random], 200, &claim_tx, &conn).await;
+            if claimed > 0 {
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        enqueue.abort();
+        assert_eq!(claimed, 1);
+        assert_eq!(
+            claim_rx.try_recv().unwrap().content_key,
+            content_keys[content_keys.len() - 1]
+        );
+    }
+
+    /// Tests that tasks are claimed by only one of several auditors.
+    #[tokio::test]
+    async fn test_claim_tasks_once() {
+        let conn = setup_database().await;
+        let content_keys = insert_keys(10, &conn).await;
+        for content_key in &content_keys {
+            let content_key_model = content::get(content_key, &conn).await.unwrap().unwrap();
+            audit_queue::enqueue(
+                content_key_model.id,
+                SelectionStrategy::Latest,
+                STRATEGY_PRIORITY,
+                &conn,
+            )
+            .await
+            .unwrap();
+        }
+        let (tx, mut rx) = channel::<AuditTask>(20);
+        let strategies = [SelectionStrategy::Latest];
+        let claimed = tokio::join!(
+            claim_tasks(&strategies, 5, &tx, &conn),
+            claim_tasks(&strategies, 5, &tx, &conn),
+            claim_tasks(&strategies, 5, &tx, &conn),
+            claim_tasks(&strategies, 5, &tx, &conn),
+        );
+        let mut total = claimed.0 + claimed.1 + claimed.2 + claimed.3;
+        // Auditors that lose a race claim fewer tasks, which are left for later claims.
+        total += claim_tasks(&strategies, 20, &tx, &conn).await;
+        assert_eq!(total, content_keys.len());
+        let mut claimed_keys = vec![];
+        while let Ok(task) = rx.try_recv() {
+            claimed_keys.push(task.content_key.to_bytes());
+        }
+        claimed_keys.sort();
+        claimed_keys.dedup();
+        assert_eq!(claimed_keys.len(), content_keys.len());
+    }
+}
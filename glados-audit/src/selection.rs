@@ -1,11 +1,12 @@
-use std::collections::HashSet;
+use std::{collections::HashSet, future::Future, ops::Range};
 
 use chrono::{DateTime, FixedOffset, TimeZone, Utc};
 use ethportal_api::types::content_key::HistoryContentKey;
-use rand::{thread_rng, Rng};
+use rand::{seq::SliceRandom, thread_rng, Rng};
 use sea_orm::{
-    ColumnTrait, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder,
-    QuerySelect,
+    sea_query::{Expr, SimpleExpr},
+    ColumnTrait, ConnectionTrait, DatabaseBackend, DatabaseConnection, EntityTrait, Iterable,
+    Order, QueryFilter, QueryOrder, QuerySelect,
 };
 use tokio::{
     sync::mpsc,
@@ -16,6 +17,7 @@ use tracing::{debug, error, warn};
 use entity::{
     content::{self, Model},
     content_audit::{self, SelectionStrategy},
+    execution_metadata::{self, BlockAgeBand},
 };
 
 use crate::AuditTask;
@@ -32,6 +34,21 @@ pub async fn start_audit_selection_task(
         SelectionStrategy::SelectOldestUnaudited => {
             select_oldest_unaudited_content_for_audit(tx, conn).await
         }
+        SelectionStrategy::KeyspaceStratified => {
+            select_keyspace_stratified_content_for_audit(tx, conn).await
+        }
+        SelectionStrategy::BlockAgeStratified => {
+            select_block_age_stratified_content_for_audit(tx, conn).await
+        }
+        SelectionStrategy::BlockHeaders
+        | SelectionStrategy::BlockBodies
+        | SelectionStrategy::Receipts
+        | SelectionStrategy::EpochAccumulators => {
+            select_content_of_kind_for_audit(strategy, tx, conn).await
+        }
+        SelectionStrategy::Requested => {
+            warn!("Requested audits are not selected by a strategy")
+        }
     }
 }
 
@@ -118,6 +135,7 @@ async fn add_to_queue(
         let task = AuditTask {
             strategy: strategy.clone(),
             content_key,
+            queue_id: None,
         };
         if let Err(e) = tx.send(task).await {
             debug!(audit.strategy=?strategy, err=?e, "Could not send key for audit, channel might be full or closed.")
@@ -128,9 +146,10 @@ async fn add_to_queue(
 /// Finds and sends audit tasks for [Strategy::Random].
 ///
 /// Strategy achieved by:
-/// 1. Checking number of keys in DB.
-/// 2. Generating random ids.
-/// 3. Looking up each one separately, then sending them all in the channel.
+/// 1. Ordering the content keys randomly in the database.
+/// 2. Taking as many as are needed, then sending them all in the channel.
+///
+/// Every stored content key is equally likely to be picked, however the ids are spread.
 ///
 /// At regular intervals the channel capacity is assessed and new tasks are added to reach capacity.
 async fn select_random_content_for_audit(
@@ -142,42 +161,18 @@ async fn select_random_content_for_audit(
     let mut interval = interval(Duration::from_secs(10));
     loop {
         interval.tick().await;
-
-        let num_keys = match content::Entity::find().count(&conn).await {
-            // Skip if no keys yet.
-            Ok(0) => continue,
-            Ok(count) => count as u32,
-            Err(err) => {
-                error!(audit.strategy="random", err=?err, "Could not make audit query");
-                continue;
-            }
-        };
         let keys_required = tx.capacity();
         if keys_required == 0 {
             continue;
         };
-        let mut random_ids: HashSet<u32> = HashSet::new();
+        let content_key_db_entries = match select_random_content(keys_required as u64, &conn).await
         {
-            // Thread safe block for the rng, which is not `Send`.
-            let mut rng = thread_rng();
-            for _ in 0..keys_required {
-                random_ids.insert(rng.gen_range(0..num_keys));
+            Ok(content_key_db_entries) => content_key_db_entries,
+            Err(err) => {
+                error!(audit.strategy="random", err=?err, "Could not make audit query");
+                continue;
             }
-        }
-        let mut content_key_db_entries: Vec<Model> = vec![];
-        for random_id in random_ids {
-            match content::Entity::find()
-                .filter(content::Column::Id.eq(random_id))
-                .all(&conn)
-                .await
-            {
-                Ok(found) => content_key_db_entries.extend(found),
-                Err(err) => {
-                    error!(audit.strategy="random", err=?err, "Could not make audit query");
-                    continue;
-                }
-            };
-        }
+        };
         let item_count = content_key_db_entries.len();
         debug!(
             strategy = "random",
@@ -192,6 +187,26 @@ async fn select_random_content_for_audit(
     }
 }
 
+/// Returns up to `count` distinct content keys, chosen uniformly at random by the database.
+async fn select_random_content(
+    count: u64,
+    conn: &DatabaseConnection,
+) -> Result<Vec<Model>, sea_orm::DbErr> {
+    content::Entity::find()
+        .order_by(random_order(conn), Order::Asc)
+        .limit(count)
+        .all(conn)
+        .await
+}
+
+/// Returns an expression that orders rows randomly in the connected database.
+fn random_order(conn: &DatabaseConnection) -> SimpleExpr {
+    match conn.get_database_backend() {
+        DatabaseBackend::MySql => Expr::cust("RAND()"),
+        DatabaseBackend::Postgres | DatabaseBackend::Sqlite => Expr::cust("RANDOM()"),
+    }
+}
+
 /// Finds and sends audit tasks for [SelectionStrategy::SelectOldestUnaudited].
 ///
 /// Strategy achieved by:
@@ -266,9 +281,366 @@ async fn select_oldest_unaudited_content_for_audit(
     }
 }
 
+/// Finds and sends audit tasks for strategies that take content from several strata in turn,
+/// such as keyspace buckets or block age bands.
+///
+/// At regular intervals the channel capacity is assessed. `plan_round` is given the number of
+/// keys required and returns the strata to take them from, in order, or None to skip the round.
+/// Each pass takes at most one key from every stratum with `pick`, until enough keys are found
+/// or a pass finds nothing new.
+async fn select_stratified_content_for_audit<Stratum, Plan, PlanFut, Pick, PickFut>(
+    strategy: SelectionStrategy,
+    tx: mpsc::Sender<AuditTask>,
+    conn: DatabaseConnection,
+    mut plan_round: Plan,
+    pick: Pick,
+) -> !
+where
+    Stratum: Clone,
+    Plan: FnMut(usize, DatabaseConnection) -> PlanFut,
+    PlanFut: Future<Output = Option<Vec<Stratum>>>,
+    Pick: Fn(Stratum, DatabaseConnection) -> PickFut,
+    PickFut: Future<Output = Result<Option<Model>, sea_orm::DbErr>>,
+{
+    let mut interval = interval(Duration::from_secs(10));
+
+    loop {
+        interval.tick().await;
+        if tx.is_closed() {
+            error!("Channel is closed.");
+            panic!();
+        }
+        let keys_required = tx.capacity();
+        if keys_required == 0 {
+            continue;
+        };
+        let Some(strata) = plan_round(keys_required, conn.clone()).await else {
+            continue;
+        };
+
+        let mut content_key_db_entries: Vec<Model> = vec![];
+        let mut selected_ids: HashSet<i32> = HashSet::new();
+        // Each pass takes at most one key from every stratum. Stop once a pass finds nothing new.
+        for _ in 0..keys_required {
+            let mut found_in_pass = false;
+            for stratum in &strata {
+                if content_key_db_entries.len() == keys_required {
+                    break;
+                }
+                match pick(stratum.clone(), conn.clone()).await {
+                    Ok(Some(found)) => {
+                        if selected_ids.insert(found.id) {
+                            found_in_pass = true;
+                            content_key_db_entries.push(found);
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(err) => {
+                        error!(audit.strategy=?strategy, err=?err, "Could not make audit query");
+                    }
+                };
+            }
+            if !found_in_pass || content_key_db_entries.len() == keys_required {
+                break;
+            }
+        }
+        let item_count = content_key_db_entries.len();
+        debug!(
+            strategy = strategy.as_text(),
+            item_count, "Adding content keys to the audit queue."
+        );
+        add_to_queue(tx.clone(), strategy.clone(), content_key_db_entries).await;
+    }
+}
+
+/// Number of leading content id bits that define a keyspace bucket, giving 16 buckets.
+const KEYSPACE_PREFIX_BITS: u8 = 4;
+
+/// Finds and sends audit tasks for [SelectionStrategy::KeyspaceStratified].
+///
+/// Strategy achieved by:
+/// 1. Counting the audits from the last day in each keyspace bucket.
+/// 2. Ordering the buckets with the least audited first.
+/// 3. Taking one random content key from each bucket in turn, until enough keys are found.
+///
+/// At regular intervals the channel capacity is assessed and new tasks are added to reach capacity.
+async fn select_keyspace_stratified_content_for_audit(
+    tx: mpsc::Sender<AuditTask>,
+    conn: DatabaseConnection,
+) -> ! {
+    debug!("initializing audit process for 'keyspace stratified' strategy");
+    select_stratified_content_for_audit(
+        SelectionStrategy::KeyspaceStratified,
+        tx,
+        conn,
+        |_, conn| async move {
+            match content_audit::get_keyspace_counts(
+                KEYSPACE_PREFIX_BITS,
+                Utc::now() - chrono::Duration::days(1),
+                &conn,
+            )
+            .await
+            {
+                Ok(coverage) => Some(order_buckets_by_coverage(&coverage)),
+                Err(err) => {
+                    error!(audit.strategy="keyspace stratified", err=?err, "Could not count audits by keyspace bucket");
+                    None
+                }
+            }
+        },
+        |bucket, conn| async move { select_random_content_in_bucket(bucket, &conn).await },
+    )
+    .await
+}
+
+/// Orders keyspace buckets with the fewest audits first. Buckets with equal coverage are
+/// shuffled so that no region is always favored.
+fn order_buckets_by_coverage(coverage: &[content_audit::AuditCounts]) -> Vec<usize> {
+    let mut bucket_order: Vec<usize> = (0..coverage.len()).collect();
+    bucket_order.shuffle(&mut thread_rng());
+    bucket_order.sort_by_key(|bucket| coverage[*bucket].total);
+    bucket_order
+}
+
+/// Returns the first and one past the last content id in a keyspace bucket. The last bucket
+/// runs to the end of the keyspace, which is represented by None.
+fn keyspace_bucket_bounds(bucket: usize) -> ([u8; 32], Option<[u8; 32]>) {
+    let bucket_size = 1u32 << (16 - KEYSPACE_PREFIX_BITS);
+    let to_id = |high_bits: u32| {
+        let mut id = [0u8; 32];
+        id[..2].copy_from_slice(&(high_bits as u16).to_be_bytes());
+        id
+    };
+    let start = bucket as u32 * bucket_size;
+    let end = start + bucket_size;
+    (to_id(start), (end <= u16::MAX as u32).then(|| to_id(end)))
+}
+
+/// Picks a random content key whose content id is in the keyspace bucket.
+///
+/// A random id in the bucket is drawn, and the first content at or after it is used. If there
+/// is none, the search wraps around to the start of the bucket.
+async fn select_random_content_in_bucket(
+    bucket: usize,
+    conn: &DatabaseConnection,
+) -> Result<Option<Model>, sea_orm::DbErr> {
+    let (start, end) = keyspace_bucket_bounds(bucket);
+    let mut point: [u8; 32] = thread_rng().gen();
+    let prefix_mask = !(u16::MAX >> KEYSPACE_PREFIX_BITS);
+    let point_high = (u16::from_be_bytes([start[0], start[1]]) & prefix_mask)
+        | (u16::from_be_bytes([point[0], point[1]]) & !prefix_mask);
+    point[..2].copy_from_slice(&point_high.to_be_bytes());
+
+    select_content_at_or_after(
+        content::Column::ContentId,
+        point.to_vec(),
+        start.to_vec(),
+        end.map(|end| end.to_vec()),
+        conn,
+    )
+    .await
+}
+
+/// Returns the content with the lowest value of `column` that is at or after `point` and before
+/// `end`. If there is none, the search wraps around to start from `start` instead.
+///
+/// With a random `point`, this picks content at random from the range.
+async fn select_content_at_or_after(
+    column: content::Column,
+    point: Vec<u8>,
+    start: Vec<u8>,
+    end: Option<Vec<u8>>,
+    conn: &DatabaseConnection,
+) -> Result<Option<Model>, sea_orm::DbErr> {
+    for from in [point, start] {
+        let mut query = content::Entity::find().filter(column.gte(from)).filter(
+            content::Column::FirstAvailableAt.lt(Utc::now() - chrono::Duration::seconds(10)),
+        );
+        if let Some(end) = &end {
+            query = query.filter(column.lt(end.clone()));
+        }
+        if let Some(found) = query.order_by_asc(column).one(conn).await? {
+            return Ok(Some(found));
+        }
+    }
+    Ok(None)
+}
+
+/// Returns the content key selector of the kind of content that the strategy audits, for
+/// strategies that audit only one kind.
+fn content_key_selector(strategy: &SelectionStrategy) -> Option<u8> {
+    match strategy {
+        SelectionStrategy::BlockHeaders => Some(0x00),
+        SelectionStrategy::BlockBodies => Some(0x01),
+        SelectionStrategy::Receipts => Some(0x02),
+        SelectionStrategy::EpochAccumulators => Some(0x03),
+        _ => None,
+    }
+}
+
+/// Finds and sends audit tasks for the strategies that audit one kind of content:
+/// [SelectionStrategy::BlockHeaders], [SelectionStrategy::BlockBodies],
+/// [SelectionStrategy::Receipts] and [SelectionStrategy::EpochAccumulators].
+///
+/// Strategy achieved by:
+/// 1. Drawing random content keys that start with the selector byte of the kind.
+/// 2. Looking up the first content key at or after each one.
+///
+/// History content keys are the selector followed by a hash, so this picks evenly from the
+/// content of that kind.
+///
+/// At regular intervals the channel capacity is assessed and new tasks are added to reach capacity.
+async fn select_content_of_kind_for_audit(
+    strategy: SelectionStrategy,
+    tx: mpsc::Sender<AuditTask>,
+    conn: DatabaseConnection,
+) {
+    let Some(selector) = content_key_selector(&strategy) else {
+        error!(audit.strategy=?strategy, "Strategy does not select a kind of content");
+        return;
+    };
+    debug!(audit.strategy=?strategy, "initializing audit process for content kind strategy");
+    let kind_start = vec![selector];
+    let kind_end = Some(vec![selector + 1]);
+    select_stratified_content_for_audit(
+        strategy.clone(),
+        tx,
+        conn,
+        |keys_required, conn| {
+            let strategy = strategy.clone();
+            let (kind_start, kind_end) = (kind_start.clone(), kind_end.clone());
+            async move {
+                // Skip if there is no content of this kind yet.
+                match select_content_at_or_after(
+                    content::Column::ContentKey,
+                    kind_start.clone(),
+                    kind_start,
+                    kind_end,
+                    &conn,
+                )
+                .await
+                {
+                    Ok(Some(_)) => {}
+                    Ok(None) => return None,
+                    Err(err) => {
+                        error!(audit.strategy=?strategy, err=?err, "Could not make audit query");
+                        return None;
+                    }
+                }
+                // Each random key is its own stratum. Extra draws make up for draws that land
+                // on content that was already picked.
+                let mut rng = thread_rng();
+                let points = (0..keys_required * 4)
+                    .map(|_| {
+                        let mut point = vec![selector];
+                        point.extend(rng.gen::<[u8; 32]>());
+                        point
+                    })
+                    .collect();
+                Some(points)
+            }
+        },
+        |point, conn| {
+            let (kind_start, kind_end) = (kind_start.clone(), kind_end.clone());
+            async move {
+                select_content_at_or_after(
+                    content::Column::ContentKey,
+                    point,
+                    kind_start,
+                    kind_end,
+                    &conn,
+                )
+                .await
+            }
+        },
+    )
+    .await
+}
+
+/// Finds and sends audit tasks for [SelectionStrategy::BlockAgeStratified].
+///
+/// Strategy achieved by:
+/// 1. Finding the latest known block, which the age of every other block is measured from.
+/// 2. Shuffling the block age bands.
+/// 3. Taking one random content key from each band in turn, until enough keys are found.
+///
+/// Success rates for each band are logged, to compare availability of new and old content.
+///
+/// At regular intervals the channel capacity is assessed and new tasks are added to reach capacity.
+async fn select_block_age_stratified_content_for_audit(
+    tx: mpsc::Sender<AuditTask>,
+    conn: DatabaseConnection,
+) -> ! {
+    debug!("initializing audit process for 'block age stratified' strategy");
+    select_stratified_content_for_audit(
+        SelectionStrategy::BlockAgeStratified,
+        tx,
+        conn,
+        |_, conn| async move {
+            let latest_block = match execution_metadata::get_latest_block_number(&conn).await {
+                Ok(Some(latest_block)) => latest_block,
+                // Skip if no block numbers yet.
+                Ok(None) => return None,
+                Err(err) => {
+                    error!(audit.strategy="block age stratified", err=?err, "Could not look up latest block");
+                    return None;
+                }
+            };
+            log_block_age_success_rates(&conn).await;
+            let mut band_order: Vec<Range<i32>> = BlockAgeBand::iter()
+                .map(|band| band.block_range(latest_block))
+                .collect();
+            band_order.shuffle(&mut thread_rng());
+            Some(band_order)
+        },
+        |blocks, conn| async move { select_random_content_in_block_range(blocks, &conn).await },
+    )
+    .await
+}
+
+/// Logs the audit success rate of each block age band over the last day.
+async fn log_block_age_success_rates(conn: &DatabaseConnection) {
+    match content_audit::get_block_age_counts(Utc::now() - chrono::Duration::days(1), conn).await {
+        Ok(band_counts) => {
+            for (band, counts) in band_counts {
+                debug!(
+                    strategy = "block age stratified",
+                    band = band.as_text(),
+                    audits = counts.total,
+                    success_rate = ?counts.success_rate(),
+                    "Audit success rate over the last day."
+                );
+            }
+        }
+        Err(err) => {
+            error!(audit.strategy="block age stratified", err=?err, "Could not count audits by block age");
+        }
+    }
+}
+
+/// Picks a random content key whose block number is in the range.
+///
+/// The database orders the content in the range randomly, so every content key in the range is
+/// equally likely to be picked, however the blocks are spread.
+async fn select_random_content_in_block_range(
+    blocks: Range<i32>,
+    conn: &DatabaseConnection,
+) -> Result<Option<Model>, sea_orm::DbErr> {
+    if blocks.is_empty() {
+        return Ok(None);
+    }
+    content::Entity::find()
+        .inner_join(execution_metadata::Entity)
+        .filter(execution_metadata::Column::BlockNumber.gte(blocks.start))
+        .filter(execution_metadata::Column::BlockNumber.lt(blocks.end))
+        .order_by(random_order(conn), Order::Asc)
+        .one(conn)
+        .await
+}
+
 #[cfg(test)]
 mod tests {
-    use std::collections::HashSet;
+    use std::collections::{HashMap, HashSet};
 
     use chrono::Utc;
     use entity::{
@@ -277,7 +649,9 @@ mod tests {
         content_audit::{self, AuditResult},
         node,
     };
-    use ethportal_api::types::content_key::{BlockHeaderKey, HistoryContentKey, OverlayContentKey};
+    use ethportal_api::types::content_key::{
+        BlockBodyKey, BlockHeaderKey, BlockReceiptsKey, HistoryContentKey, OverlayContentKey,
+    };
     use migration::{DbErr, Migrator, MigratorTrait};
     use sea_orm::{
         ActiveModelTrait, ActiveValue::NotSet, ColumnTrait, Database, DbConn, EntityTrait,
@@ -449,7 +823,7 @@ mod tests {
         const CHANNEL_SIZE: usize = 10;
         let (tx, mut rx) = channel::<AuditTask>(CHANNEL_SIZE);
         // Start strategy
-        tokio::spawn(select_latest_content_for_audit(tx.clone(), conn.clone()));
+        tokio::spawn(select_random_content_for_audit(tx.clone(), conn.clone()));
         let mut checked_ids: HashSet<i32> = HashSet::new();
         // There are 45 possible correct values: [1, 2, ... 45]
         let expected_key_ids: Vec<i32> = (1..=45).collect();
@@ -472,4 +846,244 @@ mod tests {
         // Make sure no key was audited twice by pushing to a hashmap and checking it's length.
         assert_eq!(checked_ids.len(), CHANNEL_SIZE);
     }
+
+    /// Tests that the `SelectionStrategy::KeyspaceStratified` spreads the selected values
+    /// across keyspace buckets, starting with the least audited buckets.
+    #[tokio::test]
+    async fn test_keyspace_stratified_strategy() {
+        // Orchestration
+        let conn = get_populated_test_audit_db().await.unwrap();
+        const CHANNEL_SIZE: usize = 10;
+        let (tx, mut rx) = channel::<AuditTask>(CHANNEL_SIZE);
+        // Start strategy
+        tokio::spawn(select_keyspace_stratified_content_for_audit(
+            tx.clone(),
+            conn.clone(),
+        ));
+        let coverage = content_audit::get_keyspace_counts(
+            KEYSPACE_PREFIX_BITS,
+            Utc::now() - chrono::Duration::days(1),
+            &conn,
+        )
+        .await
+        .unwrap();
+        let mut occupied_buckets: HashSet<usize> = HashSet::new();
+        for content_model in content::Entity::find().all(&conn).await.unwrap() {
+            occupied_buckets.insert(
+                content::keyspace_bucket(&content_model.content_id, KEYSPACE_PREFIX_BITS).unwrap(),
+            );
+        }
+        assert!(occupied_buckets.len() >= CHANNEL_SIZE);
+
+        let mut checked_ids: HashSet<i32> = HashSet::new();
+        let mut selected_buckets: HashSet<usize> = HashSet::new();
+        // Await strategy results
+        while let Some(task) = rx.recv().await {
+            let key_model = content::Entity::find()
+                .filter(content::Column::ContentKey.eq(task.content_key.to_bytes()))
+                .one(&conn)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(task.strategy, SelectionStrategy::KeyspaceStratified);
+            checked_ids.insert(key_model.id);
+            selected_buckets.insert(
+                content::keyspace_bucket(&key_model.content_id, KEYSPACE_PREFIX_BITS).unwrap(),
+            );
+            if checked_ids.len() == CHANNEL_SIZE {
+                break;
+            }
+        }
+        assert_eq!(checked_ids.len(), CHANNEL_SIZE);
+        // There are enough occupied buckets for every key to come from a different one.
+        assert_eq!(selected_buckets.len(), CHANNEL_SIZE);
+        // No bucket that was skipped had fewer audits than a bucket that was selected.
+        let most_audited_selected = selected_buckets
+            .iter()
+            .map(|bucket| coverage[*bucket].total)
+            .max()
+            .unwrap();
+        for bucket in occupied_buckets.difference(&selected_buckets) {
+            assert!(coverage[*bucket].total >= most_audited_selected);
+        }
+    }
+
+    #[test]
+    fn test_keyspace_bucket_bounds() {
+        let (start, end) = keyspace_bucket_bounds(0);
+        assert_eq!(start, [0u8; 32]);
+        let mut expected_end = [0u8; 32];
+        expected_end[0] = 0x10;
+        assert_eq!(end, Some(expected_end));
+
+        let (start, end) = keyspace_bucket_bounds(15);
+        assert_eq!(start[0], 0xf0);
+        assert_eq!(end, None);
+    }
+
+    #[test]
+    fn test_order_buckets_by_coverage() {
+        let coverage: Vec<content_audit::AuditCounts> = [3, 0, 5, 1]
+            .into_iter()
+            .map(|total| content_audit::AuditCounts { total, passes: 0 })
+            .collect();
+        assert_eq!(order_buckets_by_coverage(&coverage), vec![1, 3, 0, 2]);
+    }
+
+    /// Tests that the `SelectionStrategy::BlockAgeStratified` takes one key from each block age
+    /// band before taking a second key from any band.
+    #[tokio::test]
+    async fn test_block_age_stratified_strategy() {
+        // Orchestration
+        let conn = setup_database().await.unwrap();
+        // One block in each of the six bands, plus two more before the merge.
+        let block_numbers = [
+            17_000_000, 16_999_000, 16_950_000, 16_850_000, 16_000_000, 1_000_000, 2_000_000,
+            3_000_000,
+        ];
+        for (index, block_number) in block_numbers.into_iter().enumerate() {
+            let content_key = HistoryContentKey::BlockHeaderWithProof(BlockHeaderKey {
+                block_hash: [index as u8 + 1; 32],
+            });
+            let content_model = content::ActiveModel {
+                id: NotSet,
+                content_id: Set(content_key.content_id().to_vec()),
+                content_key: Set(content_key.to_bytes()),
+                first_available_at: Set((Utc::now() - chrono::Duration::minutes(10)).into()),
+                protocol_id: Set(SubProtocol::History),
+            }
+            .insert(&conn)
+            .await
+            .unwrap();
+            execution_metadata::get_or_create(content_model.id, block_number, &conn)
+                .await
+                .unwrap();
+        }
+        const CHANNEL_SIZE: usize = 6;
+        let (tx, mut rx) = channel::<AuditTask>(CHANNEL_SIZE);
+        // Start strategy
+        tokio::spawn(select_block_age_stratified_content_for_audit(
+            tx.clone(),
+            conn.clone(),
+        ));
+        let mut checked_ids: HashSet<i32> = HashSet::new();
+        let mut selected_bands: HashSet<BlockAgeBand> = HashSet::new();
+        // Await strategy results
+        while let Some(task) = rx.recv().await {
+            let key_model = content::Entity::find()
+                .filter(content::Column::ContentKey.eq(task.content_key.to_bytes()))
+                .one(&conn)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(task.strategy, SelectionStrategy::BlockAgeStratified);
+            let block_number = execution_metadata::get(key_model.id, &conn)
+                .await
+                .unwrap()
+                .unwrap()
+                .block_number;
+            let band = BlockAgeBand::iter()
+                .find(|band| band.block_range(17_000_000).contains(&block_number))
+                .unwrap();
+            checked_ids.insert(key_model.id);
+            selected_bands.insert(band);
+            if checked_ids.len() == CHANNEL_SIZE {
+                break;
+            }
+        }
+        assert_eq!(checked_ids.len(), CHANNEL_SIZE);
+        assert_eq!(selected_bands.len(), CHANNEL_SIZE);
+    }
+
+    /// Tests that the `SelectionStrategy::Receipts` selects only receipts.
+    #[tokio::test]
+    async fn test_content_kind_strategy() {
+        // Orchestration
+        let conn = setup_database().await.unwrap();
+        // Random block hashes, like real ones, spread the keys of each kind evenly.
+        for _ in 0..10 {
+            let block_hash: [u8; 32] = thread_rng().gen();
+            let content_keys = [
+                HistoryContentKey::BlockHeaderWithProof(BlockHeaderKey { block_hash }),
+                HistoryContentKey::BlockBody(BlockBodyKey { block_hash }),
+                HistoryContentKey::BlockReceipts(BlockReceiptsKey { block_hash }),
+            ];
+            for content_key in content_keys {
+                content::ActiveModel {
+                    id: NotSet,
+                    content_id: Set(content_key.content_id().to_vec()),
+                    content_key: Set(content_key.to_bytes()),
+                    first_available_at: Set((Utc::now() - chrono::Duration::minutes(10)).into()),
+                    protocol_id: Set(SubProtocol::History),
+                }
+                .insert(&conn)
+                .await
+                .unwrap();
+            }
+        }
+        const CHANNEL_SIZE: usize = 5;
+        let (tx, mut rx) = channel::<AuditTask>(CHANNEL_SIZE);
+        // Start strategy
+        tokio::spawn(select_content_of_kind_for_audit(
+            SelectionStrategy::Receipts,
+            tx.clone(),
+            conn.clone(),
+        ));
+        let mut checked_keys: HashSet<Vec<u8>> = HashSet::new();
+        // Await strategy results
+        while let Some(task) = rx.recv().await {
+            assert_eq!(task.strategy, SelectionStrategy::Receipts);
+            assert!(matches!(
+                task.content_key,
+                HistoryContentKey::BlockReceipts(_)
+            ));
+            checked_keys.insert(task.content_key.to_bytes());
+            if checked_keys.len() == CHANNEL_SIZE {
+                break;
+            }
+        }
+        assert_eq!(checked_keys.len(), CHANNEL_SIZE);
+    }
+
+    /// Tests that random selection picks every stored content key equally often, including
+    /// when ids are not contiguous.
+    #[tokio::test]
+    async fn test_random_selection_is_uniform() {
+        let conn = get_populated_test_audit_db().await.unwrap();
+        // Leave gaps in the ids, and remove the lowest id.
+        content::Entity::delete_many()
+            .filter(content::Column::Id.is_in([1, 10, 11, 12, 30]))
+            .exec(&conn)
+            .await
+            .unwrap();
+        const ROWS: usize = 40;
+        const ROUNDS: usize = 400;
+        const PER_ROUND: u64 = 10;
+
+        let mut picks: HashMap<i32, usize> = HashMap::new();
+        for _ in 0..ROUNDS {
+            let selected = select_random_content(PER_ROUND, &conn).await.unwrap();
+            assert_eq!(selected.len(), PER_ROUND as usize);
+            let distinct: HashSet<i32> = selected.iter().map(|content| content.id).collect();
+            assert_eq!(distinct.len(), PER_ROUND as usize);
+            for id in distinct {
+                *picks.entry(id).or_default() += 1;
+            }
+        }
+        // Only stored rows are picked, and every one of them is picked, including the last.
+        assert_eq!(picks.len(), ROWS);
+        assert!(picks.contains_key(&45));
+        for deleted in [1, 10, 11, 12, 30] {
+            assert!(!picks.contains_key(&deleted));
+        }
+
+        // Pearson's chi-squared statistic against equal picks for every row. With 39 degrees
+        // of freedom, a uniform sampler exceeds 90 about once in a million runs.
+        let expected = (ROUNDS * PER_ROUND as usize) as f64 / ROWS as f64;
+        let chi_squared: f64 = picks
+            .values()
+            .map(|observed| (*observed as f64 - expected).powi(2) / expected)
+            .sum();
+        assert!(chi_squared < 90.0, "chi squared was {chi_squared}");
+    }
 }
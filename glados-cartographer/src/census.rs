@@ -0,0 +1,227 @@
+use std::collections::{HashSet, VecDeque};
+
+use ethereum_types::H256;
+use sea_orm::DatabaseConnection;
+use tracing::{debug, error, info, warn};
+use trin_types::{enr::Enr, node_id::NodeId};
+
+use entity::{census, record};
+use glados_core::jsonrpc::{JsonRpcError, PortalApi, Subnetwork};
+
+use crate::metrics::{CENSUS_NODES_RESPONDED, CENSUS_NODES_SEEN};
+
+/// Log distances requested from each node, which cover the furthest half, quarter and
+/// eighth of the keyspace from that node.
+const FIND_NODES_DISTANCES: [u16; 3] = [256, 255, 254];
+
+/// Returns one random target within each of the `2^prefix_bits` equal regions of the keyspace.
+pub(crate) fn census_targets(prefix_bits: u8) -> Vec<H256> {
+    let shift = 16 - u32::from(prefix_bits);
+    let mask = (u32::from(u16::MAX) << shift) as u16;
+    (0..1u32 << prefix_bits)
+        .map(|prefix| {
+            let mut target = NodeId::random().0;
+            let high = u16::from_be_bytes([target[0], target[1]]);
+            let high = (high & !mask) | (prefix << shift) as u16;
+            target[..2].copy_from_slice(&high.to_be_bytes());
+            H256::from(target)
+        })
+        .collect()
+}
+
+/// Nodes discovered so far during a census.
+#[derive(Default)]
+struct Sweep {
+    /// Node ids of every node seen.
+    seen: HashSet<H256>,
+    /// Nodes that have not been asked for their neighbours yet.
+    pending: VecDeque<(Enr, record::Model)>,
+    /// ENRs of the nodes that answered a FINDNODES request.
+    responded: Vec<record::Model>,
+}
+
+impl Sweep {
+    /// Saves ENRs of nodes not seen before and queues them to be asked for their neighbours.
+    async fn add(&mut self, enrs: Vec<Enr>, conn: &DatabaseConnection) {
+        for enr in enrs {
+            let node_id = H256::from(enr.node_id().raw());
+            if self.seen.contains(&node_id) {
+                continue;
+            }
+            match record::get_or_create(&enr, conn).await {
+                Ok(enr_record) => {
+                    self.seen.insert(node_id);
+                    self.pending.push_back((enr, enr_record));
+                }
+                Err(e) => error!(enr.node_id=?node_id, err=?e, "Could not save ENR"),
+            }
+        }
+    }
+}
+
+/// Sweeps the whole DHT and saves the nodes found as a census.
+///
+/// A recursive lookup is made toward a target in each region of the keyspace, then every
+/// node found is asked for the nodes in its furthest buckets until no new nodes turn up.
+/// The census only includes nodes that answered, so that stale ENRs are not counted.
+///
+/// If the local Portal node cannot be reached the census is left unfinished.
+pub(crate) async fn perform_census(api: &PortalApi, conn: &DatabaseConnection, prefix_bits: u8) {
+    let census_model = match census::create(conn).await {
+        Ok(census_model) => census_model,
+        Err(e) => {
+            error!(err=?e, "Could not start census");
+            return;
+        }
+    };
+    info!(census.id = census_model.id, "Starting census");
+
+    let mut sweep = Sweep::default();
+    for target in census_targets(prefix_bits) {
+        match api.recursive_find_nodes(target).await {
+            Ok(enrs) => sweep.add(enrs, conn).await,
+            Err(e) if e.is_connection_failure() => return abandon(census_model.id, api, e),
+            Err(e) => warn!(target.node_id=?target, err=?e, "RFN request failed"),
+        }
+    }
+    info!(
+        census.id = census_model.id,
+        count = sweep.seen.len(),
+        "Census lookups finished, querying found nodes",
+    );
+
+    while let Some((enr, enr_record)) = sweep.pending.pop_front() {
+        match api
+            .find_nodes(Subnetwork::History, &enr, &FIND_NODES_DISTANCES)
+            .await
+        {
+            Ok(found) => {
+                sweep.responded.push(enr_record);
+                sweep.add(found.enrs, conn).await;
+            }
+            Err(e) if e.is_connection_failure() => return abandon(census_model.id, api, e),
+            Err(e) => debug!(
+                enr.node_id=?H256::from(enr.node_id().raw()),
+                err=?e,
+                "FINDNODES request failed",
+            ),
+        }
+    }
+
+    let census_id = census_model.id;
+    match census::finish(census_model, &sweep.responded, conn).await {
+        Ok(census_model) => {
+            CENSUS_NODES_SEEN.set(sweep.seen.len() as i64);
+            CENSUS_NODES_RESPONDED.set(sweep.responded.len() as i64);
+            info!(
+                census.id = census_id,
+                seen = sweep.seen.len(),
+                responded = sweep.responded.len(),
+                duration=?census_model.duration(),
+                "Census finished",
+            )
+        }
+        Err(e) => error!(census.id = census_id, err=?e, "Could not save census"),
+    }
+}
+
+fn abandon(census_id: i32, api: &PortalApi, err: JsonRpcError) {
+    error!(
+        census.id = census_id,
+        client.url = api.client_url,
+        err=?err,
+        "Portal client unreachable, census abandoned",
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use glados_test_support::{
+        setup_database, MockPortalNode, MockResponse, MOCK_ENR, PEER_ENR as FOUND_ENR,
+    };
+    use sea_orm::EntityTrait;
+    use serde_json::json;
+
+    use entity::census_node;
+
+    use super::*;
+
+    #[test]
+    fn test_census_targets_cover_keyspace() {
+        assert_eq!(census_targets(0).len(), 1);
+        let prefixes: Vec<u8> = census_targets(2)
+            .iter()
+            .map(|target| target[0] >> 6)
+            .collect();
+        assert_eq!(prefixes, vec![0, 1, 2, 3]);
+        let prefixes: Vec<u16> = census_targets(16)
+            .iter()
+            .map(|target| u16::from_be_bytes([target[0], target[1]]))
+            .collect();
+        assert_eq!(prefixes, (0..=u16::MAX).collect::<Vec<u16>>());
+    }
+
+    /// Tests that nodes found by lookups are crawled and only responsive nodes are counted.
+    #[tokio::test]
+    async fn test_census_counts_responding_nodes() {
+        let conn = setup_database().await;
+        let node = MockPortalNode::new();
+        node.respond(
+            "portal_historyRecursiveFindNodes",
+            MockResponse::Result(json!([FOUND_ENR])),
+        );
+        node.respond(
+            "portal_historyFindNodes",
+            MockResponse::Error {
+                code: -32000,
+                message: "request timed out".to_owned(),
+            },
+        );
+        node.respond_to(
+            "portal_historyFindNodes",
+            json!([FOUND_ENR, FIND_NODES_DISTANCES]),
+            MockResponse::Result(json!({"total": 1, "enrs": [MOCK_ENR, FOUND_ENR]})),
+        );
+
+        let api = PortalApi::new(node.serve_http().await.unwrap()).unwrap();
+        perform_census(&api, &conn, 2).await;
+
+        assert_eq!(node.request_count("portal_historyRecursiveFindNodes"), 4);
+        // Each node is only asked once, even if it is found again.
+        assert_eq!(node.request_count("portal_historyFindNodes"), 2);
+        assert_eq!(record::Entity::find().all(&conn).await.unwrap().len(), 2);
+
+        let censuses = census::get_finished(10, &conn).await.unwrap();
+        assert_eq!(censuses.len(), 1);
+        let found = record::Entity::find()
+            .all(&conn)
+            .await
+            .unwrap()
+            .into_iter()
+            .find(|enr_record| enr_record.raw == FOUND_ENR)
+            .unwrap();
+        assert_eq!(
+            census_node::get_node_ids(censuses[0].id, &conn)
+                .await
+                .unwrap(),
+            vec![found.node_id]
+        );
+        assert_eq!(CENSUS_NODES_SEEN.get(), 2);
+        assert_eq!(CENSUS_NODES_RESPONDED.get(), 1);
+    }
+
+    /// Tests that a census is not finished when the local node is down.
+    #[tokio::test]
+    async fn test_census_abandoned_without_portal_client() {
+        let conn = setup_database().await;
+
+        // Nothing listens on the discard port.
+        let api = PortalApi::new("http://127.0.0.1:9".to_owned()).unwrap();
+        perform_census(&api, &conn, 2).await;
+
+        let censuses = census::Entity::find().all(&conn).await.unwrap();
+        assert_eq!(censuses.len(), 1);
+        assert_eq!(censuses[0].ended_at, None);
+        assert!(census::get_finished(10, &conn).await.unwrap().is_empty());
+    }
+}
@@ -1,32 +1,90 @@
-use clap::{Parser, ValueEnum};
-use std::path::PathBuf;
-use url::Url;
+use clap::{ArgAction, Parser, ValueEnum};
+use std::net::SocketAddr;
 
 const DEFAULT_DB_URL: &str = "sqlite::memory:";
 
 // 15 minutes
 const DEFAULT_AUDIT_INTERVAL: &str = "30";
 
+// 5 minutes
+const DEFAULT_LIVENESS_INTERVAL: &str = "300";
+
+// 1 hour
+const DEFAULT_CENSUS_INTERVAL: &str = "3600";
+
+const DEFAULT_CENSUS_PREFIX_BITS: &str = "4";
+
 #[derive(Clone, Debug, Eq, Parser, PartialEq)]
 #[command(author, version, about, long_about = None)]
 pub struct Args {
     #[arg(short, long, default_value = DEFAULT_DB_URL)]
     pub database_url: String,
-    #[arg(short, long, requires = "transport")]
-    pub ipc_path: Option<PathBuf>,
-    #[arg(short = 'u', long, requires = "transport")]
-    pub http_url: Option<Url>,
-    #[arg(short, long)]
-    pub transport: TransportType,
+    /// The portal client to explore the network through, as an `ipc:///`, `http://`,
+    /// `https://`, `ws://` or `wss://` URL.
+    #[arg(long)]
+    pub portal_client: String,
+    /// Header sent with every request to an https:// or wss:// portal client, written as
+    /// 'Name: value'. May be passed multiple times.
+    #[arg(long, action(ArgAction::Append))]
+    pub portal_client_header: Vec<String>,
+    /// Bearer token sent in the Authorization header to an https:// or wss:// portal client.
+    #[arg(long)]
+    pub portal_client_bearer_token: Option<String>,
     #[arg(short, long, default_value = DEFAULT_AUDIT_INTERVAL)]
     pub probe_interval: u64,
+    /// Seconds between rounds of pinging every known node to check that it is reachable.
+    #[arg(long, default_value = DEFAULT_LIVENESS_INTERVAL)]
+    pub liveness_interval: u64,
+    /// How the network is explored: a lookup toward one random node per interval, or a
+    /// census that sweeps the whole keyspace.
+    #[arg(long, value_enum, default_value = "probe")]
+    pub mode: CartographerMode,
+    /// Seconds between the start of each census in 'census' mode.
+    #[arg(long, default_value = DEFAULT_CENSUS_INTERVAL)]
+    pub census_interval: u64,
+    /// A census starts with one lookup in each of 2^N regions of the keyspace.
+    #[arg(long, default_value = DEFAULT_CENSUS_PREFIX_BITS, value_parser = clap::value_parser!(u8).range(0..=16))]
+    pub census_prefix_bits: u8,
+    /// Address to serve Prometheus metrics at /metrics on, e.g. 127.0.0.1:9103. Metrics are
+    /// not served unless set.
+    #[arg(long)]
+    pub metrics_address: Option<SocketAddr>,
 }
 
-/// Used by a user to specify the intended form of transport
-/// to connect to a Portal node.
+/// Used by a user to specify how the network is explored.
 #[derive(Debug, Clone, Eq, PartialEq, ValueEnum)]
 #[clap(rename_all = "snake_case")]
-pub enum TransportType {
-    IPC,
-    HTTP,
+pub enum CartographerMode {
+    /// Look up the nodes closest to a random node id once per probe interval.
+    Probe,
+    /// Periodically sweep the whole DHT and save the nodes found as a snapshot.
+    Census,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Tests that headers and a bearer token can be passed for an authenticated endpoint.
+    #[test]
+    fn test_portal_client_headers() {
+        const PORTAL_CLIENT_STRING: &str = "wss://portal.example.com";
+        let result = Args::parse_from([
+            "test",
+            "--portal-client",
+            PORTAL_CLIENT_STRING,
+            "--portal-client-header",
+            "X-Api-Key: abc",
+            "--portal-client-header",
+            "X-Team: glados",
+            "--portal-client-bearer-token",
+            "secret",
+        ]);
+        assert_eq!(result.portal_client, PORTAL_CLIENT_STRING);
+        assert_eq!(
+            result.portal_client_header,
+            vec!["X-Api-Key: abc".to_owned(), "X-Team: glados".to_owned()]
+        );
+        assert_eq!(result.portal_client_bearer_token, Some("secret".to_owned()));
+    }
 }
@@ -1,22 +1,30 @@
-use anyhow::{bail, Result};
+use std::net::SocketAddr;
+
+use anyhow::Result;
 use clap::Parser;
 use cli::Args;
 use ethereum_types::H256;
-use ethportal_api::HistoryNetworkApiClient;
-use ethportal_api::{
-    jsonrpsee::http_client::HttpClientBuilder, types::discv5::NodeId as EthPortalNodeId,
-};
 use sea_orm::DatabaseConnection;
-use tokio::time::{self, Duration};
-use tracing::{debug, info};
-use trin_types::node_id::NodeId;
+use tokio::{
+    task::JoinSet,
+    time::{self, Duration},
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn};
+use trin_types::{enr::Enr, node_id::NodeId};
 
-use entity::record;
-use glados_core::jsonrpc::TransportConfig;
+use entity::{node_radius, record};
+use glados_core::jsonrpc::{PortalApi, RequestHeaders, Subnetwork, TransportConfig};
 
-use crate::cli::TransportType;
+use crate::census::perform_census;
+use crate::cli::CartographerMode;
+use crate::liveness::perform_liveness_checks;
+use crate::metrics::PROBE_ENRS_FOUND;
 
+mod census;
 pub mod cli;
+mod liveness;
+mod metrics;
 
 /// Configuration created from CLI arguments.
 #[derive(Clone, Debug)]
@@ -27,29 +35,38 @@ pub struct CartographerConfig {
     pub transport: TransportConfig,
     /// Defines the rate at which the network is probed in seconds
     pub probe_interval: u64,
+    /// Defines the rate at which known nodes are checked for liveness in seconds
+    pub liveness_interval: u64,
+    /// Whether the network is probed at random or swept by a census
+    pub mode: CartographerMode,
+    /// Defines the rate at which censuses are started in seconds
+    pub census_interval: u64,
+    /// A census starts with a lookup in each of 2^N regions of the keyspace
+    pub census_prefix_bits: u8,
+    /// Where metrics are served, if anywhere.
+    pub metrics_address: Option<SocketAddr>,
 }
 
 impl CartographerConfig {
     pub fn from_args() -> Result<CartographerConfig> {
         let args = Args::parse();
-        let transport: TransportConfig = match args.transport {
-            TransportType::IPC => match args.ipc_path {
-                Some(p) => TransportConfig::IPC(p),
-                None => {
-                    bail!("The '--ipc-path' flag is required if '--transport ipc' variant is selected.")
-                }
-            },
-            TransportType::HTTP => match args.http_url {
-                Some(h) => TransportConfig::HTTP(h),
-                None => {
-                    bail!("The '--http-url' flag is required if '--transport http' variant is selected.");
-                }
-            },
-        };
+        let mut headers = RequestHeaders::default();
+        for header in &args.portal_client_header {
+            headers.insert_parsed(header)?;
+        }
+        if let Some(token) = &args.portal_client_bearer_token {
+            headers.insert_bearer_token(token)?;
+        }
+        let transport = TransportConfig::from_url(&args.portal_client, headers)?;
         Ok(CartographerConfig {
             database_url: args.database_url,
             transport,
             probe_interval: args.probe_interval,
+            liveness_interval: args.liveness_interval,
+            mode: args.mode,
+            census_interval: args.census_interval,
+            census_prefix_bits: args.census_prefix_bits,
+            metrics_address: args.metrics_address,
         })
     }
 }
@@ -61,39 +78,112 @@ pub struct DHTAudit {
     pub pending_nodes: Vec<NodeId>,
 }
 
-pub async fn run_glados_cartographer(conn: DatabaseConnection, config: CartographerConfig) {
-    tokio::spawn(perform_dht_audits(config, conn.clone()));
-    debug!("setting up CTRL+C listener");
-    tokio::signal::ctrl_c()
-        .await
-        .expect("failed to pause until ctrl-c");
+/// How long rounds in progress are given to finish after shutdown is signalled.
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Maps the network until shutdown is signalled.
+///
+/// On shutdown, no new rounds are started, and rounds in progress are given
+/// [SHUTDOWN_TIMEOUT] to finish.
+pub async fn run_glados_cartographer(
+    conn: DatabaseConnection,
+    config: CartographerConfig,
+    shutdown: CancellationToken,
+) {
+    let mut rounds = JoinSet::new();
+    match config.mode {
+        CartographerMode::Probe => rounds.spawn(perform_dht_audits(
+            config.clone(),
+            conn.clone(),
+            shutdown.clone(),
+        )),
+        CartographerMode::Census => rounds.spawn(perform_censuses(
+            config.clone(),
+            conn.clone(),
+            shutdown.clone(),
+        )),
+    };
+    rounds.spawn(perform_liveness_audits(
+        config,
+        conn.clone(),
+        shutdown.clone(),
+    ));
+
+    shutdown.cancelled().await;
+    info!("Waiting for rounds in progress to finish.");
+    let finished = time::timeout(SHUTDOWN_TIMEOUT, async {
+        while rounds.join_next().await.is_some() {}
+    })
+    .await;
+    if finished.is_err() {
+        warn!(
+            timeout.seconds = SHUTDOWN_TIMEOUT.as_secs(),
+            "Rounds in progress did not finish in time, abandoning them."
+        );
+        rounds.shutdown().await;
+    }
+}
 
-    info!("got CTRL+C. shutting down...");
+/// Waits for the next tick, returning false if shutdown is signalled first.
+async fn next_round(interval: &mut time::Interval, shutdown: &CancellationToken) -> bool {
+    tokio::select! {
+        _ = shutdown.cancelled() => false,
+        _ = interval.tick() => true,
+    }
 }
 
-async fn perform_dht_audits(config: CartographerConfig, conn: DatabaseConnection) {
+async fn perform_dht_audits(
+    config: CartographerConfig,
+    conn: DatabaseConnection,
+    shutdown: CancellationToken,
+) {
     let mut interval = time::interval(Duration::from_secs(config.probe_interval));
+    let api = PortalApi::from_config(config.transport);
 
-    loop {
+    while next_round(&mut interval, &shutdown).await {
         debug!("Begin main cartographer audit loop");
-        interval.tick().await;
-        perform_dht_probe(&config, &conn).await;
+        perform_dht_probe(&api, &conn).await;
         debug!("End main cartographer audit loop");
     }
 }
 
+async fn perform_censuses(
+    config: CartographerConfig,
+    conn: DatabaseConnection,
+    shutdown: CancellationToken,
+) {
+    let mut interval = time::interval(Duration::from_secs(config.census_interval));
+    // A census may outlast the interval, in which case the next one starts right away.
+    interval.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+    let api = PortalApi::from_config(config.transport);
+
+    while next_round(&mut interval, &shutdown).await {
+        debug!("Begin census");
+        perform_census(&api, &conn, config.census_prefix_bits).await;
+        debug!("End census");
+    }
+}
+
+async fn perform_liveness_audits(
+    config: CartographerConfig,
+    conn: DatabaseConnection,
+    shutdown: CancellationToken,
+) {
+    let mut interval = time::interval(Duration::from_secs(config.liveness_interval));
+    let api = PortalApi::from_config(config.transport);
+
+    while next_round(&mut interval, &shutdown).await {
+        debug!("Begin liveness check round");
+        perform_liveness_checks(&api, &conn).await;
+        debug!("End liveness check round");
+    }
+}
+
 /// Performs an audit against a Portal node.
 ///
 /// After auditing finishes the thread counter is deprecated. This
 /// applies even if the audit process encounters an error.
-async fn perform_dht_probe(config: &CartographerConfig, conn: &DatabaseConnection) {
-    let client = match &config.transport {
-        TransportConfig::HTTP(http_url) => HttpClientBuilder::default()
-            .build(http_url.as_ref())
-            .unwrap(),
-        TransportConfig::IPC(_path) => panic!("not implemented"),
-    };
-
+async fn perform_dht_probe(api: &PortalApi, conn: &DatabaseConnection) {
     let target = NodeId::random();
     let target_display = H256::from(target.0);
 
@@ -102,24 +192,189 @@ async fn perform_dht_probe(config: &CartographerConfig, conn: &DatabaseConnectio
         "Performing RFN on DHT",
     );
 
-    let found_enrs = client
-        .recursive_find_nodes(EthPortalNodeId(target.raw()))
-        .await
-        .unwrap();
+    let found_enrs = match api.recursive_find_nodes(target_display).await {
+        Ok(enrs) => enrs,
+        Err(e) => {
+            error!(
+                target.node_id=?target_display,
+                client.url=api.client_url,
+                err=?e,
+                "RFN request failed",
+            );
+            return;
+        }
+    };
 
     info!(
         target.node_id=?target_display,
         count=?found_enrs.len(),
         "RFN found ENR records",
     );
+    PROBE_ENRS_FOUND.set(found_enrs.len() as i64);
 
     for enr in found_enrs {
-        record::get_or_create(&enr, conn).await.unwrap();
+        let enr_record = record::get_or_create(&enr, conn).await.unwrap();
         info!(
         enr.base64=?enr,
         enr.seq=?enr.seq(),
         enr.node_id=?H256::from(enr.node_id().raw()),
         "ENR saved",
         );
+        record_data_radius(api, &enr, enr_record.node_id, conn).await;
+    }
+}
+
+/// Pings a node and saves the data radius that it reports.
+async fn record_data_radius(
+    api: &PortalApi,
+    enr: &Enr,
+    node_model_id: i32,
+    conn: &DatabaseConnection,
+) {
+    let node_id = H256::from(enr.node_id().raw());
+    let pong = match api.ping(Subnetwork::History, enr).await {
+        Ok(pong) => pong,
+        Err(e) => {
+            warn!(
+                enr.node_id=?node_id,
+                err=?e,
+                "Ping failed, data radius not recorded",
+            );
+            return;
+        }
+    };
+    let mut data_radius = [0u8; 32];
+    pong.data_radius.to_big_endian(&mut data_radius);
+    match node_radius::create(node_model_id, data_radius.to_vec(), conn).await {
+        Ok(_) => debug!(
+            enr.node_id=?node_id,
+            data_radius=?pong.data_radius,
+            "Data radius saved",
+        ),
+        Err(e) => error!(
+            enr.node_id=?node_id,
+            err=?e,
+            "Could not save data radius",
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glados_test_support::{
+        setup_database, MockPortalNode, MockResponse, PEER_ENR as FOUND_ENR,
+    };
+    use sea_orm::EntityTrait;
+    use serde_json::json;
+
+    use super::*;
+
+    /// Tests that ENRs found over each transport are saved.
+    #[tokio::test]
+    async fn test_dht_probe_saves_found_enrs() {
+        let conn = setup_database().await;
+        let node = MockPortalNode::new();
+        node.respond(
+            "portal_historyRecursiveFindNodes",
+            MockResponse::Result(json!([FOUND_ENR])),
+        );
+        node.respond_to(
+            "portal_historyPing",
+            json!([FOUND_ENR]),
+            MockResponse::Result(json!({
+                "enrSeq": 1,
+                "dataRadius": "0xffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+            })),
+        );
+
+        let urls = [
+            node.serve_http().await.unwrap(),
+            node.serve_ipc().await.unwrap(),
+        ];
+        for url in urls {
+            let api = PortalApi::new(url).unwrap();
+            perform_dht_probe(&api, &conn).await;
+        }
+
+        assert_eq!(node.request_count("portal_historyRecursiveFindNodes"), 2);
+        let records = record::Entity::find().all(&conn).await.unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].raw, FOUND_ENR);
+        let radii = node_radius::get_history(records[0].node_id, 10, &conn)
+            .await
+            .unwrap();
+        assert_eq!(radii.len(), 2);
+        assert_eq!(radii[0].data_radius, vec![0xff; 32]);
+    }
+
+    /// Tests that a failed lookup does not stop the cartographer.
+    #[tokio::test]
+    async fn test_dht_probe_survives_error_response() {
+        let conn = setup_database().await;
+        let node = MockPortalNode::new();
+        node.respond(
+            "portal_historyRecursiveFindNodes",
+            MockResponse::Error {
+                code: -32000,
+                message: "lookup failed".to_owned(),
+            },
+        );
+
+        let api = PortalApi::new(node.serve_http().await.unwrap()).unwrap();
+        perform_dht_probe(&api, &conn).await;
+
+        let records = record::Entity::find().all(&conn).await.unwrap();
+        assert!(records.is_empty());
+    }
+
+    /// Tests that shutdown lets a probe in progress finish, and starts no further rounds.
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_shutdown_finishes_probe() {
+        let conn = setup_database().await;
+        let node = MockPortalNode::new();
+        node.respond(
+            "portal_historyRecursiveFindNodes",
+            MockResponse::Slow(
+                Duration::from_millis(300),
+                Box::new(MockResponse::Result(json!([FOUND_ENR]))),
+            ),
+        );
+        node.respond(
+            "portal_historyPing",
+            MockResponse::Result(json!({"enrSeq": 1, "dataRadius": "0x0"})),
+        );
+        let url = node.serve_http().await.unwrap();
+        let config = CartographerConfig {
+            database_url: "sqlite::memory:".to_owned(),
+            transport: TransportConfig::from_url(&url, Default::default()).unwrap(),
+            probe_interval: 3600,
+            liveness_interval: 3600,
+            mode: CartographerMode::Probe,
+            census_interval: 3600,
+            census_prefix_bits: 0,
+            metrics_address: None,
+        };
+        let shutdown = CancellationToken::new();
+        let cartographer = tokio::spawn(run_glados_cartographer(
+            conn.clone(),
+            config,
+            shutdown.clone(),
+        ));
+
+        for _ in 0..100 {
+            if node.request_count("portal_historyRecursiveFindNodes") > 0 {
+                break;
+            }
+            time::sleep(Duration::from_millis(10)).await;
+        }
+        shutdown.cancel();
+        time::timeout(Duration::from_secs(5), cartographer)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(node.request_count("portal_historyRecursiveFindNodes"), 1);
+        let records = record::Entity::find().all(&conn).await.unwrap();
+        assert_eq!(records.len(), 1);
     }
 }
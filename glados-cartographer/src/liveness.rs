@@ -0,0 +1,158 @@
+use std::{str::FromStr, time::Instant};
+
+use ethereum_types::H256;
+use sea_orm::DatabaseConnection;
+use tokio::task::JoinSet;
+use tracing::{debug, error, info, warn};
+use trin_types::enr::Enr;
+
+use entity::{node_liveness, record};
+use glados_core::jsonrpc::{PortalApi, Subnetwork};
+
+use crate::metrics::{LIVENESS_CHECKS, LIVENESS_NODES};
+
+/// Number of pings that are in flight at once.
+const CONCURRENT_PINGS: usize = 16;
+
+/// Pings every known node at its latest ENR and records whether it responded.
+pub(crate) async fn perform_liveness_checks(api: &PortalApi, conn: &DatabaseConnection) {
+    let records = match record::get_latest_for_each_node(conn).await {
+        Ok(records) => records,
+        Err(e) => {
+            error!(err=?e, "Could not look up nodes for liveness checks");
+            return;
+        }
+    };
+    info!(count = records.len(), "Checking liveness of known nodes");
+    LIVENESS_NODES.set(records.len() as i64);
+
+    let mut pending = records.into_iter();
+    let mut checks = JoinSet::new();
+    loop {
+        while checks.len() < CONCURRENT_PINGS {
+            let Some(enr_record) = pending.next() else {
+                break;
+            };
+            checks.spawn(check_liveness(api.clone(), enr_record, conn.clone()));
+        }
+        if checks.join_next().await.is_none() {
+            break;
+        }
+    }
+}
+
+/// Pings the node and records the outcome, unless the local node could not be reached.
+async fn check_liveness(api: PortalApi, enr_record: record::Model, conn: DatabaseConnection) {
+    let enr = match Enr::from_str(&enr_record.raw) {
+        Ok(enr) => enr,
+        Err(e) => {
+            warn!(record.id = enr_record.id, err=?e, "Could not decode stored ENR");
+            return;
+        }
+    };
+    let node_id = H256::from(enr.node_id().raw());
+
+    let start = Instant::now();
+    let latency_ms = match api.ping(Subnetwork::History, &enr).await {
+        Ok(_) => Some(i32::try_from(start.elapsed().as_millis()).unwrap_or(i32::MAX)),
+        Err(e) if e.is_connection_failure() => {
+            warn!(
+                client.url = api.client_url,
+                err=?e,
+                "Portal client unreachable, liveness not recorded",
+            );
+            return;
+        }
+        Err(e) => {
+            debug!(enr.node_id=?node_id, err=?e, "Ping failed");
+            None
+        }
+    };
+
+    let result = match latency_ms {
+        Some(_) => "alive",
+        None => "unresponsive",
+    };
+    LIVENESS_CHECKS.with_label_values(&[result]).inc();
+    if let Err(e) = node_liveness::create(&enr_record, latency_ms, &conn).await {
+        error!(enr.node_id=?node_id, err=?e, "Could not save liveness check");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glados_test_support::{
+        setup_database, MockPortalNode, MockResponse, MOCK_ENR, PEER_ENR as UNREACHABLE_ENR,
+    };
+    use sea_orm::EntityTrait;
+    use serde_json::json;
+
+    use super::*;
+
+    async fn save_records(conn: &DatabaseConnection) -> (record::Model, record::Model) {
+        let reachable = Enr::from_str(MOCK_ENR).unwrap();
+        let unreachable = Enr::from_str(UNREACHABLE_ENR).unwrap();
+        (
+            record::get_or_create(&reachable, conn).await.unwrap(),
+            record::get_or_create(&unreachable, conn).await.unwrap(),
+        )
+    }
+
+    /// Tests that responsive and unresponsive nodes are both recorded.
+    #[tokio::test]
+    async fn test_liveness_checks_record_outcome() {
+        let conn = setup_database().await;
+        let (reachable, unreachable) = save_records(&conn).await;
+        let node = MockPortalNode::new();
+        node.respond(
+            "portal_historyPing",
+            MockResponse::Error {
+                code: -32000,
+                message: "ping timed out".to_owned(),
+            },
+        );
+        node.respond_to(
+            "portal_historyPing",
+            json!([MOCK_ENR]),
+            MockResponse::Result(json!({
+                "enrSeq": 1,
+                "dataRadius": "0xffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+            })),
+        );
+
+        let checks = |result: &str| LIVENESS_CHECKS.with_label_values(&[result]).get();
+        let (alive_checks, unresponsive_checks) = (checks("alive"), checks("unresponsive"));
+        let api = PortalApi::new(node.serve_http().await.unwrap()).unwrap();
+        perform_liveness_checks(&api, &conn).await;
+
+        assert_eq!(node.request_count("portal_historyPing"), 2);
+        let alive = node_liveness::get_history(reachable.node_id, 10, &conn)
+            .await
+            .unwrap();
+        assert_eq!(alive.len(), 1);
+        assert!(alive[0].is_alive);
+        assert!(alive[0].latency_ms.is_some());
+        let dead = node_liveness::get_history(unreachable.node_id, 10, &conn)
+            .await
+            .unwrap();
+        assert_eq!(dead.len(), 1);
+        assert!(!dead[0].is_alive);
+        assert_eq!(dead[0].latency_ms, None);
+        assert_eq!(checks("alive") - alive_checks, 1);
+        assert_eq!(checks("unresponsive") - unresponsive_checks, 1);
+    }
+
+    /// Tests that nodes are not marked as unreachable when the local node is down.
+    #[tokio::test]
+    async fn test_liveness_checks_skipped_without_portal_client() {
+        let conn = setup_database().await;
+        save_records(&conn).await;
+
+        // Nothing listens on the discard port.
+        let api = PortalApi::new("http://127.0.0.1:9".to_owned()).unwrap();
+        perform_liveness_checks(&api, &conn).await;
+
+        let checks = node_liveness::Entity::find().all(&conn).await.unwrap();
+        assert!(checks.is_empty());
+    }
+}
@@ -1,8 +1,11 @@
 use anyhow::Result;
 use sea_orm::Database;
-use tracing::{debug, info};
+use tokio::signal;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info};
 
 use glados_cartographer::{run_glados_cartographer, CartographerConfig};
+use glados_core::metrics;
 use migration::{Migrator, MigratorTrait};
 
 #[tokio::main]
@@ -36,6 +39,19 @@ async fn main() -> Result<()> {
 
     Migrator::up(&conn, None).await?;
 
-    run_glados_cartographer(conn, config).await;
+    if let Some(address) = config.metrics_address {
+        tokio::spawn(async move {
+            if let Err(e) = metrics::serve(address).await {
+                error!(err=?e, "Could not serve metrics.");
+            }
+        });
+    }
+
+    let shutdown = CancellationToken::new();
+    let cartographer = tokio::spawn(run_glados_cartographer(conn, config, shutdown.clone()));
+    signal::ctrl_c().await?;
+    info!("got CTRL+C. shutting down...");
+    shutdown.cancel();
+    cartographer.await?;
     Ok(())
 }
@@ -0,0 +1,40 @@
+use lazy_static::lazy_static;
+use prometheus::{register_int_counter_vec, register_int_gauge, IntCounterVec, IntGauge};
+
+lazy_static! {
+    /// ENRs found by the latest probe.
+    pub static ref PROBE_ENRS_FOUND: IntGauge = register_int_gauge!(
+        "glados_cartographer_probe_enrs_found",
+        "ENRs found by the latest probe."
+    )
+    .expect("probe metric can be registered");
+
+    /// Nodes seen by the latest finished census.
+    pub static ref CENSUS_NODES_SEEN: IntGauge = register_int_gauge!(
+        "glados_cartographer_census_nodes_seen",
+        "Nodes seen by the latest finished census."
+    )
+    .expect("census metric can be registered");
+
+    /// Nodes that answered during the latest finished census, which are the ones it counts.
+    pub static ref CENSUS_NODES_RESPONDED: IntGauge = register_int_gauge!(
+        "glados_cartographer_census_nodes_responded",
+        "Nodes that answered during the latest finished census."
+    )
+    .expect("census metric can be registered");
+
+    /// Known nodes checked by the latest liveness round.
+    pub static ref LIVENESS_NODES: IntGauge = register_int_gauge!(
+        "glados_cartographer_liveness_nodes",
+        "Known nodes checked by the latest liveness round."
+    )
+    .expect("liveness metric can be registered");
+
+    /// Liveness checks recorded, by whether the node answered.
+    pub static ref LIVENESS_CHECKS: IntCounterVec = register_int_counter_vec!(
+        "glados_cartographer_liveness_checks_total",
+        "Liveness checks recorded, by whether the node answered.",
+        &["result"]
+    )
+    .expect("liveness metric can be registered");
+}
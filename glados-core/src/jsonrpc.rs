@@ -1,18 +1,26 @@
-use std::io::Write;
 #[cfg(unix)]
-use std::os::unix::net::UnixStream;
+use std::collections::HashMap;
+use std::fmt;
 use std::path::PathBuf;
 use std::str::FromStr;
+#[cfg(unix)]
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+#[cfg(unix)]
+use std::time::Duration;
 
 use ethereum_types::{H256, U256};
 use ethportal_api::types::content_key::OverlayContentKey;
+use http::header::{HeaderName, AUTHORIZATION};
+#[cfg(unix)]
 use jsonrpc::Request;
 use jsonrpsee::{
     core::{client::ClientT, params::ArrayParams},
-    http_client::{HttpClient, HttpClientBuilder},
+    http_client::{HeaderMap, HeaderValue, HttpClient, HttpClientBuilder},
     rpc_params,
+    ws_client::{WsClient, WsClientBuilder},
 };
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::{
     json,
     value::{to_raw_value, RawValue},
@@ -21,30 +29,62 @@ use serde_json::{
 use trin_utils::bytes::hex_encode;
 
 use thiserror::Error;
-use tracing::error;
+use tokio::sync::Mutex;
+#[cfg(unix)]
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{
+        unix::{OwnedReadHalf, OwnedWriteHalf},
+        UnixStream,
+    },
+    sync::oneshot,
+};
+use tracing::{debug, warn};
 use trin_utils::bytes::{hex_decode, ByteUtilsError};
-#[cfg(windows)]
-use uds_windows::UnixStream;
 use url::Url;
 
+use crate::metrics::RPC_ERRORS;
+
 use ethportal_api::types::discv5::Enr;
+pub use ethportal_api::types::portal::{AcceptInfo, DataRadius, FindNodesInfo, PongInfo};
 
 /// Configuration details for connection to a Portal network node.
 #[derive(Clone, Debug)]
 pub enum TransportConfig {
     HTTP(Url),
+    /// HTTP over TLS. The headers are sent with every request.
+    HTTPS(Url, RequestHeaders),
+    /// WebSocket (`ws://` or `wss://`). The headers are sent with the opening handshake.
+    WS(Url, RequestHeaders),
     IPC(PathBuf),
 }
 
+/// Additional headers for HTTPS and WebSocket connections, such as those
+/// required by an authenticating proxy in front of a Portal node.
+///
+/// Header values are omitted from debug output so that credentials are not logged.
+#[derive(Clone, Default)]
+pub struct RequestHeaders(HeaderMap);
+
 /// Details for a Connection to a Portal network node over different transports.
+#[derive(Clone, Debug)]
 pub enum Transport {
     HTTP(HttpClientManager),
+    WS(WsClientManager),
+    #[cfg(unix)]
     IPC(IpcClientManager),
 }
 
+/// JSON-RPC interface to a single Portal network node.
+///
+/// The underlying transport is opened on first use and then shared by all clones
+/// of the api. If a request fails because the connection broke, the transport is
+/// reopened and the request is retried once.
 #[derive(Clone, Debug)]
 pub struct PortalApi {
     pub client_url: String,
+    config: TransportConfig,
+    transport: Arc<Mutex<Option<Transport>>>,
 }
 
 #[derive(Clone, Debug)]
@@ -55,14 +95,57 @@ pub struct PortalClient {
 }
 
 /// HTTP-based transport for connecting to a Portal network node.
+#[derive(Clone, Debug)]
 pub struct HttpClientManager {
     client: HttpClient,
 }
 
+/// WebSocket-based transport for connecting to a Portal network node.
+#[derive(Clone, Debug)]
+pub struct WsClientManager {
+    client: Arc<WsClient>,
+}
+
 /// IPC-based transport for connecting to a Portal network node.
+///
+/// Requests are written to the socket as they are made. A background task reads the
+/// responses and hands each one to the request with the matching id, so many requests
+/// may be in flight on one connection. Ids are unique for the lifetime of the connection.
+///
+/// Only available on Unix, where the portal clients serve IPC over a Unix domain socket.
+#[cfg(unix)]
+#[derive(Clone, Debug)]
 pub struct IpcClientManager {
-    stream: UnixStream,
-    request_id: u64,
+    writer: Arc<Mutex<OwnedWriteHalf>>,
+    pending: PendingRequests,
+    request_id: Arc<AtomicU64>,
+    request_timeout: Duration,
+}
+
+/// How long to wait for a response to an IPC request, matching the jsonrpsee default
+/// used for the other transports.
+#[cfg(unix)]
+const IPC_REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Requests awaiting a response, by request id. `None` once the connection has closed.
+#[cfg(unix)]
+type PendingRequests = Arc<std::sync::Mutex<Option<HashMap<u64, oneshot::Sender<Value>>>>>;
+
+/// Removes a request from [`PendingRequests`] when it stops waiting for a response,
+/// whether it was answered, timed out or abandoned by the caller.
+#[cfg(unix)]
+struct PendingGuard<'a> {
+    pending: &'a PendingRequests,
+    id: u64,
+}
+
+#[cfg(unix)]
+impl Drop for PendingGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(pending) = self.pending.lock().expect("poisoned IPC lock").as_mut() {
+            pending.remove(&self.id);
+        }
+    }
 }
 
 #[derive(Error, Debug)]
@@ -73,6 +156,13 @@ pub enum JsonRpcError {
     #[error("received empty response (EOF only)")]
     Empty,
 
+    #[error("connection to portal client closed")]
+    ConnectionClosed,
+
+    #[cfg(unix)]
+    #[error("no response from portal client within {0:?}")]
+    Timeout(Duration),
+
     #[error("HTTP client error")]
     HttpClient(#[from] jsonrpsee_core::Error),
 
@@ -114,12 +204,22 @@ pub enum JsonRpcError {
     #[error("malformed portal client URL")]
     ClientURL { url: String },
 
+    #[error("invalid header `{name}`, expected `name: value`")]
+    InvalidHeader { name: String },
+
+    /// Headers may carry credentials, so they are only sent over an encrypted connection.
+    #[error("refusing to send custom headers to {url} without TLS")]
+    InsecureHeaders { url: String },
+
     #[error("unable to use byte utils {0}")]
     ByteUtils(#[from] ByteUtilsError),
 
     #[error("unable to serialize/deserialize")]
     Serialization(#[from] serde_json::Error),
 
+    #[error("IPC portal client {path:?} is only supported on Unix")]
+    IpcUnsupported { path: PathBuf },
+
     #[error("could not open file {path:?}")]
     OpenFileFailed {
         source: std::io::Error,
@@ -127,9 +227,110 @@ pub enum JsonRpcError {
     },
 }
 
+impl JsonRpcError {
+    /// Whether the error indicates that the connection to the node is no longer usable.
+    ///
+    /// These errors are resolved by reconnecting, rather than by changing the request.
+    pub fn is_connection_failure(&self) -> bool {
+        match self {
+            JsonRpcError::Empty | JsonRpcError::ConnectionClosed | JsonRpcError::IO(_) => true,
+            // Reading from a closed stream surfaces as a deserialization error.
+            JsonRpcError::Serialization(e) => e.is_io() || e.is_eof(),
+            JsonRpcError::HttpClient(e) => matches!(
+                e,
+                jsonrpsee_core::Error::Transport(_) | jsonrpsee_core::Error::RestartNeeded(_)
+            ),
+            _ => false,
+        }
+    }
+}
+
+impl TransportConfig {
+    /// Creates a configuration from a portal client URL.
+    ///
+    /// Supported schemes are `http://`, `https://`, `ws://`, `wss://` and `ipc:///`.
+    /// Headers are only permitted for `https://` and `wss://`, and are not used for IPC.
+    pub fn from_url(client_url: &str, headers: RequestHeaders) -> Result<Self, JsonRpcError> {
+        if let Some(ipc_path) = client_url.strip_prefix("ipc:///") {
+            return Ok(TransportConfig::IPC(ipc_path.into()));
+        }
+        let url = Url::parse(client_url).map_err(|_| JsonRpcError::ClientURL {
+            url: client_url.to_string(),
+        })?;
+        match url.scheme() {
+            "http" | "ws" if !headers.is_empty() => Err(JsonRpcError::InsecureHeaders {
+                url: client_url.to_string(),
+            }),
+            "http" => Ok(TransportConfig::HTTP(url)),
+            "https" => Ok(TransportConfig::HTTPS(url, headers)),
+            "ws" | "wss" => Ok(TransportConfig::WS(url, headers)),
+            _ => Err(JsonRpcError::ClientURL {
+                url: client_url.to_string(),
+            }),
+        }
+    }
+}
+
+/// Displays the URL of the portal client, without any headers.
+impl fmt::Display for TransportConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransportConfig::HTTP(url)
+            | TransportConfig::HTTPS(url, _)
+            | TransportConfig::WS(url, _) => write!(f, "{url}"),
+            TransportConfig::IPC(path) => write!(f, "ipc:///{}", path.display()),
+        }
+    }
+}
+
+impl RequestHeaders {
+    pub fn insert(&mut self, name: &str, value: &str) -> Result<(), JsonRpcError> {
+        let invalid = || JsonRpcError::InvalidHeader {
+            name: name.to_string(),
+        };
+        let name = HeaderName::from_bytes(name.trim().as_bytes()).map_err(|_| invalid())?;
+        let value = HeaderValue::from_str(value.trim()).map_err(|_| invalid())?;
+        self.0.append(name, value);
+        Ok(())
+    }
+
+    /// Adds a header written as `name: value`, as accepted on the command line.
+    pub fn insert_parsed(&mut self, header: &str) -> Result<(), JsonRpcError> {
+        match header.split_once(':') {
+            Some((name, value)) => self.insert(name, value),
+            None => Err(JsonRpcError::InvalidHeader {
+                name: header.to_string(),
+            }),
+        }
+    }
+
+    /// Adds an `Authorization: Bearer <token>` header.
+    pub fn insert_bearer_token(&mut self, token: &str) -> Result<(), JsonRpcError> {
+        let mut value =
+            HeaderValue::from_str(&format!("Bearer {}", token.trim())).map_err(|_| {
+                JsonRpcError::InvalidHeader {
+                    name: AUTHORIZATION.to_string(),
+                }
+            })?;
+        value.set_sensitive(true);
+        self.0.append(AUTHORIZATION, value);
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Debug for RequestHeaders {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.0.keys()).finish()
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct JsonRPCResult {
-    id: u32,
+    id: u64,
     jsonrpc: String,
     result: Value,
 }
@@ -172,6 +373,51 @@ pub struct Content {
     pub raw: Vec<u8>,
 }
 
+/// A Portal sub-network, which selects the namespace of `portal_*` methods.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Subnetwork {
+    History,
+    State,
+    Beacon,
+}
+
+impl Subnetwork {
+    /// Returns the full method name, e.g., `portal_historyPing` for `Ping`.
+    fn method(self, name: &str) -> String {
+        let namespace = match self {
+            Subnetwork::History => "history",
+            Subnetwork::State => "state",
+            Subnetwork::Beacon => "beacon",
+        };
+        format!("portal_{namespace}{name}")
+    }
+}
+
+/// Response to a FindContent request sent to a single node.
+pub enum FindContentInfo {
+    /// The node returned the content.
+    Content(Content),
+    /// The node does not have the content and returned nodes closer to it.
+    Enrs(Vec<Enr>),
+    /// The node will send the content over uTP using this connection id.
+    ConnectionId(u16),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum FindContentInfoRaw {
+    #[serde(rename_all = "camelCase")]
+    ConnectionId {
+        connection_id: u16,
+    },
+    Content {
+        content: String,
+    },
+    Enrs {
+        enrs: Vec<Enr>,
+    },
+}
+
 /// Differentiates content absent responses from other responses.
 /// Portal network specs define content absent by an "0x" response, which otherwise
 /// is not readily convertible to the Response type.
@@ -204,6 +450,19 @@ impl PortalResponse {
             },
         }
     }
+    /// Deserializes a non-content response into its typed form.
+    fn parse<T: DeserializeOwned>(self) -> Result<T, JsonRpcError> {
+        match self {
+            PortalResponse::ContentAbsent => Err(JsonRpcError::SpecialMessageUnexpected),
+            PortalResponse::Regular(response) => {
+                serde_json::from_value(response.clone()).map_err(|e| JsonRpcError::InvalidJson {
+                    source: e,
+                    input: response.to_string(),
+                })
+            }
+        }
+    }
+
     /// Converts a non-content (e.g., node info) response JSON value to a string.
     ///
     /// A valid non-content response may be None, unlike content responses,
@@ -218,9 +477,12 @@ impl PortalResponse {
 
 impl PortalClient {
     pub async fn from(portal_client_url: String) -> Result<Self, JsonRpcError> {
-        let api = PortalApi {
-            client_url: portal_client_url.clone(),
-        };
+        let config = TransportConfig::from_url(&portal_client_url, RequestHeaders::default())?;
+        PortalClient::from_config(config).await
+    }
+
+    pub async fn from_config(config: TransportConfig) -> Result<Self, JsonRpcError> {
+        let api = PortalApi::from_config(config);
 
         let client_info = &api.get_client_version().await?;
 
@@ -240,62 +502,347 @@ impl PortalClient {
     }
 }
 
+impl Transport {
+    pub async fn connect(config: &TransportConfig) -> Result<Self, JsonRpcError> {
+        match config {
+            TransportConfig::HTTP(url) => Ok(Transport::HTTP(HttpClientManager {
+                client: HttpClientBuilder::default().build(url)?,
+            })),
+            TransportConfig::HTTPS(url, headers) => Ok(Transport::HTTP(HttpClientManager {
+                client: HttpClientBuilder::default()
+                    .set_headers(headers.0.clone())
+                    .build(url)?,
+            })),
+            TransportConfig::WS(url, headers) => Ok(Transport::WS(WsClientManager {
+                client: Arc::new(
+                    WsClientBuilder::default()
+                        .set_headers(headers.0.clone())
+                        .build(url)
+                        .await?,
+                ),
+            })),
+            #[cfg(unix)]
+            TransportConfig::IPC(path) => Ok(Transport::IPC(
+                IpcClientManager::connect(&path.to_string_lossy(), IPC_REQUEST_TIMEOUT).await?,
+            )),
+            #[cfg(not(unix))]
+            TransportConfig::IPC(path) => Err(JsonRpcError::IpcUnsupported { path: path.clone() }),
+        }
+    }
+}
+
+/// Sends a request using one of the jsonrpsee clients.
+async fn jsonrpsee_request<C: ClientT + Sync>(
+    client: &C,
+    method: &str,
+    params: Option<Vec<Box<RawValue>>>,
+) -> Result<PortalResponse, JsonRpcError> {
+    // jsonrpsee requires the conversion of `Option<Vec<Box<RawValue>>>` to `ArrayParams`
+    let array_params: ArrayParams = match params {
+        Some(json_params) => {
+            let mut param_aggregator = rpc_params!();
+            for json_param in json_params {
+                param_aggregator.insert(json_param).unwrap()
+            }
+            param_aggregator
+        }
+        None => rpc_params!(),
+    };
+    let val: Value = client.request(method, array_params).await?;
+    Ok(PortalResponse::from_value(val))
+}
+
+#[cfg(unix)]
+impl IpcClientManager {
+    async fn connect(ipc_path: &str, request_timeout: Duration) -> Result<Self, JsonRpcError> {
+        let stream =
+            UnixStream::connect(ipc_path)
+                .await
+                .map_err(|e| JsonRpcError::OpenFileFailed {
+                    source: e,
+                    path: ipc_path.into(),
+                })?;
+        let (reader, writer) = stream.into_split();
+        let pending: PendingRequests = Arc::new(std::sync::Mutex::new(Some(HashMap::new())));
+        tokio::spawn(read_ipc_responses(reader, pending.clone()));
+        Ok(IpcClientManager {
+            writer: Arc::new(Mutex::new(writer)),
+            pending,
+            request_id: Arc::new(AtomicU64::new(0)),
+            request_timeout,
+        })
+    }
+
+    async fn request(
+        &self,
+        method: &str,
+        params: Option<Vec<Box<RawValue>>>,
+    ) -> Result<PortalResponse, JsonRpcError> {
+        let id = self.request_id.fetch_add(1, Ordering::Relaxed);
+        let request = match &params {
+            Some(raw_params) => Request {
+                method,
+                params: raw_params,
+                id: serde_json::json!(id),
+                jsonrpc: Some("2.0"),
+            },
+            None => Request {
+                method,
+                params: &[],
+                id: serde_json::json!(id),
+                jsonrpc: Some("2.0"),
+            },
+        };
+        let data = serde_json::to_vec(&request)?;
+
+        // Register interest in the response before it can possibly arrive.
+        let (tx, rx) = oneshot::channel();
+        match self.pending.lock().expect("poisoned IPC lock").as_mut() {
+            Some(pending) => pending.insert(id, tx),
+            None => return Err(JsonRpcError::ConnectionClosed),
+        };
+        let _guard = PendingGuard {
+            pending: &self.pending,
+            id,
+        };
+
+        if let Err(e) = self.write(&data).await {
+            // The stream is unusable, so no request on it will be answered.
+            self.pending.lock().expect("poisoned IPC lock").take();
+            return Err(e.into());
+        }
+
+        // The sender is dropped without a response if the connection closes.
+        let response = tokio::time::timeout(self.request_timeout, rx)
+            .await
+            .map_err(|_| JsonRpcError::Timeout(self.request_timeout))?
+            .map_err(|_| JsonRpcError::ConnectionClosed)?;
+        let response: JsonRPCResult = serde_json::from_value(response)?;
+        Ok(PortalResponse::from_value(response.result))
+    }
+
+    async fn write(&self, data: &[u8]) -> std::io::Result<()> {
+        let mut writer = self.writer.lock().await;
+        writer.write_all(data).await?;
+        writer.flush().await
+    }
+
+    /// Whether the connection has closed, in which case no further requests can be made.
+    fn is_closed(&self) -> bool {
+        self.pending.lock().expect("poisoned IPC lock").is_none()
+    }
+}
+
+/// Reads JSON-RPC responses from an IPC stream and passes each to the request that is
+/// waiting for it.
+///
+/// When the stream ends or becomes unreadable, all waiting requests are failed.
+#[cfg(unix)]
+async fn read_ipc_responses(mut reader: OwnedReadHalf, pending: PendingRequests) {
+    let mut frames = JsonFrameBuffer::default();
+    let mut chunk = vec![0u8; 8192];
+    loop {
+        match frames.next_frame() {
+            Ok(Some(response)) => {
+                let Some(id) = response.get("id").and_then(Value::as_u64) else {
+                    warn!(response=?response, "Received IPC response without a request id.");
+                    continue;
+                };
+                let waiting = pending
+                    .lock()
+                    .expect("poisoned IPC lock")
+                    .as_mut()
+                    .and_then(|pending| pending.remove(&id));
+                match waiting {
+                    // The request may have been abandoned, in which case the response is unused.
+                    Some(tx) => {
+                        let _ = tx.send(response);
+                    }
+                    None => warn!(
+                        request.id = id,
+                        "Received IPC response for unknown request."
+                    ),
+                }
+                continue;
+            }
+            Ok(None) => {}
+            Err(e) => {
+                warn!(err=?e, "Received malformed IPC response, closing connection.");
+                break;
+            }
+        }
+        match reader.read(&mut chunk).await {
+            Ok(0) => {
+                debug!("IPC connection closed by portal client.");
+                break;
+            }
+            Ok(n) => frames.extend(&chunk[..n]),
+            Err(e) => {
+                warn!(err=?e, "Could not read from IPC connection.");
+                break;
+            }
+        }
+    }
+    // Dropping the senders fails every request still waiting on this connection.
+    pending.lock().expect("poisoned IPC lock").take();
+}
+
+/// Splits a stream of bytes into consecutive JSON values.
+///
+/// JSON-RPC over IPC has no delimiter between messages, so the end of each object or
+/// array is found by tracking nesting outside of strings. Bytes are only scanned once,
+/// however many reads a large value is split across.
+#[cfg(unix)]
+#[derive(Default)]
+struct JsonFrameBuffer {
+    buffer: Vec<u8>,
+    /// Bytes at the start of the buffer already scanned for the end of the next value.
+    scanned: usize,
+    /// Objects and arrays open at the end of the scanned bytes.
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+}
+
+#[cfg(unix)]
+impl JsonFrameBuffer {
+    fn extend(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// Removes and returns the next complete value, or `None` if more bytes are required.
+    fn next_frame(&mut self) -> Result<Option<Value>, serde_json::Error> {
+        while self.scanned < self.buffer.len() {
+            let byte = self.buffer[self.scanned];
+            self.scanned += 1;
+            if self.in_string {
+                match byte {
+                    _ if self.escaped => self.escaped = false,
+                    b'\\' => self.escaped = true,
+                    b'"' => self.in_string = false,
+                    _ => {}
+                }
+                continue;
+            }
+            match byte {
+                b'{' | b'[' => self.depth += 1,
+                _ if byte.is_ascii_whitespace() => {}
+                // JSON-RPC messages are objects, or arrays for batches.
+                _ if self.depth == 0 => {
+                    return Err(<serde_json::Error as serde::de::Error>::custom(
+                        "expected a JSON object or array",
+                    ))
+                }
+                b'"' => self.in_string = true,
+                b'}' | b']' => {
+                    self.depth -= 1;
+                    if self.depth == 0 {
+                        let frame: Vec<u8> = self.buffer.drain(..self.scanned).collect();
+                        self.scanned = 0;
+                        return serde_json::from_slice(&frame).map(Some);
+                    }
+                }
+                _ => {}
+            }
+        }
+        // Only whitespace remains.
+        if self.depth == 0 {
+            self.buffer.clear();
+            self.scanned = 0;
+        }
+        Ok(None)
+    }
+}
+
 impl PortalApi {
+    /// Creates an api for the node at the given url. No connection is made until the first request.
+    pub fn new(client_url: String) -> Result<Self, JsonRpcError> {
+        let config = TransportConfig::from_url(&client_url, RequestHeaders::default())?;
+        Ok(PortalApi::from_config(config))
+    }
+
+    /// Creates an api for the configured node. No connection is made until the first request.
+    pub fn from_config(config: TransportConfig) -> Self {
+        PortalApi {
+            client_url: config.to_string(),
+            config,
+            transport: Arc::new(Mutex::new(None)),
+        }
+    }
+
     pub async fn make_request(
         &self,
         method: &str,
         params: Option<Vec<Box<RawValue>>>,
     ) -> Result<PortalResponse, JsonRpcError> {
-        let transport = PortalApi::parse_client_url(self.client_url.clone())?;
-        match transport {
+        let result = match self.send(method, params.clone()).await {
+            Err(e) if e.is_connection_failure() => {
+                warn!(
+                    client.url = self.client_url,
+                    err=?e,
+                    "Connection to portal client failed, reconnecting."
+                );
+                self.reset_transport().await;
+                self.send(method, params).await
+            }
+            result => result,
+        };
+        if result.is_err() {
+            RPC_ERRORS.with_label_values(&[method]).inc();
+        }
+        result
+    }
+
+    /// Sends a request over the shared transport, connecting first if required.
+    async fn send(
+        &self,
+        method: &str,
+        params: Option<Vec<Box<RawValue>>>,
+    ) -> Result<PortalResponse, JsonRpcError> {
+        let mut guard = self.transport.lock().await;
+        let transport = match guard.take() {
+            Some(transport) => guard.insert(transport),
+            None => {
+                debug!(client.url = self.client_url, "Connecting to portal client.");
+                guard.insert(Transport::connect(&self.config).await?)
+            }
+        };
+        // All transports may be used concurrently, so the lock is released before sending.
+        match transport.clone() {
             Transport::HTTP(http) => {
-                // jsonrpsee requires the conversion of `Option<Vec<Box<RawValue>>>` to `ArrayParams`
-                let array_params: ArrayParams = match params {
-                    Some(json_params) => {
-                        let mut param_aggregator = rpc_params!();
-                        for json_param in json_params {
-                            param_aggregator.insert(json_param).unwrap()
-                        }
-                        param_aggregator
-                    }
-                    None => rpc_params!(),
-                };
-                let val: Value = http.client.request(method, array_params).await?;
-                Ok(PortalResponse::from_value(val))
+                drop(guard);
+                jsonrpsee_request(&http.client, method, params).await
             }
-            Transport::IPC(mut ipc) => {
-                let request = match &params {
-                    Some(raw_params) => Request {
-                        method,
-                        params: raw_params,
-                        id: serde_json::json!(ipc.request_id),
-                        jsonrpc: Some("2.0"),
-                    },
-                    None => Request {
-                        method,
-                        params: &[],
-                        id: serde_json::json!(ipc.request_id),
-                        jsonrpc: Some("2.0"),
-                    },
-                };
-                // Manually increment the request id after using it in the request.
-                ipc.request_id += 1;
-
-                let data = serde_json::to_vec(&request)?;
-                ipc.stream.write_all(&data)?;
-                ipc.stream.flush()?;
-
-                let response: JsonRPCResult =
-                    serde_json::Deserializer::from_reader(ipc.stream.try_clone()?)
-                        .into_iter()
-                        .next()
-                        // Empty response should only happen when they immediately send EOF
-                        .ok_or(JsonRpcError::Empty)??;
-                Ok(PortalResponse::from_value(response.result))
+            Transport::WS(ws) => {
+                drop(guard);
+                jsonrpsee_request(ws.client.as_ref(), method, params).await
+            }
+            #[cfg(unix)]
+            Transport::IPC(ipc) => {
+                drop(guard);
+                ipc.request(method, params).await
             }
         }
     }
 
+    /// Discards the shared transport so that the next request opens a new connection.
+    ///
+    /// Another request may have already replaced a failed IPC connection, in which case
+    /// the replacement is kept.
+    async fn reset_transport(&self) {
+        let mut guard = self.transport.lock().await;
+        let replace = match guard.as_ref() {
+            #[cfg(unix)]
+            Some(Transport::IPC(ipc)) => ipc.is_closed(),
+            Some(Transport::WS(ws)) => !ws.client.is_connected(),
+            Some(Transport::HTTP(_)) => true,
+            None => false,
+        };
+        if replace {
+            guard.take();
+        }
+    }
+
     pub async fn get_client_version(&self) -> Result<String, JsonRpcError> {
         let method = "web3_clientVersion";
         let params = None;
@@ -320,29 +867,135 @@ impl PortalApi {
     pub async fn get_routing_table_info(self) -> Result<RoutingTableInfo, JsonRpcError> {
         let method = "discv5_routingTableInfo";
         let params = None;
-        let response = self
-            .make_request(method, params)
+        let response = self.make_request(method, params).await?;
+        parse_routing_table_info(response.parse()?)
+    }
+
+    /// Looks up the nodes closest to the target node id in the history network.
+    pub async fn recursive_find_nodes(&self, node_id: H256) -> Result<Vec<Enr>, JsonRpcError> {
+        let params = Some(vec![to_raw_value(&node_id)?]);
+        self.make_request("portal_historyRecursiveFindNodes", params)
             .await?
-            .non_content_response_to_string()?;
-        let result_raw: RoutingTableInfoRaw =
-            serde_json::from_str(&response).map_err(|e| JsonRpcError::InvalidJson {
-                source: e,
-                input: response.to_string(),
-            })?;
-        let local_node_id =
-            H256::from_str(&result_raw.localKey).map_err(|e| JsonRpcError::InvalidHash {
-                source: e,
-                input: result_raw.localKey.to_string(),
-            })?;
-        let buckets: Result<Vec<RoutingTableEntry>, JsonRpcError> = result_raw
-            .buckets
-            .iter()
-            .map(|entry| parse_routing_table_entry(&local_node_id, &entry.0, &entry.1, &entry.2))
-            .collect();
-        Ok(RoutingTableInfo {
-            localKey: local_node_id,
-            buckets: buckets?,
-        })
+            .parse()
+    }
+
+    /// Sends a PING to the given node.
+    pub async fn ping(&self, network: Subnetwork, enr: &Enr) -> Result<PongInfo, JsonRpcError> {
+        let params = Some(vec![to_raw_value(&enr.to_base64())?]);
+        self.make_request(&network.method("Ping"), params)
+            .await?
+            .parse()
+    }
+
+    /// Sends a FINDNODES to the given node, for nodes at the given log distances from it.
+    pub async fn find_nodes(
+        &self,
+        network: Subnetwork,
+        enr: &Enr,
+        distances: &[u16],
+    ) -> Result<FindNodesInfo, JsonRpcError> {
+        let params = Some(vec![
+            to_raw_value(&enr.to_base64())?,
+            to_raw_value(distances)?,
+        ]);
+        self.make_request(&network.method("FindNodes"), params)
+            .await?
+            .parse()
+    }
+
+    /// Sends a FINDCONTENT to the given node only, without following the returned nodes.
+    pub async fn find_content<T: OverlayContentKey>(
+        &self,
+        network: Subnetwork,
+        enr: &Enr,
+        content_key: &T,
+    ) -> Result<FindContentInfo, JsonRpcError> {
+        let params = Some(vec![
+            to_raw_value(&enr.to_base64())?,
+            to_raw_value(&hex_encode(content_key.to_bytes()))?,
+        ]);
+        let response: FindContentInfoRaw = self
+            .make_request(&network.method("FindContent"), params)
+            .await?
+            .parse()?;
+        match response {
+            FindContentInfoRaw::ConnectionId { connection_id } => {
+                Ok(FindContentInfo::ConnectionId(connection_id))
+            }
+            FindContentInfoRaw::Content { content } => Ok(FindContentInfo::Content(Content {
+                raw: hex_decode(&content)?,
+            })),
+            FindContentInfoRaw::Enrs { enrs } => Ok(FindContentInfo::Enrs(enrs)),
+        }
+    }
+
+    /// Sends an OFFER of locally stored content to the given node.
+    pub async fn offer<T: OverlayContentKey>(
+        &self,
+        network: Subnetwork,
+        enr: &Enr,
+        content_key: &T,
+    ) -> Result<AcceptInfo, JsonRpcError> {
+        let params = Some(vec![
+            to_raw_value(&enr.to_base64())?,
+            to_raw_value(&hex_encode(content_key.to_bytes()))?,
+        ]);
+        self.make_request(&network.method("Offer"), params)
+            .await?
+            .parse()
+    }
+
+    /// Stores content in the node's local database. Returns whether it was stored.
+    pub async fn store<T: OverlayContentKey>(
+        &self,
+        network: Subnetwork,
+        content_key: &T,
+        content: &Content,
+    ) -> Result<bool, JsonRpcError> {
+        let params = Some(vec![
+            to_raw_value(&hex_encode(content_key.to_bytes()))?,
+            to_raw_value(&hex_encode(&content.raw))?,
+        ]);
+        self.make_request(&network.method("Store"), params)
+            .await?
+            .parse()
+    }
+
+    /// Reads content from the node's local database, without any network requests.
+    pub async fn local_content<T: OverlayContentKey>(
+        &self,
+        network: Subnetwork,
+        content_key: &T,
+    ) -> Result<Option<Content>, JsonRpcError> {
+        let params = Some(vec![to_raw_value(&hex_encode(content_key.to_bytes()))?]);
+        match self
+            .make_request(&network.method("LocalContent"), params)
+            .await?
+            .content_response_to_string()?
+        {
+            Some(response) => Ok(Some(Content {
+                raw: hex_decode(&response)?,
+            })),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the data radius of the node.
+    pub async fn radius(&self, network: Subnetwork) -> Result<DataRadius, JsonRpcError> {
+        self.make_request(&network.method("Radius"), None)
+            .await?
+            .parse()
+    }
+
+    /// Returns the routing table of the node for the sub-network.
+    pub async fn routing_table_info(
+        &self,
+        network: Subnetwork,
+    ) -> Result<RoutingTableInfo, JsonRpcError> {
+        let response = self
+            .make_request(&network.method("RoutingTableInfo"), None)
+            .await?;
+        parse_routing_table_info(response.parse()?)
     }
 
     pub async fn get_content<T: OverlayContentKey>(
@@ -387,28 +1040,25 @@ impl PortalApi {
             Ok((None, trace))
         }
     }
+}
 
-    pub fn parse_client_url(client_url: String) -> Result<Transport, JsonRpcError> {
-        let http_prefix = "http://";
-        let ipc_prefix = "ipc:///";
-        if client_url.strip_prefix(http_prefix).is_some() {
-            Ok(Transport::HTTP(HttpClientManager {
-                client: HttpClientBuilder::default().build(client_url)?,
-            }))
-        } else if let Some(ipc_path) = client_url.strip_prefix(ipc_prefix) {
-            Ok(Transport::IPC(IpcClientManager {
-                stream: UnixStream::connect(ipc_path).map_err(|e| {
-                    JsonRpcError::OpenFileFailed {
-                        source: e,
-                        path: ipc_path.into(),
-                    }
-                })?,
-                request_id: 0,
-            }))
-        } else {
-            Err(JsonRpcError::ClientURL { url: client_url })
-        }
-    }
+fn parse_routing_table_info(
+    result_raw: RoutingTableInfoRaw,
+) -> Result<RoutingTableInfo, JsonRpcError> {
+    let local_node_id =
+        H256::from_str(&result_raw.localKey).map_err(|e| JsonRpcError::InvalidHash {
+            source: e,
+            input: result_raw.localKey.to_string(),
+        })?;
+    let buckets: Result<Vec<RoutingTableEntry>, JsonRpcError> = result_raw
+        .buckets
+        .iter()
+        .map(|entry| parse_routing_table_entry(&local_node_id, &entry.0, &entry.1, &entry.2))
+        .collect();
+    Ok(RoutingTableInfo {
+        localKey: local_node_id,
+        buckets: buckets?,
+    })
 }
 
 fn parse_routing_table_entry(
@@ -452,3 +1102,504 @@ fn distance_log2(distance: U256) -> Result<u16, JsonRpcError> {
         Ok((256 - distance.leading_zeros()).try_into()?)
     }
 }
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::io::Write;
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::thread;
+
+    use ethportal_api::types::content_key::HistoryContentKey;
+
+    use super::*;
+
+    fn socket_path(test_name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("glados-{test_name}-{}.ipc", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    /// Reads `count` requests and returns their ids.
+    fn read_request_ids(stream: &UnixStream, count: usize) -> Vec<Value> {
+        serde_json::Deserializer::from_reader(stream)
+            .into_iter::<Value>()
+            .take(count)
+            .map(|request| request.unwrap()["id"].clone())
+            .collect()
+    }
+
+    fn respond_with_id(mut stream: &UnixStream, id: Value) {
+        let response = json!({"jsonrpc": "2.0", "id": id, "result": id});
+        stream.write_all(response.to_string().as_bytes()).unwrap();
+    }
+
+    /// Answers each request with its own id as the result. Each connection is closed
+    /// after serving the number of requests given for it.
+    fn serve_ids(listener: UnixListener, requests_per_connection: &[usize]) {
+        for request_count in requests_per_connection {
+            let (stream, _) = listener.accept().unwrap();
+            let requests = serde_json::Deserializer::from_reader(&stream).into_iter::<Value>();
+            for request in requests.take(*request_count) {
+                respond_with_id(&stream, request.unwrap()["id"].clone());
+            }
+        }
+    }
+
+    fn response_id(response: PortalResponse) -> Value {
+        match response {
+            PortalResponse::Regular(value) => value,
+            PortalResponse::ContentAbsent => panic!("unexpected content absent response"),
+        }
+    }
+
+    /// Tests that an IPC connection is reused between requests, and reopened once closed.
+    #[tokio::test]
+    async fn test_ipc_connection_reuse_and_reconnect() {
+        let path = socket_path("reconnect");
+        let listener = UnixListener::bind(&path).unwrap();
+        let server = thread::spawn(move || serve_ids(listener, &[2, 1]));
+
+        let api = PortalApi::new(format!("ipc:///{}", path.display())).unwrap();
+        let first = api.make_request("web3_clientVersion", None).await.unwrap();
+        let second = api
+            .clone()
+            .make_request("web3_clientVersion", None)
+            .await
+            .unwrap();
+        // Request ids continue on the same connection.
+        assert_eq!(response_id(first), json!(0));
+        assert_eq!(response_id(second), json!(1));
+
+        // The server has closed the first connection, so the api reconnects.
+        let third = api.make_request("web3_clientVersion", None).await.unwrap();
+        assert_eq!(response_id(third), json!(0));
+
+        server.join().unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    /// Tests that failed requests are counted by method.
+    #[tokio::test]
+    async fn test_rpc_errors_counted() {
+        let path = socket_path("no-listener");
+        let api = PortalApi::new(format!("ipc:///{}", path.display())).unwrap();
+        let method = "glados_testUnreachable";
+        assert_eq!(RPC_ERRORS.with_label_values(&[method]).get(), 0);
+        assert!(api.make_request(method, None).await.is_err());
+        assert!(api.make_request(method, None).await.is_err());
+        assert_eq!(RPC_ERRORS.with_label_values(&[method]).get(), 2);
+
+        let text = crate::metrics::encode(&prometheus::gather()).unwrap();
+        assert!(text.contains(r#"glados_rpc_errors_total{method="glados_testUnreachable"} 2"#));
+    }
+
+    /// Tests that concurrent IPC requests each receive their own response, even when the
+    /// responses arrive in a different order to the requests.
+    #[tokio::test]
+    async fn test_ipc_concurrent_requests_out_of_order() {
+        let path = socket_path("out-of-order");
+        let listener = UnixListener::bind(&path).unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let ids = read_request_ids(&stream, 3);
+            for id in ids.into_iter().rev() {
+                respond_with_id(&stream, id);
+            }
+        });
+
+        let api = PortalApi::new(format!("ipc:///{}", path.display())).unwrap();
+        let (first, second, third) = tokio::join!(
+            api.make_request("web3_clientVersion", None),
+            api.make_request("web3_clientVersion", None),
+            api.make_request("web3_clientVersion", None),
+        );
+        let mut ids: Vec<u64> = [first, second, third]
+            .into_iter()
+            .map(|response| response_id(response.unwrap()).as_u64().unwrap())
+            .collect();
+        ids.sort_unstable();
+        assert_eq!(ids, vec![0, 1, 2]);
+
+        server.join().unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    /// Tests that an unanswered IPC request times out, and that requests which stop
+    /// waiting are forgotten whether they time out or are dropped by the caller.
+    #[tokio::test]
+    async fn test_ipc_unanswered_requests_forgotten() {
+        let path = socket_path("unanswered");
+        let listener = UnixListener::bind(&path).unwrap();
+        let (done_tx, done_rx) = std::sync::mpsc::channel::<()>();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            read_request_ids(&stream, 2);
+            // Hold the connection open without answering.
+            done_rx.recv().unwrap();
+        });
+
+        let timeout = Duration::from_millis(50);
+        let ipc = IpcClientManager::connect(&path.to_string_lossy(), timeout)
+            .await
+            .unwrap();
+        let pending_count = || ipc.pending.lock().unwrap().as_ref().unwrap().len();
+
+        let result = ipc.request("web3_clientVersion", None).await;
+        assert!(matches!(result, Err(JsonRpcError::Timeout(t)) if t == timeout));
+        assert_eq!(pending_count(), 0);
+
+        // The caller gives up before the request does.
+        let abandoned =
+            tokio::time::timeout(timeout / 5, ipc.request("web3_clientVersion", None)).await;
+        assert!(abandoned.is_err());
+        assert_eq!(pending_count(), 0);
+
+        done_tx.send(()).unwrap();
+        server.join().unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    /// Tests that a recursive node lookup can be made over IPC.
+    #[tokio::test]
+    async fn test_ipc_recursive_find_nodes() {
+        const ENR: &str = "enr:-I24QDy_atpK3KlPjl6X5yIrK7FosdHI1cW0I0MeiaIVuYg3AEEH9tRSTyFb2k6lpUiFsqxt8uTW3jVMUzoSlQf5OXYBY4d0IDAuMS4wgmlkgnY0gmlwhKEjVaWJc2VjcDI1NmsxoQOSGugH1jSdiE_fRK1FIBe9oLxaWH8D_7xXSnaOVBe-SYN1ZHCCIyg";
+        let path = socket_path("find-nodes");
+        let listener = UnixListener::bind(&path).unwrap();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let request: Value = serde_json::Deserializer::from_reader(&stream)
+                .into_iter()
+                .next()
+                .unwrap()
+                .unwrap();
+            assert_eq!(request["method"], "portal_historyRecursiveFindNodes");
+            assert_eq!(request["params"], json!([format!("0x{}", "11".repeat(32))]));
+            let response = json!({"jsonrpc": "2.0", "id": request["id"], "result": [ENR]});
+            stream.write_all(response.to_string().as_bytes()).unwrap();
+        });
+
+        let api = PortalApi::new(format!("ipc:///{}", path.display())).unwrap();
+        let enrs = api
+            .recursive_find_nodes(H256::repeat_byte(0x11))
+            .await
+            .unwrap();
+        assert_eq!(enrs, vec![Enr::from_str(ENR).unwrap()]);
+
+        server.join().unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    /// Tests that values split across reads, or sharing a read, are framed correctly.
+    #[test]
+    fn test_json_frame_buffer() {
+        let mut frames = JsonFrameBuffer::default();
+        frames.extend(br#"{"id": 0, "result": "ab"#);
+        assert_eq!(frames.next_frame().unwrap(), None);
+
+        frames.extend(br#"c"}  {"id": 1, "result": "0x"}{"id""#);
+        assert_eq!(
+            frames.next_frame().unwrap(),
+            Some(json!({"id": 0, "result": "abc"}))
+        );
+        assert_eq!(
+            frames.next_frame().unwrap(),
+            Some(json!({"id": 1, "result": "0x"}))
+        );
+        assert_eq!(frames.next_frame().unwrap(), None);
+
+        frames.extend(br#": 2}"#);
+        assert_eq!(frames.next_frame().unwrap(), Some(json!({"id": 2})));
+        assert_eq!(frames.next_frame().unwrap(), None);
+
+        // Brackets and escaped quotes inside strings do not end a value.
+        frames.extend(br#"{"id": 3, "result": ["}\"{", "\\"#);
+        assert_eq!(frames.next_frame().unwrap(), None);
+        frames.extend(br#""]}"#);
+        assert_eq!(
+            frames.next_frame().unwrap(),
+            Some(json!({"id": 3, "result": ["}\"{", "\\"]}))
+        );
+        assert_eq!(frames.next_frame().unwrap(), None);
+
+        frames.extend(b"not json");
+        assert!(frames.next_frame().is_err());
+    }
+
+    /// Tests the transport selected for each supported URL scheme.
+    #[test]
+    fn test_transport_config_from_url() {
+        let config = TransportConfig::from_url("http://127.0.0.1:8545", RequestHeaders::default());
+        assert!(matches!(config, Ok(TransportConfig::HTTP(_))));
+
+        let mut headers = RequestHeaders::default();
+        headers.insert_bearer_token("secret").unwrap();
+        headers.insert_parsed("X-Api-Key: abc").unwrap();
+        let config = TransportConfig::from_url("https://portal.example.com", headers.clone());
+        assert!(matches!(config, Ok(TransportConfig::HTTPS(_, _))));
+        let config = TransportConfig::from_url("wss://portal.example.com", headers.clone());
+        assert!(matches!(config, Ok(TransportConfig::WS(_, _))));
+
+        let config = TransportConfig::from_url("ipc:////tmp/trin-jsonrpc.ipc", headers.clone());
+        match config {
+            Ok(TransportConfig::IPC(path)) => {
+                assert_eq!(path, PathBuf::from("/tmp/trin-jsonrpc.ipc"))
+            }
+            other => panic!("unexpected config: {other:?}"),
+        }
+
+        assert!(matches!(
+            TransportConfig::from_url("http://portal.example.com", headers.clone()),
+            Err(JsonRpcError::InsecureHeaders { .. })
+        ));
+        assert!(matches!(
+            TransportConfig::from_url("ws://portal.example.com", headers),
+            Err(JsonRpcError::InsecureHeaders { .. })
+        ));
+        assert!(matches!(
+            TransportConfig::from_url("ftp://portal.example.com", RequestHeaders::default()),
+            Err(JsonRpcError::ClientURL { .. })
+        ));
+    }
+
+    /// Tests that header values, which may hold credentials, are never displayed.
+    #[test]
+    fn test_request_headers_not_displayed() {
+        let mut headers = RequestHeaders::default();
+        headers.insert_bearer_token("secret").unwrap();
+        assert!(headers.insert_parsed("no separator").is_err());
+
+        let config = TransportConfig::from_url("https://portal.example.com/rpc", headers).unwrap();
+        assert_eq!(config.to_string(), "https://portal.example.com/rpc");
+        assert!(!format!("{config:?}").contains("secret"));
+        assert_eq!(
+            PortalApi::from_config(config).client_url,
+            "https://portal.example.com/rpc"
+        );
+    }
+
+    /// A recorded request and response, served to a `PortalApi` over IPC.
+    struct Replay {
+        api: PortalApi,
+        server: thread::JoinHandle<()>,
+        path: PathBuf,
+    }
+
+    impl Replay {
+        /// Serves the fixture, checking that the request matches the recorded one.
+        fn start(test_name: &str, fixture: &str) -> Self {
+            let fixture: Value = serde_json::from_str(fixture).unwrap();
+            let path = socket_path(test_name);
+            let listener = UnixListener::bind(&path).unwrap();
+            let server = thread::spawn(move || {
+                let (mut stream, _) = listener.accept().unwrap();
+                let request: Value = serde_json::Deserializer::from_reader(&stream)
+                    .into_iter()
+                    .next()
+                    .unwrap()
+                    .unwrap();
+                assert_eq!(request["method"], fixture["request"]["method"]);
+                assert_eq!(request["params"], fixture["request"]["params"]);
+                let mut response = fixture["response"].clone();
+                response["id"] = request["id"].clone();
+                stream.write_all(response.to_string().as_bytes()).unwrap();
+            });
+            let api = PortalApi::new(format!("ipc:///{}", path.display())).unwrap();
+            Replay { api, server, path }
+        }
+
+        fn finish(self) {
+            self.server.join().unwrap();
+            std::fs::remove_file(&self.path).unwrap();
+        }
+    }
+
+    const ENR: &str = "enr:-I24QDy_atpK3KlPjl6X5yIrK7FosdHI1cW0I0MeiaIVuYg3AEEH9tRSTyFb2k6lpUiFsqxt8uTW3jVMUzoSlQf5OXYBY4d0IDAuMS4wgmlkgnY0gmlwhKEjVaWJc2VjcDI1NmsxoQOSGugH1jSdiE_fRK1FIBe9oLxaWH8D_7xXSnaOVBe-SYN1ZHCCIyg";
+    const OTHER_ENR: &str = "enr:-I24QIdQtNSyUNcoyR4R7pWLfGj0YuX550Qld0HuInYo_b7JE9CIzmi2TF9hPg-OFL3kebYgLjnPkRu17niXB6xKQugBY4d0IDAuMS4wgmlkgnY0gmlwhJO2oc6Jc2VjcDI1NmsxoQJal-rNlNBoOMikJ7PcGk1h6Mlt_XtTWihHwOKmFVE-GoN1ZHCCIyg";
+    const CONTENT_KEY: &str =
+        "0x00720704f3aa11c53cf344ea069db95cecb81ad7453c8f276b2a1062979611f09c";
+    const CONTENT: &str = "0x080000001c020000f90211a0d4e56740f876aef8c010b86a40d5f56745a118d0906a34e69aec8c0db1cb8fa3";
+
+    fn enr() -> Enr {
+        Enr::from_str(ENR).unwrap()
+    }
+
+    fn content_key() -> HistoryContentKey {
+        HistoryContentKey::from(hex_decode(CONTENT_KEY).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_ping() {
+        let replay = Replay::start(
+            "ping",
+            include_str!("../tests/fixtures/portal_historyPing.json"),
+        );
+        let pong = replay.api.ping(Subnetwork::History, &enr()).await.unwrap();
+        assert_eq!(pong.enr_seq, 1);
+        assert_eq!(pong.data_radius, DataRadius::MAX);
+        replay.finish();
+
+        let replay = Replay::start(
+            "state-ping",
+            include_str!("../tests/fixtures/portal_statePing.json"),
+        );
+        let pong = replay.api.ping(Subnetwork::State, &enr()).await.unwrap();
+        assert_eq!(pong.data_radius, DataRadius::MAX >> 1);
+        replay.finish();
+    }
+
+    #[tokio::test]
+    async fn test_find_nodes() {
+        let replay = Replay::start(
+            "find-nodes-single",
+            include_str!("../tests/fixtures/portal_historyFindNodes.json"),
+        );
+        let nodes = replay
+            .api
+            .find_nodes(Subnetwork::History, &enr(), &[256])
+            .await
+            .unwrap();
+        assert_eq!(nodes.total, 1);
+        assert_eq!(nodes.enrs, vec![Enr::from_str(OTHER_ENR).unwrap()]);
+        replay.finish();
+    }
+
+    #[tokio::test]
+    async fn test_find_content() {
+        let replay = Replay::start(
+            "find-content",
+            include_str!("../tests/fixtures/portal_historyFindContent_content.json"),
+        );
+        let response = replay
+            .api
+            .find_content(Subnetwork::History, &enr(), &content_key())
+            .await
+            .unwrap();
+        match response {
+            FindContentInfo::Content(content) => {
+                assert_eq!(content.raw, hex_decode(CONTENT).unwrap())
+            }
+            _ => panic!("expected content"),
+        }
+        replay.finish();
+
+        let replay = Replay::start(
+            "find-content-enrs",
+            include_str!("../tests/fixtures/portal_historyFindContent_enrs.json"),
+        );
+        let response = replay
+            .api
+            .find_content(Subnetwork::History, &enr(), &content_key())
+            .await
+            .unwrap();
+        match response {
+            FindContentInfo::Enrs(enrs) => {
+                assert_eq!(enrs, vec![Enr::from_str(OTHER_ENR).unwrap()])
+            }
+            _ => panic!("expected enrs"),
+        }
+        replay.finish();
+    }
+
+    #[tokio::test]
+    async fn test_offer() {
+        let replay = Replay::start(
+            "offer",
+            include_str!("../tests/fixtures/portal_historyOffer.json"),
+        );
+        let accept = replay
+            .api
+            .offer(Subnetwork::History, &enr(), &content_key())
+            .await
+            .unwrap();
+        assert_eq!(accept.content_keys.len(), 1);
+        assert!(accept.content_keys.get(0).unwrap());
+        replay.finish();
+    }
+
+    #[tokio::test]
+    async fn test_store() {
+        let replay = Replay::start(
+            "store",
+            include_str!("../tests/fixtures/portal_historyStore.json"),
+        );
+        let content = Content {
+            raw: hex_decode(CONTENT).unwrap(),
+        };
+        let stored = replay
+            .api
+            .store(Subnetwork::History, &content_key(), &content)
+            .await
+            .unwrap();
+        assert!(stored);
+        replay.finish();
+    }
+
+    #[tokio::test]
+    async fn test_local_content() {
+        let replay = Replay::start(
+            "local-content",
+            include_str!("../tests/fixtures/portal_historyLocalContent.json"),
+        );
+        let content = replay
+            .api
+            .local_content(Subnetwork::History, &content_key())
+            .await
+            .unwrap();
+        assert_eq!(content.unwrap().raw, hex_decode(CONTENT).unwrap());
+        replay.finish();
+
+        let replay = Replay::start(
+            "state-local-content",
+            include_str!("../tests/fixtures/portal_stateLocalContent.json"),
+        );
+        let content = replay
+            .api
+            .local_content(Subnetwork::State, &content_key())
+            .await
+            .unwrap();
+        assert!(content.is_none());
+        replay.finish();
+    }
+
+    #[tokio::test]
+    async fn test_radius() {
+        let replay = Replay::start(
+            "radius",
+            include_str!("../tests/fixtures/portal_historyRadius.json"),
+        );
+        let radius = replay.api.radius(Subnetwork::History).await.unwrap();
+        assert_eq!(radius, DataRadius::MAX);
+        replay.finish();
+
+        let replay = Replay::start(
+            "beacon-radius",
+            include_str!("../tests/fixtures/portal_beaconRadius.json"),
+        );
+        let radius = replay.api.radius(Subnetwork::Beacon).await.unwrap();
+        assert_eq!(radius, DataRadius::MAX >> 16);
+        replay.finish();
+    }
+
+    #[tokio::test]
+    async fn test_routing_table_info() {
+        let replay = Replay::start(
+            "routing-table",
+            include_str!("../tests/fixtures/portal_historyRoutingTableInfo.json"),
+        );
+        let routing_table = replay
+            .api
+            .routing_table_info(Subnetwork::History)
+            .await
+            .unwrap();
+        assert_eq!(routing_table.localKey, H256::from(enr().node_id().raw()));
+        let other_enr = Enr::from_str(OTHER_ENR).unwrap();
+        assert_eq!(routing_table.buckets.len(), 1);
+        let entry = &routing_table.buckets[0];
+        assert_eq!(entry.node_id, H256::from(other_enr.node_id().raw()));
+        assert_eq!(entry.enr, other_enr);
+        assert_eq!(entry.status, "Connected");
+        assert_eq!(entry.log_distance, 256);
+        replay.finish();
+    }
+}
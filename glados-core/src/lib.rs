@@ -1 +1,2 @@
 pub mod jsonrpc;
+pub mod metrics;
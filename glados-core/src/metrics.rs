@@ -0,0 +1,51 @@
+use std::net::SocketAddr;
+
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use lazy_static::lazy_static;
+use prometheus::{
+    proto::MetricFamily, register_int_counter_vec, Encoder, IntCounterVec, TextEncoder, TEXT_FORMAT,
+};
+use tracing::{error, info};
+
+lazy_static! {
+    /// Requests that returned an error, by JSON-RPC method.
+    pub static ref RPC_ERRORS: IntCounterVec = register_int_counter_vec!(
+        "glados_rpc_errors_total",
+        "Requests to a portal or execution client that returned an error.",
+        &["method"]
+    )
+    .expect("RPC error metric can be registered");
+}
+
+/// Serves the metrics registered by this process at `/metrics` until the server fails.
+pub async fn serve(address: SocketAddr) -> anyhow::Result<()> {
+    let app = Router::new().route("/metrics", get(|| async { render(&prometheus::gather()) }));
+    info!(%address, "Serving metrics.");
+    axum::Server::try_bind(&address)?
+        .serve(app.into_make_service())
+        .await?;
+    Ok(())
+}
+
+/// Responds with the metric families in the Prometheus text format.
+pub fn render(metric_families: &[MetricFamily]) -> Response {
+    match encode(metric_families) {
+        Ok(text) => ([(header::CONTENT_TYPE, TEXT_FORMAT)], text).into_response(),
+        Err(e) => {
+            error!(err=?e, "Could not encode metrics.");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Encodes the metric families in the Prometheus text format.
+pub fn encode(metric_families: &[MetricFamily]) -> prometheus::Result<String> {
+    let mut buffer = vec![];
+    TextEncoder::new().encode(metric_families, &mut buffer)?;
+    String::from_utf8(buffer).map_err(|e| prometheus::Error::Msg(e.to_string()))
+}
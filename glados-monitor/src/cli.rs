@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::{net::SocketAddr, path::PathBuf};
 
 use clap::{Parser, Subcommand};
 
@@ -12,6 +12,10 @@ pub struct Cli {
     #[arg(short, long, default_value = "false")]
     pub migrate: bool,
 
+    /// Address to serve Prometheus metrics at /metrics on. Metrics are not served unless set.
+    #[arg(long)]
+    pub metrics_address: Option<SocketAddr>,
+
     #[command(subcommand)]
     pub command: Option<Commands>,
 }
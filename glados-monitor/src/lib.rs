@@ -7,32 +7,56 @@ use ethportal_api::types::content_key::{
     OverlayContentKey,
 };
 use sea_orm::DatabaseConnection;
-use tokio::{fs::read_dir, sync::mpsc, time::sleep};
+use tokio::{
+    fs::read_dir,
+    sync::mpsc,
+    time::{sleep, timeout},
+};
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info, warn};
 use trin_utils::bytes::{hex_decode, hex_encode};
 use web3::types::{BlockId, H256};
 
 use entity::{content, execution_metadata};
+use glados_core::metrics::RPC_ERRORS;
+
+use crate::metrics::{set_head_block, set_stored_block};
 
 pub mod cli;
+pub mod metrics;
 
-pub async fn run_glados_monitor(conn: DatabaseConnection, w3: web3::Web3<web3::transports::Http>) {
-    let (tx, rx) = mpsc::channel(100);
+/// How long blocks already found are given to be stored after shutdown is signalled.
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
 
-    tokio::spawn(follow_chain_head(w3.clone(), tx));
-    tokio::spawn(retrieve_new_blocks(w3.clone(), rx, conn));
+/// Follows the chain head until shutdown is signalled.
+///
+/// On shutdown, no new heads are looked for, and the blocks already found are stored
+/// unless that takes longer than [SHUTDOWN_TIMEOUT].
+pub async fn run_glados_monitor(
+    conn: DatabaseConnection,
+    w3: web3::Web3<web3::transports::Http>,
+    shutdown: CancellationToken,
+) {
+    let (tx, rx) = mpsc::channel(100);
 
-    debug!("setting up CTRL+C listener");
-    tokio::signal::ctrl_c()
-        .await
-        .expect("failed to pause until ctrl-c");
+    tokio::spawn(follow_chain_head(w3.clone(), tx, shutdown.clone()));
+    let mut retrieve = tokio::spawn(retrieve_new_blocks(w3.clone(), rx, conn));
 
-    info!("got CTRL+C. shutting down...");
+    shutdown.cancelled().await;
+    info!("Storing blocks already found before stopping.");
+    if timeout(SHUTDOWN_TIMEOUT, &mut retrieve).await.is_err() {
+        warn!(
+            timeout.seconds = SHUTDOWN_TIMEOUT.as_secs(),
+            "Blocks were not stored in time, abandoning them."
+        );
+        retrieve.abort();
+    }
 }
 
 async fn follow_chain_head(
     w3: web3::Web3<web3::transports::Http>,
     tx: mpsc::Sender<web3::types::U64>,
+    shutdown: CancellationToken,
 ) {
     debug!("initializing head block number");
 
@@ -43,6 +67,7 @@ async fn follow_chain_head(
         .expect("Failed to fetch initial block number");
 
     info!(head_block.number=?start_block_number, "following chain head");
+    set_head_block(start_block_number.as_u64());
 
     tx.send(start_block_number)
         .await
@@ -53,11 +78,24 @@ async fn follow_chain_head(
 
     loop {
         debug!("sleeping....");
-        sleep(Duration::from_secs(5)).await;
+        tokio::select! {
+            _ = shutdown.cancelled() => {
+                debug!("no longer following chain head");
+                return;
+            }
+            _ = sleep(Duration::from_secs(5)) => {}
+        }
 
         debug!(head.number=?block_number, "checking for new block");
 
-        let Ok(candidate_block_number) = w3.eth().block_number().await else {continue};
+        let candidate_block_number = match w3.eth().block_number().await {
+            Ok(block_number) => block_number,
+            Err(e) => {
+                RPC_ERRORS.with_label_values(&["eth_blockNumber"]).inc();
+                warn!(err=?e, "Failed to fetch head block number");
+                continue;
+            }
+        };
 
         if candidate_block_number <= block_number {
             debug!(head.number=?block_number, "head unchanged");
@@ -68,6 +106,7 @@ async fn follow_chain_head(
             new_head.number=?candidate_block_number,
             "new head",
         );
+        set_head_block(candidate_block_number.as_u64());
         if let Err(e) = tx.send(candidate_block_number).await {
             warn!(head.number=?block_number, err=?e , "Failed to send new block number")
         } else {
@@ -77,13 +116,15 @@ async fn follow_chain_head(
 }
 
 /// Listens on a channel, requests blocks from an Execution node and stores derived content keys.
+///
+/// Returns once the channel is closed and the blocks sent before then are stored.
 async fn retrieve_new_blocks(
     w3: web3::Web3<web3::transports::Http>,
     mut rx: mpsc::Receiver<web3::types::U64>,
     conn: DatabaseConnection,
 ) {
     loop {
-        let Some(block_number_to_retrieve) = rx.recv().await else {continue};
+        let Some(block_number_to_retrieve) = rx.recv().await else {break};
         debug!(block.number=?block_number_to_retrieve, "fetching block");
 
         let Ok(block) = w3
@@ -91,6 +132,9 @@ async fn retrieve_new_blocks(
             .block(BlockId::from(block_number_to_retrieve))
             .await
         else {
+            RPC_ERRORS
+                .with_label_values(&["eth_getBlockByNumber"])
+                .inc();
             warn!(head.number=?block_number_to_retrieve, "Failed to retrieve block");
             continue
         };
@@ -116,6 +160,7 @@ async fn retrieve_new_blocks(
         let block_num =
             i32::try_from(block_number_to_retrieve).expect("Block num does not fit in i32.");
         store_block_keys(block_num, block_hash.as_fixed_bytes(), &conn).await;
+        set_stored_block(block_num);
     }
 }
 
@@ -189,15 +234,21 @@ enum DbOutcome {
     Fail(Error),
 }
 
+/// Imports accumulators from the directory, stopping early if shutdown is signalled.
 pub async fn import_pre_merge_accumulators(
     conn: DatabaseConnection,
     base_path: PathBuf,
+    shutdown: CancellationToken,
 ) -> Result<()> {
     info!(base_path = %base_path.as_path().display(), "Starting import of pre-merge accumulators");
 
     let mut entries = read_dir(base_path).await?;
 
     while let Some(entry) = entries.next_entry().await? {
+        if shutdown.is_cancelled() {
+            info!("Import stopped before all accumulators were imported");
+            break;
+        }
         let path = entry.path();
 
         debug!(path = path.as_path().to_str(), "Processing path");
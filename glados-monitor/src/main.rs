@@ -4,9 +4,11 @@ use reqwest::header;
 use sea_orm::{Database, DatabaseConnection};
 use std::env;
 use tokio::{signal, task};
-use tracing::{debug, info};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info};
 use url::Url;
 
+use glados_core::metrics;
 use glados_monitor::{
     cli::{Cli, Commands},
     import_pre_merge_accumulators, run_glados_monitor,
@@ -47,18 +49,39 @@ async fn main() -> Result<()> {
             .expect("Database migration failed");
     }
 
-    let task_handle = match &cli.command {
+    if let Some(address) = cli.metrics_address {
+        task::spawn(async move {
+            if let Err(e) = metrics::serve(address).await {
+                error!(err=?e, "Could not serve metrics.");
+            }
+        });
+    }
+
+    let shutdown = CancellationToken::new();
+    let mut task_handle = match &cli.command {
         Some(Commands::FollowHead { provider_url }) => {
             info!("Running follow head");
-            task::spawn(follow_head_command(conn, provider_url.to_string()))
+            task::spawn(follow_head_command(
+                conn,
+                provider_url.to_string(),
+                shutdown.clone(),
+            ))
         }
         Some(Commands::FollowHeadPandaops { provider_url }) => {
             info!("Running follow head");
-            task::spawn(follow_head_command_pandaops(conn, provider_url.to_string()))
+            task::spawn(follow_head_command_pandaops(
+                conn,
+                provider_url.to_string(),
+                shutdown.clone(),
+            ))
         }
         Some(Commands::ImportPreMergeAccumulators { path }) => {
             info!("Importing pre-merge accumulators");
-            task::spawn(import_pre_merge_accumulators(conn, path.to_path_buf()))
+            task::spawn(import_pre_merge_accumulators(
+                conn,
+                path.to_path_buf(),
+                shutdown.clone(),
+            ))
         }
         &None => {
             info!("No command specified");
@@ -69,9 +92,12 @@ async fn main() -> Result<()> {
     // Wait for either the signal stream or the oneshot channel to receive a message
     tokio::select! {
         _ = signal::ctrl_c() => {
-            println!("Received a CTRL+C signal, exiting");
+            println!("Received a CTRL+C signal, finishing work in progress");
+            shutdown.cancel();
+            let _ = task_handle.await;
+            println!("Exiting");
         }
-        _ = task_handle => {
+        _ = &mut task_handle => {
             println!("Command completed, exiting");
         }
     }
@@ -82,7 +108,11 @@ async fn do_nothing() -> Result<()> {
     Ok(())
 }
 
-async fn follow_head_command(conn: DatabaseConnection, provider_url: String) -> Result<()> {
+async fn follow_head_command(
+    conn: DatabaseConnection,
+    provider_url: String,
+    shutdown: CancellationToken,
+) -> Result<()> {
     //
     // Web3 Connection
     //
@@ -97,13 +127,14 @@ async fn follow_head_command(conn: DatabaseConnection, provider_url: String) ->
         "web3 provider connection established"
     );
 
-    run_glados_monitor(conn, w3).await;
+    run_glados_monitor(conn, w3, shutdown).await;
     Ok(())
 }
 
 async fn follow_head_command_pandaops(
     conn: DatabaseConnection,
     provider_url: String,
+    shutdown: CancellationToken,
 ) -> Result<()> {
     //
     // Web3 Connection
@@ -132,6 +163,6 @@ async fn follow_head_command_pandaops(
         "web3 pandaops connection established"
     );
 
-    run_glados_monitor(conn, w3).await;
+    run_glados_monitor(conn, w3, shutdown).await;
     Ok(())
 }
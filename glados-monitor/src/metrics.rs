@@ -0,0 +1,46 @@
+use lazy_static::lazy_static;
+use prometheus::{register_int_gauge, IntGauge};
+
+lazy_static! {
+    /// Latest block number reported by the execution client.
+    pub static ref HEAD_BLOCK: IntGauge = register_int_gauge!(
+        "glados_monitor_head_block_number",
+        "Latest block number reported by the execution client."
+    )
+    .expect("head block metric can be registered");
+
+    /// Highest block number whose content keys have been stored.
+    pub static ref STORED_BLOCK: IntGauge = register_int_gauge!(
+        "glados_monitor_stored_block_number",
+        "Highest block number whose content keys have been stored."
+    )
+    .expect("stored block metric can be registered");
+
+    /// Blocks between the chain head and the highest stored block.
+    pub static ref HEAD_LAG: IntGauge = register_int_gauge!(
+        "glados_monitor_head_lag_blocks",
+        "Blocks between the chain head and the highest stored block."
+    )
+    .expect("head lag metric can be registered");
+}
+
+/// Records a new chain head.
+pub fn set_head_block(block_number: u64) {
+    HEAD_BLOCK.set(block_number as i64);
+    update_head_lag();
+}
+
+/// Records that the content keys of a block have been stored.
+pub fn set_stored_block(block_number: i32) {
+    if i64::from(block_number) > STORED_BLOCK.get() {
+        STORED_BLOCK.set(block_number.into());
+    }
+    update_head_lag();
+}
+
+/// The lag is only known once a block has been stored.
+fn update_head_lag() {
+    if STORED_BLOCK.get() > 0 {
+        HEAD_LAG.set(HEAD_BLOCK.get() - STORED_BLOCK.get());
+    }
+}
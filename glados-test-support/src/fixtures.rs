@@ -0,0 +1,35 @@
+use chrono::{Duration, Utc};
+use ethportal_api::types::content_key::{BlockBodyKey, HistoryContentKey, OverlayContentKey};
+use sea_orm::{ActiveModelTrait, ActiveValue::NotSet, DatabaseConnection, Set};
+
+use entity::content::{self, SubProtocol};
+
+/// The record of a node other than the mock node, for tests that need a second peer.
+pub const PEER_ENR: &str = "enr:-I24QIdQtNSyUNcoyR4R7pWLfGj0YuX550Qld0HuInYo_b7JE9CIzmi2TF9hPg-OFL3kebYgLjnPkRu17niXB6xKQugBY4d0IDAuMS4wgmlkgnY0gmlwhJO2oc6Jc2VjcDI1NmsxoQJal-rNlNBoOMikJ7PcGk1h6Mlt_XtTWihHwOKmFVE-GoN1ZHCCIyg";
+
+/// An empty block body, which is a valid content value for any block body key.
+pub const EMPTY_BLOCK_BODY: &str = "0x0800000008000000c0";
+
+/// A block body key whose block hash is the seed repeated, so tests can make distinct keys.
+pub fn block_body_key(seed: u8) -> HistoryContentKey {
+    HistoryContentKey::BlockBody(BlockBodyKey {
+        block_hash: [seed; 32],
+    })
+}
+
+/// Adds a content key that has been available for long enough to be audited.
+pub async fn insert_content(
+    content_key: &HistoryContentKey,
+    conn: &DatabaseConnection,
+) -> content::Model {
+    content::ActiveModel {
+        id: NotSet,
+        content_id: Set(content_key.content_id().to_vec()),
+        content_key: Set(content_key.to_bytes()),
+        first_available_at: Set((Utc::now() - Duration::minutes(1)).into()),
+        protocol_id: Set(SubProtocol::History),
+    }
+    .insert(conn)
+    .await
+    .expect("could not insert content")
+}
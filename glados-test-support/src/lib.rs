@@ -0,0 +1,20 @@
+//! Helpers for testing Glados components without a live Portal node or database server.
+use migration::{Migrator, MigratorTrait};
+use sea_orm::{Database, DatabaseConnection};
+
+pub use fixtures::{block_body_key, insert_content, EMPTY_BLOCK_BODY, PEER_ENR};
+pub use mock_node::{MockPortalNode, MockResponse, MOCK_CLIENT_VERSION, MOCK_ENR};
+
+mod fixtures;
+mod mock_node;
+
+/// Creates an in-memory SQLite database with all migrations applied.
+pub async fn setup_database() -> DatabaseConnection {
+    let conn = Database::connect("sqlite::memory:")
+        .await
+        .expect("could not open in-memory database");
+    Migrator::up(&conn, None)
+        .await
+        .expect("could not apply migrations");
+    conn
+}
@@ -0,0 +1,309 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+
+use ethportal_api::types::{
+    content_key::{HistoryContentKey, OverlayContentKey},
+    discv5::Enr,
+};
+use serde_json::{json, Value};
+#[cfg(unix)]
+use tokio::net::{unix::OwnedWriteHalf, UnixListener, UnixStream};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    time::{sleep, Duration},
+};
+use trin_utils::bytes::hex_encode;
+
+/// The ENR that a mock node reports as its own.
+pub const MOCK_ENR: &str = "enr:-I24QDy_atpK3KlPjl6X5yIrK7FosdHI1cW0I0MeiaIVuYg3AEEH9tRSTyFb2k6lpUiFsqxt8uTW3jVMUzoSlQf5OXYBY4d0IDAuMS4wgmlkgnY0gmlwhKEjVaWJc2VjcDI1NmsxoQOSGugH1jSdiE_fRK1FIBe9oLxaWH8D_7xXSnaOVBe-SYN1ZHCCIyg";
+
+/// The client version that a mock node reports.
+pub const MOCK_CLIENT_VERSION: &str = "trin v0.1.0-mock";
+
+/// Used to give each IPC socket in a test run a unique path.
+#[cfg(unix)]
+static SOCKET_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// A scripted response to a JSON-RPC request.
+#[derive(Clone, Debug)]
+pub enum MockResponse {
+    /// Content that was found, sent hex encoded. Malformed content is scripted by
+    /// passing bytes that are not a valid content value.
+    Content(Vec<u8>),
+    /// The special "0x" response used by the Portal specs for absent content.
+    ContentAbsent,
+    /// Any other successful result.
+    Result(Value),
+    /// A JSON-RPC error response.
+    Error { code: i64, message: String },
+    /// A response that is not valid JSON.
+    Malformed,
+    /// A response that is sent after a delay.
+    Slow(Duration, Box<MockResponse>),
+}
+
+#[derive(Default)]
+struct Script {
+    /// Responses for a method called with specific params, keyed by (method, params).
+    by_params: HashMap<(String, String), MockResponse>,
+    /// Responses for a method called with any other params.
+    by_method: HashMap<String, MockResponse>,
+    requests: Vec<Value>,
+}
+
+/// An in-process Portal node that answers JSON-RPC requests over HTTP and IPC with
+/// scripted responses.
+///
+/// Clones share the same script, so responses may be changed while the node is serving.
+#[derive(Clone)]
+pub struct MockPortalNode {
+    script: Arc<Mutex<Script>>,
+}
+
+impl Default for MockPortalNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockPortalNode {
+    /// Creates a node that answers the requests made when connecting a `PortalClient`.
+    /// All other methods respond with a "method not found" error until scripted.
+    pub fn new() -> Self {
+        let node = MockPortalNode {
+            script: Arc::new(Mutex::new(Script::default())),
+        };
+        let enr = node.enr();
+        node.respond(
+            "web3_clientVersion",
+            MockResponse::Result(json!(MOCK_CLIENT_VERSION)),
+        );
+        node.respond(
+            "discv5_nodeInfo",
+            MockResponse::Result(json!({
+                "enr": MOCK_ENR,
+                "nodeId": hex_encode(enr.node_id().raw()),
+            })),
+        );
+        node
+    }
+
+    pub fn enr(&self) -> Enr {
+        Enr::from_str(MOCK_ENR).expect("mock ENR is valid")
+    }
+
+    /// Sets the response to a method, unless there is a response for the specific params.
+    pub fn respond(&self, method: &str, response: MockResponse) {
+        self.lock().by_method.insert(method.to_owned(), response);
+    }
+
+    /// Sets the response to a method when called with exactly these params.
+    pub fn respond_to(&self, method: &str, params: Value, response: MockResponse) {
+        self.lock()
+            .by_params
+            .insert((method.to_owned(), params.to_string()), response);
+    }
+
+    /// Sets the response to recursive lookups of the content key, with or without a trace.
+    pub fn respond_to_content_key(&self, content_key: &HistoryContentKey, response: MockResponse) {
+        let params = json!([hex_encode(content_key.to_bytes())]);
+        self.respond_to(
+            "portal_historyRecursiveFindContent",
+            params.clone(),
+            response.clone(),
+        );
+        self.respond_to("portal_historyTraceRecursiveFindContent", params, response);
+    }
+
+    /// Returns all requests received so far, in order of arrival.
+    pub fn requests(&self) -> Vec<Value> {
+        self.lock().requests.clone()
+    }
+
+    /// Returns the number of requests received so far for the method.
+    pub fn request_count(&self, method: &str) -> usize {
+        self.lock()
+            .requests
+            .iter()
+            .filter(|request| request["method"] == method)
+            .count()
+    }
+
+    /// Starts serving over HTTP on a local port. Returns the URL of the node.
+    pub async fn serve_http(&self) -> std::io::Result<String> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}", listener.local_addr()?);
+        let node = self.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve_http_connection(node.clone(), stream));
+            }
+        });
+        Ok(url)
+    }
+
+    /// Starts serving over IPC on a new socket in the temporary directory.
+    /// Returns the URL of the node.
+    #[cfg(unix)]
+    pub async fn serve_ipc(&self) -> std::io::Result<String> {
+        let path = std::env::temp_dir().join(format!(
+            "glados-mock-{}-{}.ipc",
+            std::process::id(),
+            SOCKET_COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path)?;
+        let node = self.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve_ipc_connection(node.clone(), stream));
+            }
+        });
+        Ok(format!("ipc:///{}", path.display()))
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Script> {
+        self.script.lock().expect("poisoned mock node lock")
+    }
+
+    /// Records the request and returns its response body, along with how long to wait
+    /// before sending it.
+    fn handle(&self, request: Value) -> (Duration, String) {
+        let method = request["method"].as_str().unwrap_or_default().to_owned();
+        let key = (method.clone(), request["params"].to_string());
+        let id = request["id"].clone();
+        let response = {
+            let mut script = self.lock();
+            script.requests.push(request);
+            script
+                .by_params
+                .get(&key)
+                .or_else(|| script.by_method.get(&method))
+                .cloned()
+        };
+        let response = response.unwrap_or_else(|| MockResponse::Error {
+            code: -32601,
+            message: format!("Method not found: {method}"),
+        });
+        render(&method, &id, response)
+    }
+}
+
+/// Creates the body of a response, along with how long to wait before sending it.
+fn render(method: &str, id: &Value, response: MockResponse) -> (Duration, String) {
+    // Traced lookups wrap the content with the route taken to find it.
+    let traced = method == "portal_historyTraceRecursiveFindContent";
+    let result = match response {
+        MockResponse::Content(raw) if traced => json!({"content": hex_encode(raw), "trace": {}}),
+        MockResponse::Content(raw) => json!(hex_encode(raw)),
+        MockResponse::ContentAbsent if traced => json!({"content": "0x", "trace": {}}),
+        MockResponse::ContentAbsent => json!("0x"),
+        MockResponse::Result(result) => result,
+        MockResponse::Error { code, message } => {
+            let error =
+                json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}});
+            return (Duration::ZERO, error.to_string());
+        }
+        MockResponse::Malformed => {
+            return (
+                Duration::ZERO,
+                format!(r#"{{"jsonrpc": "2.0", "id": {id}, "result": }}"#),
+            )
+        }
+        MockResponse::Slow(delay, response) => {
+            let (inner_delay, body) = render(method, id, *response);
+            return (delay + inner_delay, body);
+        }
+    };
+    let response = json!({"jsonrpc": "2.0", "id": id, "result": result});
+    (Duration::ZERO, response.to_string())
+}
+
+/// Answers HTTP/1.1 requests on the connection until it is closed.
+async fn serve_http_connection(node: MockPortalNode, mut stream: TcpStream) {
+    let mut buffer: Vec<u8> = vec![];
+    let mut chunk = vec![0u8; 8192];
+    loop {
+        let header_end = loop {
+            if let Some(position) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+                break position + 4;
+            }
+            match stream.read(&mut chunk).await {
+                Ok(0) | Err(_) => return,
+                Ok(n) => buffer.extend_from_slice(&chunk[..n]),
+            }
+        };
+        let headers = String::from_utf8_lossy(&buffer[..header_end]).to_lowercase();
+        let content_length = headers
+            .lines()
+            .find_map(|line| line.strip_prefix("content-length:"))
+            .and_then(|length| length.trim().parse::<usize>().ok())
+            .unwrap_or(0);
+        while buffer.len() < header_end + content_length {
+            match stream.read(&mut chunk).await {
+                Ok(0) | Err(_) => return,
+                Ok(n) => buffer.extend_from_slice(&chunk[..n]),
+            }
+        }
+        let request: Vec<u8> = buffer.drain(..header_end + content_length).collect();
+        let (delay, body) = match serde_json::from_slice(&request[header_end..]) {
+            Ok(request) => node.handle(request),
+            Err(_) => return,
+        };
+        sleep(delay).await;
+        let response = format!(
+            "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{body}",
+            body.len()
+        );
+        if stream.write_all(response.as_bytes()).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Answers requests on the IPC connection until it is closed. Requests are answered
+/// concurrently, so a slow response does not hold up others on the same connection.
+#[cfg(unix)]
+async fn serve_ipc_connection(node: MockPortalNode, stream: UnixStream) {
+    let (mut reader, writer) = stream.into_split();
+    let writer = Arc::new(tokio::sync::Mutex::new(writer));
+    let mut buffer: Vec<u8> = vec![];
+    let mut chunk = vec![0u8; 8192];
+    loop {
+        match reader.read(&mut chunk).await {
+            Ok(0) | Err(_) => return,
+            Ok(n) => buffer.extend_from_slice(&chunk[..n]),
+        }
+        let mut requests = serde_json::Deserializer::from_slice(&buffer).into_iter::<Value>();
+        let mut received = vec![];
+        loop {
+            match requests.next() {
+                Some(Ok(request)) => received.push(request),
+                Some(Err(e)) if e.is_eof() => break,
+                Some(Err(_)) => return,
+                None => break,
+            }
+        }
+        let consumed = requests.byte_offset();
+        buffer.drain(..consumed);
+        for request in received {
+            let (delay, body) = node.handle(request);
+            tokio::spawn(write_ipc_response(writer.clone(), delay, body));
+        }
+    }
+}
+
+#[cfg(unix)]
+async fn write_ipc_response(
+    writer: Arc<tokio::sync::Mutex<OwnedWriteHalf>>,
+    delay: Duration,
+    body: String,
+) {
+    sleep(delay).await;
+    let _ = writer.lock().await.write_all(body.as_bytes()).await;
+}
@@ -5,4 +5,7 @@ use clap::Parser;
 pub struct Args {
     #[arg(short, long, default_value = "sqlite::memory:")]
     pub database_url: String,
+    /// Token that must accompany requests to audit content. Requests are refused when absent.
+    #[arg(long)]
+    pub audit_request_token: Option<String>,
 }
@@ -5,11 +5,11 @@ use anyhow::{bail, Result};
 use axum::http::StatusCode;
 use axum::{
     extract::Extension,
-    routing::{get, get_service},
+    routing::{get, get_service, post},
     Router,
 };
 use tower_http::services::ServeDir;
-use tracing::info;
+use tracing::{info, warn};
 
 use ethereum_types::U256;
 use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, Set};
@@ -49,10 +49,31 @@ pub async fn run_glados_web(config: Arc<State>) -> Result<()> {
         info!(row.id=?updated.id, old=?previous_value, new=?updated.node_id_high, "Setting high bits");
     }
 
+    let decoded = entity::record::decode_missing_fields(&config.database_connection).await?;
+    for (record_id, err) in &decoded.failed {
+        warn!(record.id = record_id, err, "Could not decode stored ENR, leaving it undecoded");
+    }
+    info!(rows=?decoded.decoded, "One time migration: decoding well-known ENR keys");
+    let linked_count =
+        entity::record::link_missing_client_info(&config.database_connection).await?;
+    info!(rows=?linked_count, "One time migration: linking nodes to their client");
+
     // setup router
     let app = Router::new()
         .route("/", get(routes::root))
         .route("/network/", get(routes::network_dashboard))
+        .route("/network/census/", get(routes::census_list))
+        .route(
+            "/network/census/:old_census_id/diff/:new_census_id/",
+            get(routes::census_diff),
+        )
+        .route(
+            "/api/census/:old_census_id/diff/:new_census_id/",
+            get(routes::census_diff_json),
+        )
+        .route("/network/changes/", get(routes::window_diff))
+        .route("/api/network/changes/", get(routes::window_diff_json))
+        .route("/network/keyspace/", get(routes::keyspace))
         .route("/network/node/:node_id_hex/", get(routes::node_detail))
         .route(
             "/network/node/:node_id_hex/enr/:enr_seq/",
@@ -69,7 +90,12 @@ pub async fn run_glados_web(config: Arc<State>) -> Result<()> {
             "/content/key/:content_key_hex/",
             get(routes::contentkey_detail),
         )
+        .route(
+            "/content/key/:content_key_hex/audit/",
+            post(routes::contentkey_request_audit),
+        )
         .route("/audit/id/:audit_id", get(routes::contentaudit_detail))
+        .route("/metrics", get(routes::metrics))
         .nest_service("/static/", serve_dir.clone())
         .fallback_service(serve_dir)
         .layer(Extension(config));
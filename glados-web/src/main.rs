@@ -20,6 +20,7 @@ async fn main() -> Result<()> {
 
     let config = Arc::new(State {
         database_connection: conn,
+        audit_request_token: args.audit_request_token,
     });
 
     run_glados_web(config).await?;
@@ -1,31 +1,37 @@
+use std::collections::{BTreeSet, HashMap};
 use std::sync::Arc;
 use std::{fmt::Display, io};
 
 use axum::{
-    extract::{Extension, Path},
-    http::StatusCode,
-    response::IntoResponse,
+    extract::{Extension, Form, Path, Query},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+    Json,
 };
-use chrono::{DateTime, Duration, Utc};
+use chrono::{DateTime, Duration, FixedOffset, Utc};
 use entity::{
-    content,
-    content_audit::{self, AuditResult},
-    execution_metadata, key_value, node, record,
+    audit_queue, census, client_info, content,
+    content_audit::{self, AuditResult, SelectionStrategy, TraceObservation},
+    execution_metadata, key_value, node, node_liveness, node_radius, node_storage_audit, record,
 };
 use ethportal_api::types::content_key::{HistoryContentKey, OverlayContentKey};
+use prometheus::{proto::MetricFamily, IntGauge, IntGaugeVec, Opts, Registry};
 use sea_orm::{
     ColumnTrait, DatabaseConnection, EntityTrait, ModelTrait, PaginatorTrait, QueryFilter,
     QueryOrder, QuerySelect,
 };
+use serde::Deserialize;
 use tracing::error;
 use tracing::info;
+use tracing::warn;
 use trin_utils::bytes::{hex_decode, hex_encode};
 
 use crate::state::State;
 use crate::templates::{
-    ContentAuditDetailTemplate, ContentDashboardTemplate, ContentIdDetailTemplate,
-    ContentIdListTemplate, ContentKeyDetailTemplate, ContentKeyListTemplate, EnrDetailTemplate,
-    HtmlTemplate, IndexTemplate, NetworkDashboardTemplate, NodeDetailTemplate,
+    CensusDiffTemplate, CensusListTemplate, ContentAuditDetailTemplate, ContentDashboardTemplate,
+    ContentIdDetailTemplate, ContentIdListTemplate, ContentKeyDetailTemplate,
+    ContentKeyListTemplate, EnrDetailTemplate, HtmlTemplate, IndexTemplate, KeyspaceTemplate,
+    NetworkDashboardTemplate, NodeDetailTemplate, WindowDiffTemplate,
 };
 
 //
@@ -45,15 +51,13 @@ pub async fn network_dashboard(
 ) -> Result<HtmlTemplate<NetworkDashboardTemplate>, StatusCode> {
     const KEY_COUNT: u64 = 20;
 
-    let recent_node_list = node::Entity::find()
-        .order_by_desc(node::Column::Id)
-        .limit(KEY_COUNT)
-        .all(&state.database_connection)
-        .await
-        .map_err(|e| {
-            error!(key.count=KEY_COUNT, err=?e, "Could not look up recent nodes");
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+    let recent_node_list =
+        node_liveness::get_recent_nodes_with_latest_check(KEY_COUNT, &state.database_connection)
+            .await
+            .map_err(|e| {
+                error!(key.count=KEY_COUNT, err=?e, "Could not look up recent nodes");
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
     let total_node_count = node::Entity::find()
         .count(&state.database_connection)
         .await
@@ -88,20 +92,290 @@ pub async fn network_dashboard(
             StatusCode::INTERNAL_SERVER_ERROR
         })?;
 
+    let latest_census = match census::get_finished(1, &state.database_connection)
+        .await
+        .map_err(|e| {
+            error!(err=?e, "Could not look up latest census");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .pop()
+    {
+        Some(census_model) => {
+            let node_count = census_model
+                .node_count(&state.database_connection)
+                .await
+                .map_err(|e| {
+                    error!(census.id=census_model.id, err=?e, "Could not count census nodes");
+                    StatusCode::INTERNAL_SERVER_ERROR
+                })?;
+            Some((census_model, node_count))
+        }
+        None => None,
+    };
+
+    let client_counts = client_info::get_node_counts(&state.database_connection)
+        .await
+        .map_err(|e| {
+            error!(err=?e, "Could not count nodes by client");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    let (client_names, client_history) =
+        client_diversity_history(&state.database_connection).await?;
+
     let template = NetworkDashboardTemplate {
         total_node_count,
         total_enr_count,
+        latest_census,
+        client_counts,
+        client_names,
+        client_history,
         recent_node_list,
         recent_enr_list,
     };
     Ok(HtmlTemplate(template))
 }
 
+/// Counts the nodes found by recent censuses by client, oldest first.
+///
+/// Returns every client name seen, and for each census the count for each of those names.
+async fn client_diversity_history(
+    conn: &DatabaseConnection,
+) -> Result<(Vec<String>, Vec<(census::Model, Vec<u64>)>), StatusCode> {
+    const CENSUS_COUNT: u64 = 10;
+
+    let mut censuses = census::get_finished(CENSUS_COUNT, conn)
+        .await
+        .map_err(|e| {
+            error!(census.count=CENSUS_COUNT, err=?e, "Could not look up recent censuses");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    censuses.reverse();
+    let mut census_counts = Vec::with_capacity(censuses.len());
+    for census_model in censuses {
+        let counts = census_model.client_counts(conn).await.map_err(|e| {
+            error!(census.id=census_model.id, err=?e, "Could not count census nodes by client");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        census_counts.push((census_model, counts));
+    }
+
+    let client_names: Vec<String> = census_counts
+        .iter()
+        .flat_map(|(_, counts)| counts.keys().cloned())
+        .collect::<BTreeSet<String>>()
+        .into_iter()
+        .collect();
+    let client_history = census_counts
+        .into_iter()
+        .map(|(census_model, counts)| {
+            let row = client_names
+                .iter()
+                .map(|name| counts.get(name).copied().unwrap_or(0))
+                .collect();
+            (census_model, row)
+        })
+        .collect();
+    Ok((client_names, client_history))
+}
+
+/// Start of the period that node uptime and churn are measured over.
+fn liveness_period_start() -> DateTime<FixedOffset> {
+    (Utc::now() - Duration::hours(24)).into()
+}
+
+pub async fn census_list(
+    Extension(state): Extension<Arc<State>>,
+) -> Result<HtmlTemplate<CensusListTemplate>, StatusCode> {
+    const CENSUS_COUNT: u64 = 20;
+
+    let censuses = census::get_finished(CENSUS_COUNT + 1, &state.database_connection)
+        .await
+        .map_err(|e| {
+            error!(census.count=CENSUS_COUNT, err=?e, "Could not look up recent censuses");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    // The extra census is only fetched so that the oldest one shown can be compared to it.
+    let previous_ids: Vec<Option<i32>> = censuses
+        .iter()
+        .skip(1)
+        .map(|census_model| Some(census_model.id))
+        .chain([None])
+        .collect();
+    let mut census_list = Vec::with_capacity(censuses.len());
+    for (census_model, previous_id) in censuses
+        .into_iter()
+        .zip(previous_ids)
+        .take(CENSUS_COUNT as usize)
+    {
+        let node_count = census_model
+            .node_count(&state.database_connection)
+            .await
+            .map_err(|e| {
+                error!(census.id=census_model.id, err=?e, "Could not count census nodes");
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+        census_list.push((census_model, node_count, previous_id));
+    }
+    Ok(HtmlTemplate(CensusListTemplate { census_list }))
+}
+
+pub async fn census_diff(
+    Path((old_census_id, new_census_id)): Path<(i32, i32)>,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<HtmlTemplate<CensusDiffTemplate>, StatusCode> {
+    let old_census = get_finished_census(old_census_id, &state.database_connection).await?;
+    let new_census = get_finished_census(new_census_id, &state.database_connection).await?;
+    let diff = compare_censuses(&old_census, &new_census, &state.database_connection).await?;
+    let template = CensusDiffTemplate {
+        old_census,
+        new_census,
+        diff,
+    };
+    Ok(HtmlTemplate(template))
+}
+
+pub async fn census_diff_json(
+    Path((old_census_id, new_census_id)): Path<(i32, i32)>,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<Json<census::CensusDiff>, StatusCode> {
+    let old_census = get_finished_census(old_census_id, &state.database_connection).await?;
+    let new_census = get_finished_census(new_census_id, &state.database_connection).await?;
+    let diff = compare_censuses(&old_census, &new_census, &state.database_connection).await?;
+    Ok(Json(diff))
+}
+
+/// Looks up a census, which must have finished for its node set to be complete.
+async fn get_finished_census(
+    census_id: i32,
+    conn: &DatabaseConnection,
+) -> Result<census::Model, StatusCode> {
+    census::Entity::find_by_id(census_id)
+        .filter(census::Column::EndedAt.is_not_null())
+        .one(conn)
+        .await
+        .map_err(|e| {
+            error!(census.id=census_id, err=?e, "Could not look up census");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or_else(|| {
+            error!(census.id = census_id, "No finished census found");
+            StatusCode::NOT_FOUND
+        })
+}
+
+async fn compare_censuses(
+    old_census: &census::Model,
+    new_census: &census::Model,
+    conn: &DatabaseConnection,
+) -> Result<census::CensusDiff, StatusCode> {
+    census::diff(old_census, new_census, conn)
+        .await
+        .map_err(|e| {
+            error!(old_census.id=old_census.id, new_census.id=new_census.id, err=?e, "Could not compare censuses");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+/// Selects the time windows compared by the network changes page.
+#[derive(Deserialize)]
+pub struct WindowDiffParams {
+    /// Length of each window, the latest of which ends now. Defaults to a day.
+    hours: Option<i64>,
+}
+
+pub async fn window_diff(
+    Query(params): Query<WindowDiffParams>,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<HtmlTemplate<WindowDiffTemplate>, StatusCode> {
+    let hours = window_hours(&params)?;
+    let diff = compare_windows(hours, &state.database_connection).await?;
+    Ok(HtmlTemplate(WindowDiffTemplate { hours, diff }))
+}
+
+pub async fn window_diff_json(
+    Query(params): Query<WindowDiffParams>,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<Json<census::WindowDiff>, StatusCode> {
+    let hours = window_hours(&params)?;
+    let diff = compare_windows(hours, &state.database_connection).await?;
+    Ok(Json(diff))
+}
+
+fn window_hours(params: &WindowDiffParams) -> Result<i64, StatusCode> {
+    const MAX_HOURS: i64 = 24 * 30;
+    match params.hours.unwrap_or(24) {
+        hours @ 1..=MAX_HOURS => Ok(hours),
+        hours => {
+            warn!(hours, "Window length out of range");
+            Err(StatusCode::BAD_REQUEST)
+        }
+    }
+}
+
+/// Compares the last `hours` hours with the same length of time before them.
+async fn compare_windows(
+    hours: i64,
+    conn: &DatabaseConnection,
+) -> Result<census::WindowDiff, StatusCode> {
+    let new_end: DateTime<FixedOffset> = Utc::now().into();
+    let new_start = new_end - Duration::hours(hours);
+    let old_start = new_start - Duration::hours(hours);
+    census::diff_windows(old_start..new_start, new_start..new_end, conn)
+        .await
+        .map_err(|e| {
+            error!(window.hours=hours, err=?e, "Could not compare time windows");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+pub async fn keyspace(
+    Extension(state): Extension<Arc<State>>,
+) -> Result<HtmlTemplate<KeyspaceTemplate>, StatusCode> {
+    const PREFIX_BITS: u8 = 6;
+    let period = Period::Day;
+    let node_counts = node::get_keyspace_counts(PREFIX_BITS, &state.database_connection)
+        .await
+        .map_err(|e| {
+            error!(err=?e, "Could not count nodes by keyspace bucket");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    let audit_counts = content_audit::get_keyspace_counts(
+        PREFIX_BITS,
+        period.cutoff_time(),
+        &state.database_connection,
+    )
+    .await
+    .map_err(|e| {
+        error!(err=?e, "Could not count audits by keyspace bucket");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let max_node_count = node_counts.iter().copied().max().unwrap_or(0);
+    let bucket_list = node_counts
+        .into_iter()
+        .zip(audit_counts)
+        .enumerate()
+        .map(|(index, (node_count, audits))| {
+            KeyspaceBucket::new(index, PREFIX_BITS, node_count, max_node_count, audits)
+        })
+        .collect();
+    let template = KeyspaceTemplate {
+        period,
+        prefix_bits: PREFIX_BITS,
+        bucket_list,
+    };
+    Ok(HtmlTemplate(template))
+}
+
 pub async fn node_detail(
     Path(node_id_hex): Path<String>,
     Extension(state): Extension<Arc<State>>,
 ) -> Result<HtmlTemplate<NodeDetailTemplate>, StatusCode> {
     const KEY_COUNT: u64 = 50;
+    const RADIUS_COUNT: u64 = 20;
+    const LIVENESS_COUNT: u64 = 20;
+    const STORAGE_AUDIT_COUNT: u64 = 20;
+    const CLOSEST_COUNT: u64 = 100;
     let node_id = hex_decode(&node_id_hex).map_err(|e| {
         error!(node_id=node_id_hex, err=?e, "Could not decode proved node_id");
         StatusCode::INTERNAL_SERVER_ERROR
@@ -125,9 +399,12 @@ pub async fn node_detail(
             error!(node.node_id=node_id_hex, node.db_id=node_model.id, err=?e, "Error looking up ENRs");
             StatusCode::NOT_FOUND
         })?;
-    let closest_node_list = node::closest_xor(node_model.get_node_id(), &state.database_connection)
+    let closest_node_list = node::closest_xor(node_model.get_node_id(), CLOSEST_COUNT, &state.database_connection)
         .await
-        .unwrap();
+        .map_err(|e| {
+            error!(node.node_id=node_id_hex, node.db_id=node_model.id, err=?e, "Error looking up closest nodes");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
 
     let latest_enr = enr_list.get(0).cloned();
 
@@ -145,12 +422,40 @@ pub async fn node_detail(
         ),
         None => None,
     };
+    let radius_list = node_radius::get_history(node_model.id, RADIUS_COUNT, &state.database_connection)
+        .await
+        .map_err(|e| {
+            error!(node.node_id=node_id_hex, node.db_id=node_model.id, err=?e, "Error looking up data radius history");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    let liveness = node_liveness::get_summary(node_model.id, liveness_period_start(), &state.database_connection)
+        .await
+        .map_err(|e| {
+            error!(node.node_id=node_id_hex, node.db_id=node_model.id, err=?e, "Could not summarize node liveness");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    let liveness_list = node_liveness::get_history(node_model.id, LIVENESS_COUNT, &state.database_connection)
+        .await
+        .map_err(|e| {
+            error!(node.node_id=node_id_hex, node.db_id=node_model.id, err=?e, "Error looking up liveness checks");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    let storage_audit_list = node_storage_audit::get_for_node(node_model.id, STORAGE_AUDIT_COUNT, &state.database_connection)
+        .await
+        .map_err(|e| {
+            error!(node.node_id=node_id_hex, node.db_id=node_model.id, err=?e, "Error looking up node storage audits");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
     let template = NodeDetailTemplate {
         node: node_model,
         latest_enr,
         latest_enr_key_value_list,
         enr_list,
         closest_node_list,
+        radius_list,
+        liveness,
+        liveness_list,
+        storage_audit_list,
     };
     Ok(HtmlTemplate(template))
 }
@@ -267,6 +572,14 @@ pub async fn content_dashboard(
                 StatusCode::INTERNAL_SERVER_ERROR
             })?;
 
+    let block_age_stats =
+        content_audit::get_block_age_counts(Period::Day.cutoff_time(), &state.database_connection)
+            .await
+            .map_err(|e| {
+                error!(err=?e, "Could not count audits by block age");
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+
     let template = ContentDashboardTemplate {
         stats: [
             get_audit_stats(Period::Hour, &state.database_connection).await?,
@@ -278,6 +591,7 @@ pub async fn content_dashboard(
         recent_audits: audit_model_to_display(recent_audits_model)?,
         recent_audit_successes: audit_model_to_display(recent_audit_success_model)?,
         recent_audit_failures: audit_model_to_display(recent_audit_failure_model)?,
+        block_age_stats,
     };
     Ok(HtmlTemplate(template))
 }
@@ -382,9 +696,32 @@ pub async fn contentid_detail(
             StatusCode::INTERNAL_SERVER_ERROR
         })?;
 
+    // Observations of the responsible nodes are taken from the most recent audits.
+    const AUDIT_COUNT: u64 = 100;
+    let contentaudit_list = content_audit::Entity::find()
+        .filter(
+            content_audit::Column::ContentKey
+                .is_in(contentkey_list.iter().map(|content_key| content_key.id)),
+        )
+        .order_by_desc(content_audit::Column::CreatedAt)
+        .limit(AUDIT_COUNT)
+        .all(&state.database_connection)
+        .await
+        .map_err(|e| {
+            error!(content.id=content_id_hex, err=?e, "Could not look up audits for id");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    let responsible_node_list = get_responsible_nodes(
+        content_id_raw_array(&content_id.content_id, &content_id_hex)?,
+        &contentaudit_list,
+        &state.database_connection,
+    )
+    .await?;
+
     let template = ContentIdDetailTemplate {
         content_id,
         contentkey_list,
+        responsible_node_list,
     };
     Ok(HtmlTemplate(template))
 }
@@ -413,6 +750,7 @@ pub async fn contentkey_detail(
     Path(content_key_hex): Path<String>,
     Extension(state): Extension<Arc<State>>,
 ) -> Result<HtmlTemplate<ContentKeyDetailTemplate>, StatusCode> {
+    const STORAGE_AUDIT_COUNT: u64 = 20;
     let content_key_raw = hex_decode(&content_key_hex).map_err(|e| {
         error!(content.key=content_key_hex, err=?e, "Could not decode up key bytes");
         StatusCode::INTERNAL_SERVER_ERROR
@@ -454,6 +792,34 @@ pub async fn contentkey_detail(
         })?;
     let block_number = metadata_model.map(|m| m.block_number);
 
+    let responsible_node_list = get_responsible_nodes(
+        content_key.content_id(),
+        &contentaudit_list,
+        &state.database_connection,
+    )
+    .await?;
+
+    // Content that a strategy queued is not shown as requested.
+    let requested_audit =
+        audit_queue::get_pending(content_key_model.id, &state.database_connection)
+            .await
+            .map_err(|e| {
+                error!(content.key=content_key_hex, err=?e, "Could not look up queued audit");
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?
+            .filter(|entry| entry.strategy == SelectionStrategy::Requested);
+
+    let storage_audit_list = node_storage_audit::get_for_content(
+        content_key_model.id,
+        STORAGE_AUDIT_COUNT,
+        &state.database_connection,
+    )
+    .await
+    .map_err(|e| {
+        error!(content.key=content_key_hex, err=?e, "Could not look up node storage audits");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
     let content_id = hex_encode(content_key.content_id());
     let content_kind = content_key.to_string();
     let template = ContentKeyDetailTemplate {
@@ -463,10 +829,93 @@ pub async fn contentkey_detail(
         content_id,
         content_kind,
         block_number,
+        responsible_node_list,
+        storage_audit_list,
+        requested_audit,
+        audit_requests_enabled: state.audit_request_token.is_some(),
     };
     Ok(HtmlTemplate(template))
 }
 
+#[derive(Deserialize)]
+pub struct AuditRequestForm {
+    token: Option<String>,
+}
+
+/// Queues a high priority audit of the content key for glados-audit to pick up.
+///
+/// The configured token must be given either as a bearer token or in the form.
+pub async fn contentkey_request_audit(
+    Path(content_key_hex): Path<String>,
+    Extension(state): Extension<Arc<State>>,
+    headers: HeaderMap,
+    form: Option<Form<AuditRequestForm>>,
+) -> Result<Redirect, StatusCode> {
+    let Some(expected_token) = &state.audit_request_token else {
+        warn!(
+            content.key = content_key_hex,
+            "Audit requests are not enabled"
+        );
+        return Err(StatusCode::FORBIDDEN);
+    };
+    let bearer_token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let form_token = form.as_ref().and_then(|form| form.token.as_deref());
+    let given_token = bearer_token.or(form_token).unwrap_or_default();
+    if !tokens_match(given_token, expected_token) {
+        warn!(
+            content.key = content_key_hex,
+            "Audit request with a wrong token"
+        );
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let content_key_raw = hex_decode(&content_key_hex).map_err(|e| {
+        error!(content.key=content_key_hex, err=?e, "Could not decode up key bytes");
+        StatusCode::BAD_REQUEST
+    })?;
+    let content_key_model = content::Entity::find()
+        .filter(content::Column::ContentKey.eq(content_key_raw))
+        .one(&state.database_connection)
+        .await
+        .map_err(|e| {
+            error!(content.key=content_key_hex, err=?e, "Could not look up key");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or_else(|| {
+            error!(content.key = content_key_hex, "No data for key");
+            StatusCode::NOT_FOUND
+        })?;
+
+    // Content a strategy already queued is promoted to a request, and asking again while a
+    // request is waiting leaves that request in place.
+    audit_queue::enqueue(
+        content_key_model.id,
+        SelectionStrategy::Requested,
+        audit_queue::REQUESTED_PRIORITY,
+        &state.database_connection,
+    )
+    .await
+    .map_err(|e| {
+        error!(content.key=content_key_hex, err=?e, "Could not queue audit");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    info!(content.key = content_key_hex, "Audit requested");
+    Ok(Redirect::to(&format!("/content/key/{content_key_hex}/")))
+}
+
+/// Compares tokens in time that does not depend on where they first differ.
+fn tokens_match(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
 pub async fn contentaudit_detail(
     Path(audit_id): Path<String>,
     Extension(state): Extension<Arc<State>>,
@@ -490,6 +939,226 @@ pub async fn contentaudit_detail(
     HtmlTemplate(template)
 }
 
+/// Metrics read from the database, in the Prometheus text format.
+///
+/// Every instance of glados-web reports the same values, so any one of them can be scraped.
+pub async fn metrics(Extension(state): Extension<Arc<State>>) -> Result<Response, StatusCode> {
+    let metric_families = database_metrics(&state.database_connection)
+        .await
+        .map_err(|e| {
+            error!(err=?e, "Could not gather metrics");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    Ok(glados_core::metrics::render(&metric_families))
+}
+
+async fn database_metrics(conn: &DatabaseConnection) -> anyhow::Result<Vec<MetricFamily>> {
+    let registry = Registry::new();
+
+    let audits = IntGaugeVec::new(
+        Opts::new(
+            "glados_audits_last_hour",
+            "Audits made in the last hour, by result, strategy and client.",
+        ),
+        &["result", "strategy", "client"],
+    )?;
+    let since = Utc::now() - Duration::hours(1);
+    for (result, strategy, client, count) in content_audit::get_result_counts(since, conn).await? {
+        let strategy = match strategy {
+            Some(strategy) => strategy.as_text(),
+            None => "none".to_owned(),
+        };
+        audits
+            .with_label_values(&[&result.as_text(), &strategy, &client])
+            .set(count as i64);
+    }
+    registry.register(Box::new(audits))?;
+
+    let queue_depth = IntGaugeVec::new(
+        Opts::new(
+            "glados_audit_queue_depth",
+            "Tasks in the audit queue, by strategy and whether they have been claimed.",
+        ),
+        &["strategy", "state"],
+    )?;
+    for (strategy, waiting, claimed) in audit_queue::get_depths(conn).await? {
+        let strategy = strategy.as_text();
+        queue_depth
+            .with_label_values(&[&strategy, "waiting"])
+            .set(waiting as i64);
+        queue_depth
+            .with_label_values(&[&strategy, "claimed"])
+            .set(claimed as i64);
+    }
+    registry.register(Box::new(queue_depth))?;
+
+    if let Some(block_number) = execution_metadata::get_latest_block_number(conn).await? {
+        let latest_block = IntGauge::new(
+            "glados_latest_block_number",
+            "Highest block number with stored content keys.",
+        )?;
+        latest_block.set(block_number.into());
+        registry.register(Box::new(latest_block))?;
+    }
+
+    let known_nodes = IntGauge::new("glados_known_nodes", "Nodes seen on the network.")?;
+    known_nodes.set(node::Entity::find().count(conn).await? as i64);
+    registry.register(Box::new(known_nodes))?;
+
+    if let Some(census_model) = census::get_finished(1, conn).await?.pop() {
+        let census_nodes = IntGauge::new(
+            "glados_census_nodes",
+            "Nodes counted by the latest finished census.",
+        )?;
+        census_nodes.set(census_model.node_count(conn).await? as i64);
+        registry.register(Box::new(census_nodes))?;
+    }
+
+    Ok(registry.gather())
+}
+
+fn content_id_raw_array(content_id: &[u8], content_id_hex: &str) -> Result<[u8; 32], StatusCode> {
+    content_id.try_into().map_err(|_| {
+        error!(content.id = content_id_hex, "Content id is not 32 bytes");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+/// A node that is expected to store a piece of content, based on its distance from the content id.
+pub struct ResponsibleNode {
+    pub node: node::ModelWithDistance,
+    pub last_seen: Option<DateTime<FixedOffset>>,
+    pub radius: Option<node_radius::Model>,
+    /// How many traced audits saw this node send the content.
+    pub served_count: usize,
+    /// How many traced audits saw this node respond without the content.
+    pub missing_count: usize,
+}
+
+impl ResponsibleNode {
+    pub fn last_seen_local_time(&self) -> String {
+        match self.last_seen {
+            Some(last_seen) => last_seen.with_timezone(&chrono::Local).to_rfc2822(),
+            None => "Never".to_string(),
+        }
+    }
+
+    /// Whether the content falls within the latest radius the node advertised.
+    pub fn is_within_radius(&self) -> Option<bool> {
+        self.radius
+            .as_ref()
+            .map(|radius| self.node.distance <= radius.get_data_radius())
+    }
+}
+
+/// Finds the known nodes closest to the content, along with what traced audits observed of them.
+async fn get_responsible_nodes(
+    content_id: [u8; 32],
+    audits: &[content_audit::Model],
+    conn: &DatabaseConnection,
+) -> Result<Vec<ResponsibleNode>, StatusCode> {
+    const RESPONSIBLE_COUNT: u64 = 20;
+    let closest_nodes = node::closest_to_content(content_id, RESPONSIBLE_COUNT, conn)
+        .await
+        .map_err(|e| {
+            error!(content.id=hex_encode(content_id), err=?e, "Could not look up closest nodes");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let mut observation_counts: HashMap<String, (usize, usize)> = HashMap::new();
+    for audit in audits {
+        for (node_id, observation) in audit.trace_observations() {
+            let counts = observation_counts.entry(node_id).or_default();
+            match observation {
+                TraceObservation::Served => counts.0 += 1,
+                TraceObservation::Missing => counts.1 += 1,
+            }
+        }
+    }
+
+    let node_ids: Vec<i32> = closest_nodes.iter().map(|node| node.id).collect();
+    let mut last_seen = node_liveness::get_last_seen_for_nodes(&node_ids, conn)
+        .await
+        .map_err(|e| {
+            error!(content.id=hex_encode(content_id), err=?e, "Could not look up last seen times");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    let mut radii = node_radius::get_latest_for_nodes(&node_ids, conn)
+        .await
+        .map_err(|e| {
+            error!(content.id=hex_encode(content_id), err=?e, "Could not look up data radii");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let mut responsible_nodes = Vec::with_capacity(closest_nodes.len());
+    for node in closest_nodes {
+        let last_seen = last_seen.remove(&node.id);
+        let radius = radii.remove(&node.id);
+        let (served_count, missing_count) = observation_counts
+            .get(&node.node_id_as_hex())
+            .copied()
+            .unwrap_or_default();
+        responsible_nodes.push(ResponsibleNode {
+            node,
+            last_seen,
+            radius,
+            served_count,
+            missing_count,
+        });
+    }
+    Ok(responsible_nodes)
+}
+
+/// The nodes and audited content in one keyspace bucket, laid out for the keyspace chart.
+pub struct KeyspaceBucket {
+    /// The shared prefix of the ids in the bucket, in binary.
+    pub prefix: String,
+    pub node_count: u64,
+    pub audits: content_audit::AuditCounts,
+    /// Horizontal position of the bucket in the chart.
+    pub x: usize,
+    /// Height of the node density bar, scaled to the fullest bucket.
+    pub node_bar_height: u64,
+    /// Height of the audit success bar.
+    pub audit_bar_height: u64,
+    pub audit_bar_color: String,
+}
+
+impl KeyspaceBucket {
+    pub const BAR_WIDTH: usize = 10;
+    pub const CHART_HEIGHT: u64 = 200;
+
+    fn new(
+        index: usize,
+        prefix_bits: u8,
+        node_count: u64,
+        max_node_count: u64,
+        audits: content_audit::AuditCounts,
+    ) -> Self {
+        let node_bar_height = (node_count * Self::CHART_HEIGHT)
+            .checked_div(max_node_count)
+            .unwrap_or(0);
+        let (audit_bar_height, audit_bar_color) = match audits.success_rate() {
+            // Shades from red for no passes to green for all passes.
+            Some(rate) => (
+                (rate / 100.0 * Self::CHART_HEIGHT as f64) as u64,
+                format!("hsl({:.0}, 70%, 45%)", rate * 1.2),
+            ),
+            // Show unaudited buckets as a full grey bar, so they stand out.
+            None => (Self::CHART_HEIGHT, "#cccccc".to_string()),
+        };
+        KeyspaceBucket {
+            prefix: format!("{index:0width$b}", width = prefix_bits as usize),
+            node_count,
+            audits,
+            x: index * Self::BAR_WIDTH,
+            node_bar_height,
+            audit_bar_height,
+            audit_bar_color,
+        }
+    }
+}
+
 pub enum Period {
     Hour,
     Day,
@@ -587,3 +1256,101 @@ async fn get_audit_stats(period: Period, conn: &DatabaseConnection) -> Result<St
         audits_per_minute,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+    use ethportal_api::types::content_key::BlockBodyKey;
+    use glados_test_support::setup_database;
+
+    use super::*;
+
+    const TOKEN: &str = "secret";
+
+    #[test]
+    fn test_tokens_match() {
+        assert!(tokens_match(TOKEN, TOKEN));
+        assert!(tokens_match("", ""));
+        assert!(!tokens_match("secreT", TOKEN));
+        assert!(!tokens_match("secre", TOKEN));
+        assert!(!tokens_match("secrets", TOKEN));
+        assert!(!tokens_match("", TOKEN));
+    }
+
+    /// Sets up a database holding one content key, and returns its hex encoding.
+    async fn setup_state(audit_request_token: Option<&str>) -> (Arc<State>, String) {
+        let database_connection = setup_database().await;
+        let content_key = HistoryContentKey::BlockBody(BlockBodyKey {
+            block_hash: [1; 32],
+        });
+        content::get_or_create(&content_key, &database_connection)
+            .await
+            .unwrap();
+        let state = State {
+            database_connection,
+            audit_request_token: audit_request_token.map(str::to_owned),
+        };
+        (Arc::new(state), hex_encode(content_key.to_bytes()))
+    }
+
+    async fn request_audit(
+        state: &Arc<State>,
+        content_key_hex: &str,
+        bearer_token: Option<&str>,
+        form_token: Option<&str>,
+    ) -> Result<Redirect, StatusCode> {
+        let mut headers = HeaderMap::new();
+        if let Some(token) = bearer_token {
+            let value = HeaderValue::from_str(&format!("Bearer {token}")).unwrap();
+            headers.insert(header::AUTHORIZATION, value);
+        }
+        let form = Form(AuditRequestForm {
+            token: form_token.map(str::to_owned),
+        });
+        contentkey_request_audit(
+            Path(content_key_hex.to_owned()),
+            Extension(state.clone()),
+            headers,
+            Some(form),
+        )
+        .await
+    }
+
+    async fn queue_length(state: &State) -> u64 {
+        audit_queue::Entity::find()
+            .count(&state.database_connection)
+            .await
+            .unwrap()
+    }
+
+    /// Tests that audits cannot be requested when no token is configured.
+    #[tokio::test]
+    async fn test_request_audit_disabled() {
+        let (state, content_key_hex) = setup_state(None).await;
+        let response = request_audit(&state, &content_key_hex, Some(TOKEN), None).await;
+        assert_eq!(response.err(), Some(StatusCode::FORBIDDEN));
+        assert_eq!(queue_length(&state).await, 0);
+    }
+
+    /// Tests that requests with a missing or wrong token are rejected.
+    #[tokio::test]
+    async fn test_request_audit_wrong_token() {
+        let (state, content_key_hex) = setup_state(Some(TOKEN)).await;
+        for (bearer_token, form_token) in [(None, None), (Some("wrong"), None), (None, Some(""))] {
+            let response = request_audit(&state, &content_key_hex, bearer_token, form_token).await;
+            assert_eq!(response.err(), Some(StatusCode::UNAUTHORIZED));
+        }
+        assert_eq!(queue_length(&state).await, 0);
+    }
+
+    /// Tests that asking again for an audit that is already waiting does not queue another.
+    #[tokio::test]
+    async fn test_request_audit_already_queued() {
+        let (state, content_key_hex) = setup_state(Some(TOKEN)).await;
+        let response = request_audit(&state, &content_key_hex, Some(TOKEN), None).await;
+        assert!(response.is_ok());
+        let response = request_audit(&state, &content_key_hex, None, Some(TOKEN)).await;
+        assert!(response.is_ok());
+        assert_eq!(queue_length(&state).await, 1);
+    }
+}
@@ -2,4 +2,6 @@ use sea_orm::DatabaseConnection;
 
 pub struct State {
     pub database_connection: DatabaseConnection,
+    /// Token that must accompany requests to audit content, if requests are allowed.
+    pub audit_request_token: Option<String>,
 }
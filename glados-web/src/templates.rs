@@ -1,12 +1,21 @@
+use std::collections::BTreeMap;
+
 use askama::Template;
 use axum::{
     http::StatusCode,
     response::{Html, IntoResponse, Response},
 };
 
-use entity::{content, content_audit, key_value, node, record};
+use entity::{
+    audit_queue, census, content,
+    content_audit::{self, AuditCounts},
+    execution_metadata::BlockAgeBand,
+    key_value, node,
+    node_liveness::{self, LivenessSummary},
+    node_radius, node_storage_audit, record,
+};
 
-use crate::routes::Stats;
+use crate::routes::{KeyspaceBucket, Period, ResponsibleNode, Stats};
 
 #[derive(Template)]
 #[template(path = "index.html")]
@@ -17,10 +26,50 @@ pub struct IndexTemplate {}
 pub struct NetworkDashboardTemplate {
     pub total_node_count: u64,
     pub total_enr_count: u64,
-    pub recent_node_list: Vec<node::Model>,
+    /// The most recent finished census and the number of nodes it found.
+    pub latest_census: Option<(census::Model, u64)>,
+    /// Number of known nodes by the client named in their latest ENR.
+    pub client_counts: BTreeMap<String, u64>,
+    /// Clients seen by recent censuses, which are the columns of `client_history`.
+    pub client_names: Vec<String>,
+    /// Recent censuses, oldest first, with the number of nodes found running each client.
+    pub client_history: Vec<(census::Model, Vec<u64>)>,
+    /// Recently added nodes, each with its latest liveness check if it has been checked.
+    pub recent_node_list: Vec<(node::Model, Option<node_liveness::Model>)>,
     pub recent_enr_list: Vec<(record::Model, node::Model)>,
 }
 
+#[derive(Template)]
+#[template(path = "census_list.html")]
+pub struct CensusListTemplate {
+    /// Finished censuses, newest first, with their node count and the census before each.
+    pub census_list: Vec<(census::Model, u64, Option<i32>)>,
+}
+
+#[derive(Template)]
+#[template(path = "census_diff.html")]
+pub struct CensusDiffTemplate {
+    pub old_census: census::Model,
+    pub new_census: census::Model,
+    pub diff: census::CensusDiff,
+}
+
+#[derive(Template)]
+#[template(path = "window_diff.html")]
+pub struct WindowDiffTemplate {
+    /// Length of each of the compared windows.
+    pub hours: i64,
+    pub diff: census::WindowDiff,
+}
+
+#[derive(Template)]
+#[template(path = "keyspace.html")]
+pub struct KeyspaceTemplate {
+    pub period: Period,
+    pub prefix_bits: u8,
+    pub bucket_list: Vec<KeyspaceBucket>,
+}
+
 #[derive(Template)]
 #[template(path = "node_detail.html")]
 pub struct NodeDetailTemplate {
@@ -29,6 +78,11 @@ pub struct NodeDetailTemplate {
     pub latest_enr_key_value_list: Option<Vec<key_value::Model>>,
     pub enr_list: Vec<record::Model>,
     pub closest_node_list: Vec<node::ModelWithDistance>,
+    pub radius_list: Vec<node_radius::Model>,
+    pub liveness: LivenessSummary,
+    pub liveness_list: Vec<node_liveness::Model>,
+    /// Recent audits of whether the node stores content in its radius, with that content.
+    pub storage_audit_list: Vec<(node_storage_audit::Model, Option<content::Model>)>,
 }
 
 #[derive(Template)]
@@ -48,6 +102,7 @@ pub struct ContentDashboardTemplate {
     pub recent_audits: Vec<(content::Model, content_audit::Model)>,
     pub recent_audit_successes: Vec<(content::Model, content_audit::Model)>,
     pub recent_audit_failures: Vec<(content::Model, content_audit::Model)>,
+    pub block_age_stats: Vec<(BlockAgeBand, AuditCounts)>,
 }
 
 #[derive(Template)]
@@ -61,6 +116,7 @@ pub struct ContentIdListTemplate {
 pub struct ContentIdDetailTemplate {
     pub content_id: content::Model,
     pub contentkey_list: Vec<content::Model>,
+    pub responsible_node_list: Vec<ResponsibleNode>,
 }
 
 #[derive(Template)]
@@ -85,6 +141,12 @@ pub struct ContentKeyDetailTemplate {
     pub content_kind: String,
     pub block_number: Option<i32>,
     pub contentaudit_list: Vec<content_audit::Model>,
+    pub responsible_node_list: Vec<ResponsibleNode>,
+    /// Recent audits of whether responsible nodes store the content, with the audited node.
+    pub storage_audit_list: Vec<(node_storage_audit::Model, Option<node::Model>)>,
+    /// An audit that was asked for and has not been performed yet.
+    pub requested_audit: Option<audit_queue::Model>,
+    pub audit_requests_enabled: bool,
 }
 
 pub struct HtmlTemplate<T: Template>(pub T);
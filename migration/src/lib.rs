@@ -8,6 +8,16 @@ mod m20230404_163706_create_new_record_tables;
 mod m20230421_120832_add_audit_trace;
 mod m20230501_153121_add_node_id_high_bits_column_to_node_id;
 mod m20230503_131114_add_client_info;
+mod m20230601_120000_create_node_storage_audit;
+mod m20230602_120000_create_node_radius;
+mod m20230603_120000_create_node_liveness;
+mod m20230604_120000_create_census;
+mod m20230605_120000_add_record_column_to_census_node;
+mod m20230605_130000_add_record_column_to_node_liveness;
+mod m20230606_120000_add_decoded_enr_columns_to_record;
+mod m20230607_120000_add_client_info_column_to_node;
+mod m20230608_120000_create_audit_queue;
+mod m20230609_120000_create_backfill;
 
 pub struct Migrator;
 
@@ -23,6 +33,16 @@ impl MigratorTrait for Migrator {
             Box::new(m20230421_120832_add_audit_trace::Migration),
             Box::new(m20230501_153121_add_node_id_high_bits_column_to_node_id::Migration),
             Box::new(m20230503_131114_add_client_info::Migration),
+            Box::new(m20230601_120000_create_node_storage_audit::Migration),
+            Box::new(m20230602_120000_create_node_radius::Migration),
+            Box::new(m20230603_120000_create_node_liveness::Migration),
+            Box::new(m20230604_120000_create_census::Migration),
+            Box::new(m20230605_120000_add_record_column_to_census_node::Migration),
+            Box::new(m20230605_130000_add_record_column_to_node_liveness::Migration),
+            Box::new(m20230606_120000_add_decoded_enr_columns_to_record::Migration),
+            Box::new(m20230607_120000_add_client_info_column_to_node::Migration),
+            Box::new(m20230608_120000_create_audit_queue::Migration),
+            Box::new(m20230609_120000_create_backfill::Migration),
         ]
     }
 }
@@ -0,0 +1,131 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(NodeStorageAudit::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(NodeStorageAudit::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(NodeStorageAudit::ContentKey)
+                            .integer()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("FK_nodestorageaudit_content")
+                            .from(NodeStorageAudit::Table, NodeStorageAudit::ContentKey)
+                            .to(Content::Table, Content::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .col(ColumnDef::new(NodeStorageAudit::Node).integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("FK_nodestorageaudit_node")
+                            .from(NodeStorageAudit::Table, NodeStorageAudit::Node)
+                            .to(Node::Table, Node::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .col(
+                        ColumnDef::new(NodeStorageAudit::Record)
+                            .integer()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("FK_nodestorageaudit_record")
+                            .from(NodeStorageAudit::Table, NodeStorageAudit::Record)
+                            .to(Record::Table, Record::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .col(
+                        ColumnDef::new(NodeStorageAudit::CreatedAt)
+                            .timestamp_with_time_zone() // chrono::DateTime<FixedOffset>
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(NodeStorageAudit::StrategyUsed).integer())
+                    .col(
+                        ColumnDef::new(NodeStorageAudit::Result)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(NodeStorageAudit::DataRadius)
+                            .binary_len(32)
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_nodestorageaudit-content_key")
+                    .table(NodeStorageAudit::Table)
+                    .col(NodeStorageAudit::ContentKey)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_nodestorageaudit-node")
+                    .table(NodeStorageAudit::Table)
+                    .col(NodeStorageAudit::Node)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(NodeStorageAudit::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum NodeStorageAudit {
+    Table,
+    Id,
+    ContentKey,
+    Node,
+    Record,
+    CreatedAt,
+    StrategyUsed,
+    Result,
+    DataRadius,
+}
+
+#[derive(Iden)]
+enum Content {
+    Table,
+    Id,
+}
+
+#[derive(Iden)]
+enum Node {
+    Table,
+    Id,
+}
+
+#[derive(Iden)]
+enum Record {
+    Table,
+    Id,
+}
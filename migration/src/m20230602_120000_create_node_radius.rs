@@ -0,0 +1,75 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(NodeRadius::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(NodeRadius::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(NodeRadius::Node).integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("FK_noderadius_node")
+                            .from(NodeRadius::Table, NodeRadius::Node)
+                            .to(Node::Table, Node::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .col(
+                        ColumnDef::new(NodeRadius::DataRadius)
+                            .binary_len(32)
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(NodeRadius::CreatedAt)
+                            .timestamp_with_time_zone() // chrono::DateTime<FixedOffset>
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_noderadius-node-created_at")
+                    .table(NodeRadius::Table)
+                    .col(NodeRadius::Node)
+                    .col(NodeRadius::CreatedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(NodeRadius::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum NodeRadius {
+    Table,
+    Id,
+    Node,
+    DataRadius,
+    CreatedAt,
+}
+
+#[derive(Iden)]
+enum Node {
+    Table,
+    Id,
+}
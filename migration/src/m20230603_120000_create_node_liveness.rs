@@ -0,0 +1,73 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(NodeLiveness::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(NodeLiveness::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(NodeLiveness::Node).integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("FK_nodeliveness_node")
+                            .from(NodeLiveness::Table, NodeLiveness::Node)
+                            .to(Node::Table, Node::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .col(ColumnDef::new(NodeLiveness::IsAlive).boolean().not_null())
+                    .col(ColumnDef::new(NodeLiveness::LatencyMs).integer())
+                    .col(
+                        ColumnDef::new(NodeLiveness::CreatedAt)
+                            .timestamp_with_time_zone() // chrono::DateTime<FixedOffset>
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_nodeliveness-node-created_at")
+                    .table(NodeLiveness::Table)
+                    .col(NodeLiveness::Node)
+                    .col(NodeLiveness::CreatedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(NodeLiveness::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum NodeLiveness {
+    Table,
+    Id,
+    Node,
+    IsAlive,
+    LatencyMs,
+    CreatedAt,
+}
+
+#[derive(Iden)]
+enum Node {
+    Table,
+    Id,
+}
@@ -0,0 +1,106 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Census::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Census::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(Census::StartedAt)
+                            .timestamp_with_time_zone() // chrono::DateTime<FixedOffset>
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(Census::EndedAt).timestamp_with_time_zone())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_table(
+                Table::create()
+                    .table(CensusNode::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(CensusNode::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(CensusNode::Census).integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("FK_censusnode_census")
+                            .from(CensusNode::Table, CensusNode::Census)
+                            .to(Census::Table, Census::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .col(ColumnDef::new(CensusNode::Node).integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("FK_censusnode_node")
+                            .from(CensusNode::Table, CensusNode::Node)
+                            .to(Node::Table, Node::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_censusnode-census-node")
+                    .table(CensusNode::Table)
+                    .col(CensusNode::Census)
+                    .col(CensusNode::Node)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(CensusNode::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Census::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum Census {
+    Table,
+    Id,
+    StartedAt,
+    EndedAt,
+}
+
+#[derive(Iden)]
+enum CensusNode {
+    Table,
+    Id,
+    Census,
+    Node,
+}
+
+#[derive(Iden)]
+enum Node {
+    Table,
+    Id,
+}
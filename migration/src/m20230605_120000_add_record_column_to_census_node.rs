@@ -0,0 +1,54 @@
+use sea_orm_migration::{prelude::*, sea_orm::DatabaseBackend};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Add a column for the ENR that each node had when the census found it.
+        // Values pre-migration will be null.
+        let mut alter = Table::alter()
+            .table(CensusNode::Table)
+            .add_column_if_not_exists(ColumnDef::new(CensusNode::Record).integer()) // i32
+            .to_owned();
+        // Postgres supports adding foreign keys in later migrations.
+        if manager.get_database_backend() == DatabaseBackend::Postgres {
+            alter.add_foreign_key(
+                TableForeignKey::new()
+                    .name("FK_censusnode_record")
+                    .from_tbl(CensusNode::Table)
+                    .from_col(CensusNode::Record)
+                    .to_tbl(Record::Table)
+                    .to_col(Record::Id)
+                    .on_delete(ForeignKeyAction::Cascade)
+                    .on_update(ForeignKeyAction::Cascade),
+            );
+        }
+        manager.alter_table(alter).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CensusNode::Table)
+                    .drop_column(CensusNode::Record)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+/// Old table, but with new column to add.
+#[derive(Iden)]
+enum CensusNode {
+    Table,
+    Record, // Foreign key to the record table. Can be null (for entries predating this column).
+}
+
+#[derive(Iden)]
+enum Record {
+    Table,
+    Id,
+}
@@ -0,0 +1,54 @@
+use sea_orm_migration::{prelude::*, sea_orm::DatabaseBackend};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Add a column for the ENR that was used to reach the node.
+        // Values pre-migration will be null.
+        let mut alter = Table::alter()
+            .table(NodeLiveness::Table)
+            .add_column_if_not_exists(ColumnDef::new(NodeLiveness::Record).integer()) // i32
+            .to_owned();
+        // Postgres supports adding foreign keys in later migrations.
+        if manager.get_database_backend() == DatabaseBackend::Postgres {
+            alter.add_foreign_key(
+                TableForeignKey::new()
+                    .name("FK_nodeliveness_record")
+                    .from_tbl(NodeLiveness::Table)
+                    .from_col(NodeLiveness::Record)
+                    .to_tbl(Record::Table)
+                    .to_col(Record::Id)
+                    .on_delete(ForeignKeyAction::Cascade)
+                    .on_update(ForeignKeyAction::Cascade),
+            );
+        }
+        manager.alter_table(alter).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(NodeLiveness::Table)
+                    .drop_column(NodeLiveness::Record)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+/// Old table, but with new column to add.
+#[derive(Iden)]
+enum NodeLiveness {
+    Table,
+    Record, // Foreign key to the record table. Can be null (for checks predating this column).
+}
+
+#[derive(Iden)]
+enum Record {
+    Table,
+    Id,
+}
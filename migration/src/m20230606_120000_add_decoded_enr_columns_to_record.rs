@@ -0,0 +1,84 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Add columns for the well-known ENR keys, decoded from the raw ENR.
+        // Values pre-migration will be null until they are decoded.
+        // SQLite only allows one column to be added per statement.
+        let columns = [
+            ColumnDef::new(Record::IdentityScheme).string().to_owned(), // `id`
+            ColumnDef::new(Record::PublicKey).binary().to_owned(),      // `secp256k1`, compressed
+            ColumnDef::new(Record::Ip).string().to_owned(),             // `ip`, dotted decimal
+            ColumnDef::new(Record::Ip6).string().to_owned(),            // `ip6`
+            ColumnDef::new(Record::Udp).integer().to_owned(),           // `udp`
+            ColumnDef::new(Record::Tcp).integer().to_owned(),           // `tcp`
+            ColumnDef::new(Record::Client).string().to_owned(),         // `c`
+        ];
+        for mut column in columns {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Record::Table)
+                        .add_column_if_not_exists(&mut column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_record-ip")
+                    .table(Record::Table)
+                    .col(Record::Ip)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_record-ip")
+                    .table(Record::Table)
+                    .to_owned(),
+            )
+            .await?;
+        for column in [
+            Record::IdentityScheme,
+            Record::PublicKey,
+            Record::Ip,
+            Record::Ip6,
+            Record::Udp,
+            Record::Tcp,
+            Record::Client,
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Record::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+/// Old table, but with new columns to add.
+#[derive(Iden)]
+enum Record {
+    Table,
+    IdentityScheme,
+    PublicKey,
+    Ip,
+    Ip6,
+    Udp,
+    Tcp,
+    Client,
+}
@@ -0,0 +1,54 @@
+use sea_orm_migration::{prelude::*, sea_orm::DatabaseBackend};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Add a column for the client implementation that the node advertises in its ENR.
+        // Values pre-migration will be null.
+        let mut alter = Table::alter()
+            .table(Node::Table)
+            .add_column_if_not_exists(ColumnDef::new(Node::ClientInfo).integer()) // i32
+            .to_owned();
+        // Postgres supports adding foreign keys in later migrations.
+        if manager.get_database_backend() == DatabaseBackend::Postgres {
+            alter.add_foreign_key(
+                TableForeignKey::new()
+                    .name("FK_node_client_info")
+                    .from_tbl(Node::Table)
+                    .from_col(Node::ClientInfo)
+                    .to_tbl(ClientInfo::Table)
+                    .to_col(ClientInfo::Id)
+                    .on_delete(ForeignKeyAction::SetNull)
+                    .on_update(ForeignKeyAction::Cascade),
+            );
+        }
+        manager.alter_table(alter).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Node::Table)
+                    .drop_column(Node::ClientInfo)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+/// Old table, but with new column to add.
+#[derive(Iden)]
+enum Node {
+    Table,
+    ClientInfo, // Foreign key. Can be null (for nodes that do not advertise a client).
+}
+
+#[derive(Iden)]
+enum ClientInfo {
+    Table,
+    Id,
+}
@@ -0,0 +1,86 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(AuditQueue::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(AuditQueue::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(AuditQueue::ContentKey).integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("FK_auditqueue_content")
+                            .from(AuditQueue::Table, AuditQueue::ContentKey)
+                            .to(Content::Table, Content::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .col(ColumnDef::new(AuditQueue::Strategy).integer().not_null())
+                    .col(ColumnDef::new(AuditQueue::Priority).integer().not_null())
+                    .col(
+                        ColumnDef::new(AuditQueue::CreatedAt)
+                            .timestamp_with_time_zone() // chrono::DateTime<FixedOffset>
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(AuditQueue::ClaimedAt).timestamp_with_time_zone())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_auditqueue-priority-created_at")
+                    .table(AuditQueue::Table)
+                    .col(AuditQueue::Priority)
+                    .col(AuditQueue::CreatedAt)
+                    .to_owned(),
+            )
+            .await?;
+        // Content is queued at most once, however many auditors select it.
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_auditqueue-content_key")
+                    .table(AuditQueue::Table)
+                    .col(AuditQueue::ContentKey)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(AuditQueue::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum AuditQueue {
+    Table,
+    Id,
+    ContentKey,
+    Strategy,
+    Priority,
+    CreatedAt,
+    ClaimedAt,
+}
+
+#[derive(Iden)]
+enum Content {
+    Table,
+    Id,
+}
@@ -0,0 +1,52 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Backfills fill in data saved before a column existed. Each one is recorded here once
+        // it finishes, so that it does not run again.
+        manager
+            .create_table(
+                Table::create()
+                    .table(Backfill::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Backfill::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(Backfill::Name)
+                            .string()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(
+                        ColumnDef::new(Backfill::CompletedAt)
+                            .timestamp_with_time_zone() // chrono::DateTime<FixedOffset>
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Backfill::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum Backfill {
+    Table,
+    Id,
+    Name,
+    CompletedAt,
+}